@@ -0,0 +1,63 @@
+extern crate ggez;
+use ggez::conf;
+use ggez::event;
+use ggez::{Context, GameResult};
+use ggez::graphics;
+use ggez::graphics::{DrawMode, Point};
+use std::time::Duration;
+
+struct MainState {
+    canvas: graphics::Canvas,
+    text: graphics::Text,
+    angle: f32,
+}
+
+impl MainState {
+    fn new(ctx: &mut Context) -> GameResult<MainState> {
+        let canvas = graphics::Canvas::new(ctx, 256, 256)?;
+        let font = graphics::Font::new(ctx, "/DejaVuSerif.ttf", 24)?;
+        let text = graphics::Text::new(ctx, "Offscreen!", &font)?;
+        let s = MainState {
+            canvas: canvas,
+            text: text,
+            angle: 0.0,
+        };
+        Ok(s)
+    }
+}
+
+impl event::EventHandler for MainState {
+    fn update(&mut self, _ctx: &mut Context, _dt: Duration) -> GameResult<()> {
+        self.angle += 0.01;
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        // First draw a little scene onto the canvas...
+        graphics::set_canvas(ctx, Some(&self.canvas));
+        graphics::set_background_color(ctx, graphics::Color::new(0.2, 0.0, 0.0, 1.0));
+        graphics::clear(ctx);
+        graphics::circle(ctx, DrawMode::Fill, Point::new(64.0, 64.0), 40.0, 1.0)?;
+        graphics::draw(ctx, &self.text, Point::new(128.0, 128.0), 0.0)?;
+
+        // ...then draw the canvas to the screen a few times.
+        graphics::set_canvas(ctx, None);
+        graphics::set_background_color(ctx, graphics::Color::new(0.1, 0.2, 0.3, 1.0));
+        graphics::clear(ctx);
+        graphics::draw(ctx, &self.canvas, Point::new(200.0, 300.0), self.angle)?;
+        graphics::draw(ctx, &self.canvas, Point::new(600.0, 300.0), -self.angle)?;
+        graphics::present(ctx);
+        Ok(())
+    }
+}
+
+pub fn main() {
+    let c = conf::Conf::new();
+    let ctx = &mut Context::load_from_conf("canvas", "ggez", c).unwrap();
+    let state = &mut MainState::new(ctx).unwrap();
+    if let Err(e) = event::run(ctx, state) {
+        println!("Error encountered: {}", e);
+    } else {
+        println!("Game exited cleanly.");
+    }
+}
@@ -71,6 +71,7 @@ impl event::EventHandler for MainState {
                 // offset: Point::new(-16.0, 0.0),
                 scale: scale,
                 // shear: shear,
+                color: Some(graphics::Color::new(1.0, 0.5, 0.5, 0.75)),
                 ..Default::default()
             },
         )?;
@@ -103,6 +104,33 @@ impl event::EventHandler for MainState {
 
         graphics::circle(ctx, DrawMode::Fill, Point { x: 600.0, y: 380.0 }, 40.0, 1.0)?;
 
+        graphics::arc(
+            ctx,
+            DrawMode::Fill,
+            graphics::ArcType::Pie,
+            Point { x: 700.0, y: 100.0 },
+            40.0,
+            0.5,
+            5.5,
+            1.0,
+        )?;
+        graphics::rounded_rectangle(
+            ctx,
+            DrawMode::Line,
+            graphics::Rect::new(700.0, 220.0, 80.0, 60.0),
+            15.0,
+            1.0,
+        )?;
+        graphics::cubic_bezier(
+            ctx,
+            DrawMode::Line,
+            Point { x: 100.0, y: 500.0 },
+            Point { x: 150.0, y: 400.0 },
+            Point { x: 250.0, y: 600.0 },
+            Point { x: 300.0, y: 500.0 },
+            0.5,
+        )?;
+
         graphics::present(ctx);
         Ok(())
     }
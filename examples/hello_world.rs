@@ -7,6 +7,7 @@ use std::time::Duration;
 
 // First we make a structure to contain the game's state
 struct MainState {
+    font: graphics::Font,
     text: graphics::Text,
     frames: usize,
 }
@@ -22,6 +23,7 @@ impl MainState {
         let text = graphics::Text::new(ctx, "Hello world!", &font)?;
 
         let s = MainState {
+            font: font,
             text: text,
             frames: 0,
         };
@@ -43,6 +45,11 @@ impl event::EventHandler for MainState {
             self.text.height() as f32 / 2.0 + 10.0,
         );
         graphics::draw(ctx, &self.text, dest_point, 0.0)?;
+        // Text that changes every frame is better drawn through
+        // the glyph cache than by creating a new `Text` each time.
+        let fps = format!("FPS: {:.0}", ggez::timer::get_fps(ctx));
+        graphics::queue_text(ctx, &fps, &self.font, graphics::Point::new(10.0, 80.0), None)?;
+        graphics::draw_queued_text(ctx, graphics::DrawParam::default())?;
         graphics::present(ctx);
         self.frames += 1;
         if (self.frames % 100) == 0 {
@@ -0,0 +1,89 @@
+//! A very simple shader example.
+
+#[macro_use]
+extern crate gfx;
+extern crate ggez;
+
+use ggez::conf;
+use ggez::event;
+use ggez::{Context, GameResult};
+use ggez::graphics::{self, DrawMode, Point};
+use ggez::timer;
+use std::time::Duration;
+
+gfx_defines!{
+    constant Dim {
+        rate: f32 = "u_Rate",
+    }
+}
+
+const PIXEL_SHADER: &[u8] = b"
+#version 150 core
+
+uniform sampler2D t_Texture;
+in vec2 v_Uv;
+out vec4 Target0;
+
+layout (std140) uniform Globals {
+    mat4 u_Transform;
+    vec4 u_Color;
+};
+
+layout (std140) uniform Dim {
+    float u_Rate;
+};
+
+void main() {
+    Target0 = texture(t_Texture, v_Uv) * u_Color * u_Rate;
+}
+";
+
+struct MainState {
+    dim: Dim,
+    shader: graphics::Shader<Dim>,
+}
+
+impl MainState {
+    fn new(ctx: &mut Context) -> GameResult<MainState> {
+        let dim = Dim { rate: 0.5 };
+        let shader = graphics::Shader::from_u8(ctx, None, PIXEL_SHADER, dim, "Dim")?;
+        Ok(MainState {
+            dim: dim,
+            shader: shader,
+        })
+    }
+}
+
+impl event::EventHandler for MainState {
+    fn update(&mut self, ctx: &mut Context, _dt: Duration) -> GameResult<()> {
+        let t = timer::duration_to_f64(timer::get_time_since_start(ctx)) as f32;
+        self.dim.rate = 0.5 + (t.cos() / 2.0);
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        graphics::clear(ctx);
+
+        graphics::circle(ctx, DrawMode::Fill, Point::new(100.0, 300.0), 100.0, 2.0)?;
+        {
+            let _lock = graphics::use_shader(ctx, &self.shader);
+            self.shader.send(ctx, self.dim)?;
+            graphics::circle(ctx, DrawMode::Fill, Point::new(400.0, 300.0), 100.0, 2.0)?;
+        }
+        graphics::circle(ctx, DrawMode::Fill, Point::new(700.0, 300.0), 100.0, 2.0)?;
+
+        graphics::present(ctx);
+        Ok(())
+    }
+}
+
+pub fn main() {
+    let c = conf::Conf::new();
+    let ctx = &mut Context::load_from_conf("shader", "ggez", c).unwrap();
+    let state = &mut MainState::new(ctx).unwrap();
+    if let Err(e) = event::run(ctx, state) {
+        println!("Error encountered: {}", e);
+    } else {
+        println!("Game exited cleanly.");
+    }
+}
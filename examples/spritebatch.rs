@@ -17,7 +17,22 @@ struct MainState {
 impl MainState {
     fn new(ctx: &mut Context) -> GameResult<MainState> {
         let image = graphics::Image::new(ctx, "/tile.png").unwrap();
-        let batch = graphics::spritebatch::SpriteBatch::new(image);
+        let mut batch = graphics::spritebatch::SpriteBatch::with_capacity(image, 150 * 150);
+        // The batch only gets sent to the graphics card once, since it
+        // never changes after this; moving it around each frame is
+        // done with the DrawParam passed to `draw_ex()`.
+        for x in 0..150 {
+            for y in 0..150 {
+                let color = graphics::Color::new(x as f32 / 150.0, y as f32 / 150.0, 1.0, 1.0);
+                let p = graphics::DrawParam {
+                    dest: graphics::Point::new(x as f32 * 10.0, y as f32 * 10.0),
+                    scale: graphics::Point::new(0.0625, 0.0625),
+                    color: Some(color),
+                    .. Default::default()
+                };
+                batch.add(p);
+            }
+        }
         let s = MainState {
             spritebatch: batch
         };
@@ -39,17 +54,14 @@ impl event::EventHandler for MainState {
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         graphics::clear(ctx);
 
-        for x in 0..10 {
-            for y in 0..10 {
-                let p = graphics::DrawParam {
-                    dest: graphics::Point::new(x as f32, y as f32),
-                    .. Default::default()
-                };
-                self.spritebatch.add(p);
-            }
-        }
-        graphics::draw_ex(ctx, &self.spritebatch, Default::default())?;
-        self.spritebatch.clear();
+        let time = timer::duration_to_f64(timer::get_time_since_start(ctx)) as f32;
+        let param = graphics::DrawParam {
+            dest: graphics::Point::new(400.0, 300.0),
+            offset: graphics::Point::new(-750.0, -750.0),
+            rotation: time * 0.1,
+            .. Default::default()
+        };
+        graphics::draw_ex(ctx, &self.spritebatch, param)?;
 
         graphics::present(ctx);
         Ok(())
@@ -63,7 +75,7 @@ impl event::EventHandler for MainState {
 pub fn main() {
     let c = conf::Conf::new();
     println!("Starting with default config: {:#?}", c);
-    let ctx = &mut Context::load_from_conf("imageview", "ggez", c).unwrap();
+    let ctx = &mut Context::load_from_conf("spritebatch", "ggez", c).unwrap();
     let state = &mut MainState::new(ctx).unwrap();
     if let Err(e) = event::run(ctx, state) {
         println!("Error encountered: {}", e);
@@ -33,6 +33,28 @@ pub struct Conf {
     pub vsync: bool,
     #[default = "true"]
     pub resizable: bool,
+    /// Whether to run without a visible window, rendering into an
+    /// off-screen target instead.  Useful for testing rendering
+    /// code on machines without a display; see `graphics::is_headless()`.
+    ///
+    /// Unless the `SDL_VIDEODRIVER` environment variable says
+    /// otherwise, this uses SDL's "offscreen" video driver, which
+    /// needs SDL 2.0.10 or newer; creating the `Context` fails with
+    /// a `VideoError` if SDL doesn't have it.
+    #[default = "false"]
+    pub headless: bool,
+    /// Whether to ask for a full-resolution drawable on high-DPI
+    /// displays such as retina screens.  Screen coordinates stay
+    /// the same, so things are drawn at the same size but more
+    /// sharply; see `graphics::get_dpi_scale()`.
+    #[default = "false"]
+    pub hidpi: bool,
+    /// Whether to pad images out to power-of-two sizes when they are
+    /// loaded, for old drivers that can't handle other sizes well.
+    /// This wastes memory, and `WrapMode::Tile` samples the padding,
+    /// so leave it off unless you need it.
+    #[default = "false"]
+    pub power_of_two_textures: bool,
     /* To implement still.
      * window_borderless: bool,
      * window_resizable: bool,
@@ -3,6 +3,7 @@ use sdl2::surface;
 use sdl2::pixels;
 use image::{self, GenericImage};
 
+use std::env;
 use std::fmt;
 use std::io::Read;
 
@@ -75,10 +76,33 @@ fn set_window_icon(context: &mut Context) -> GameResult<()> {
     Ok(())
 }
 
+/// Starts up SDL, with the video driver the conf's `headless`
+/// field asks for.
+fn init_sdl(conf: &conf::Conf) -> GameResult<Sdl> {
+    // SDL picks its video driver when it starts up, so this
+    // has to happen first.  An explicitly set driver wins, so
+    // e.g. running under Xvfb still works.
+    if conf.headless && env::var_os("SDL_VIDEODRIVER").is_none() {
+        // SDL only has this driver since 2.0.10; without it, SDL
+        // would quietly fall back to opening a visible window.
+        if !sdl2::video::drivers().any(|driver| driver == "offscreen") {
+            let msg = "Headless mode needs SDL's \"offscreen\" video driver, \
+                       which needs SDL 2.0.10 or newer; set the SDL_VIDEODRIVER \
+                       environment variable to use another driver"
+                .to_owned();
+            return Err(GameError::VideoError(msg));
+        }
+        env::set_var("SDL_VIDEODRIVER", "offscreen");
+    }
+    let sdl_context = sdl2::init()?;
+    Ok(sdl_context)
+}
+
 impl Context {
     /// Tries to create a new Context using settings from the given config file.
     /// Usually called by `Context::load_from_conf()`.
-    fn from_conf(conf: conf::Conf, fs: Filesystem, sdl_context: Sdl) -> GameResult<Context> {
+    fn from_conf(conf: conf::Conf, fs: Filesystem) -> GameResult<Context> {
+        let sdl_context = init_sdl(&conf)?;
         let video = sdl_context.video()?;
 
         let audio_context = audio::AudioContext::new()?;
@@ -92,6 +116,9 @@ impl Context {
             conf.window_height,
             conf.vsync,
             conf.resizable,
+            conf.headless,
+            conf.hidpi,
+            conf.power_of_two_textures,
         )?;
         let gamepad_context = input::GamepadContext::new(&sdl_context)?;
 
@@ -128,13 +155,11 @@ impl Context {
         author: &'static str,
         default_config: conf::Conf,
     ) -> GameResult<Context> {
-
-        let sdl_context = sdl2::init()?;
         let mut fs = Filesystem::new(game_id, author)?;
 
         let config = fs.read_config().unwrap_or(default_config);
 
-        Context::from_conf(config, fs, sdl_context)
+        Context::from_conf(config, fs)
     }
 
     /// Prints out information on the resources subsystem.
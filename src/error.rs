@@ -10,8 +10,10 @@ use gfx_window_sdl;
 use image;
 use rodio::decoder::DecoderError;
 use sdl2;
+use serde_json;
 use app_dirs::AppDirsError;
 use toml;
+use xml;
 use zip;
 
 /// An enum containing all kinds of game framework errors.
@@ -158,6 +160,20 @@ impl From<toml::ser::Error> for GameError {
 }
 
 
+impl From<serde_json::Error> for GameError {
+    fn from(e: serde_json::Error) -> GameError {
+        let errstr = format!("JSON decode error: {}", e);
+        GameError::ResourceLoadError(errstr)
+    }
+}
+
+impl From<xml::reader::Error> for GameError {
+    fn from(e: xml::reader::Error) -> GameError {
+        let errstr = format!("XML decode error: {}", e);
+        GameError::ResourceLoadError(errstr)
+    }
+}
+
 impl From<zip::result::ZipError> for GameError {
     fn from(e: zip::result::ZipError) -> GameError {
         let errstr = format!("Zip error: {}", e.description());
@@ -191,6 +207,52 @@ impl From<gfx::PipelineStateError<std::string::String>> for GameError {
     }
 }
 
+impl From<gfx::buffer::CreationError> for GameError {
+    fn from(e: gfx::buffer::CreationError) -> GameError {
+        let errstr = format!("Buffer creation error: {:?}", e);
+        GameError::VideoError(errstr)
+    }
+}
+
+impl From<gfx::shade::ProgramError> for GameError {
+    fn from(e: gfx::shade::ProgramError) -> GameError {
+        let errstr = format!("Shader compilation error: {:?}", e);
+        GameError::VideoError(errstr)
+    }
+}
+
+impl<S, D> From<gfx::CopyError<S, D>> for GameError
+where
+    S: fmt::Debug,
+    D: fmt::Debug,
+{
+    fn from(e: gfx::CopyError<S, D>) -> GameError {
+        let errstr = format!("Copy error: {:?}", e);
+        GameError::VideoError(errstr)
+    }
+}
+
+impl From<gfx::mapping::Error> for GameError {
+    fn from(e: gfx::mapping::Error) -> GameError {
+        let errstr = format!("Buffer mapping error: {:?}", e);
+        GameError::VideoError(errstr)
+    }
+}
+
+impl From<gfx::texture::CreationError> for GameError {
+    fn from(e: gfx::texture::CreationError) -> GameError {
+        let errstr = format!("Texture creation error: {:?}", e);
+        GameError::VideoError(errstr)
+    }
+}
+
+impl From<gfx::ResourceViewError> for GameError {
+    fn from(e: gfx::ResourceViewError) -> GameError {
+        let errstr = format!("Texture view creation error: {:?}", e);
+        GameError::VideoError(errstr)
+    }
+}
+
 impl From<gfx::CombinedError> for GameError {
     fn from(e: gfx::CombinedError) -> GameError {
         let errstr = format!("Texture+view load error: {}", e.description());
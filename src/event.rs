@@ -99,6 +99,11 @@ pub trait EventHandler {
     /// Is not called when you resize it yourself with
     /// `graphics::set_mode()` though.
     fn resize_event(&mut self, _ctx: &mut Context, _width: u32, _height: u32) {}
+
+    /// Called when the window's DPI scale changes, such as when it
+    /// is moved onto a high-DPI display, with the new scale.
+    /// See `graphics::get_dpi_scale()`.
+    fn dpi_changed(&mut self, _ctx: &mut Context, _scale: f32) {}
 }
 
 /// Runs the game's main loop, calling event callbacks on the given state
@@ -181,6 +186,16 @@ where
                     } => {
                         state.resize_event(ctx, w as u32, h as u32);
                     }
+                    Window {
+                        win_event: event::WindowEvent::Moved(..),
+                        ..
+                    } |
+                    Window {
+                        win_event: event::WindowEvent::SizeChanged(..),
+                        ..
+                    } => if let Some(scale) = ctx.gfx_context.update_dpi()? {
+                        state.dpi_changed(ctx, scale)
+                    },
                     _ => {}
                 }
             }
@@ -0,0 +1,562 @@
+//! Frame-by-frame sprite animation.
+//!
+//! An `Animation` is an `Image`, usually a sprite sheet, with a list
+//! of frames to show from it in turn, each for its own duration.
+//! Named tags pick out runs of frames to play, such as "walk" or
+//! "jump".  Animations can be made from a grid of frames or imported
+//! from Aseprite's JSON export.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path;
+use std::time::Duration;
+
+use serde_json;
+
+use timer;
+
+use super::*;
+
+/// What an `Animation` does when it reaches the end of its frames.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoopMode {
+    /// Stops on the last frame.
+    Once,
+    /// Starts again from the first frame.
+    Loop,
+    /// Plays the frames forwards, then backwards, and so on.
+    PingPong,
+}
+
+impl Default for LoopMode {
+    fn default() -> Self {
+        LoopMode::Loop
+    }
+}
+
+/// A single frame of an `Animation`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Frame {
+    /// The part of the image to draw, as a fraction of the
+    /// whole image, like `DrawParam::src`.
+    pub src: Rect,
+    /// How long to show the frame for.
+    pub duration: Duration,
+}
+
+/// A named run of frames of an `Animation`, such as "walk".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationTag {
+    /// The indices of the frames to play, in order.
+    pub frames: Vec<usize>,
+    /// What to do at the end of the frames.
+    pub mode: LoopMode,
+}
+
+/// Where an `Animation` is in the frames it's playing.  Kept apart
+/// from the frames themselves so it can be stepped on its own.
+#[derive(Debug, Clone, PartialEq)]
+struct Playback {
+    /// The frames being played, as indices into the animation's frames.
+    sequence: Vec<usize>,
+    mode: LoopMode,
+    /// The position in `sequence`.
+    position: usize,
+    /// How long the current frame has been shown for.
+    elapsed: Duration,
+    /// Whether a ping-pong animation is on its way back.
+    backwards: bool,
+    finished: bool,
+    paused: bool,
+}
+
+impl Playback {
+    fn new(sequence: Vec<usize>, mode: LoopMode) -> Playback {
+        Playback {
+            sequence: sequence,
+            mode: mode,
+            position: 0,
+            elapsed: Duration::new(0, 0),
+            backwards: false,
+            finished: false,
+            paused: false,
+        }
+    }
+
+    /// Returns the index of the frame to show.
+    fn frame(&self) -> Option<usize> {
+        self.sequence.get(self.position).cloned()
+    }
+
+    /// Moves on to the next frame, returning false if
+    /// there are no more.
+    fn step(&mut self) -> bool {
+        let last = self.sequence.len() - 1;
+        match self.mode {
+            LoopMode::Once => if self.position < last {
+                self.position += 1;
+                true
+            } else {
+                false
+            },
+            LoopMode::Loop => {
+                self.position = if self.position < last { self.position + 1 } else { 0 };
+                true
+            }
+            LoopMode::PingPong => {
+                if last == 0 {
+                    return true;
+                }
+                if self.backwards && self.position == 0 {
+                    self.backwards = false;
+                } else if !self.backwards && self.position == last {
+                    self.backwards = true;
+                }
+                if self.backwards {
+                    self.position -= 1;
+                } else {
+                    self.position += 1;
+                }
+                true
+            }
+        }
+    }
+
+    /// Advances the playback by the given time, stepping through
+    /// as many frames as that takes.
+    fn advance(&mut self, frames: &[Frame], dt: Duration) {
+        if self.paused || self.finished || self.sequence.is_empty() {
+            return;
+        }
+        self.elapsed += dt;
+        // Frames that take no time would otherwise step forever.
+        let mut instant_steps = 0;
+        while let Some(index) = self.frame() {
+            let duration = frames[index].duration;
+            if self.elapsed < duration {
+                break;
+            }
+            if duration == Duration::new(0, 0) {
+                instant_steps += 1;
+                if instant_steps > self.sequence.len() {
+                    break;
+                }
+            } else {
+                instant_steps = 0;
+            }
+            self.elapsed -= duration;
+            if !self.step() {
+                self.finished = true;
+                self.elapsed = Duration::new(0, 0);
+                break;
+            }
+        }
+    }
+}
+
+/// An `Image` with a series of frames to draw from it in turn.
+///
+/// Call `update()` once per update to move it along with the frame
+/// time, and draw it like any other `Drawable`; it draws the current
+/// frame.
+///
+/// ```rust,ignore
+/// let mut player = Animation::from_aseprite(ctx, "/player.json")?;
+/// player.play_tag("walk")?;
+/// // ...in update():
+/// player.update(ctx);
+/// // ...in draw():
+/// graphics::draw(ctx, &player, Point::new(100.0, 100.0), 0.0)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Animation {
+    image: Image,
+    frames: Vec<Frame>,
+    tags: HashMap<String, AnimationTag>,
+    playback: Playback,
+}
+
+impl Animation {
+    /// Creates a new animation playing all of the given frames
+    /// from an image, looping.
+    pub fn new(image: Image, frames: Vec<Frame>) -> Animation {
+        let sequence = (0..frames.len()).collect();
+        Animation {
+            image: image,
+            frames: frames,
+            tags: HashMap::new(),
+            playback: Playback::new(sequence, LoopMode::default()),
+        }
+    }
+
+    /// Creates an animation from a sprite sheet with its frames laid
+    /// out in a grid of the given number of columns and rows, read
+    /// left to right and then top to bottom, each shown for the same
+    /// duration.
+    pub fn from_grid(
+        image: Image,
+        columns: u32,
+        rows: u32,
+        frame_duration: Duration,
+    ) -> GameResult<Animation> {
+        if columns == 0 || rows == 0 {
+            let msg = format!("Invalid animation grid size {}x{}", columns, rows);
+            return Err(GameError::RenderError(msg));
+        }
+        let (w, h) = (1.0 / columns as f32, 1.0 / rows as f32);
+        let frames = (0..rows)
+            .flat_map(|row| {
+                (0..columns).map(move |column| Frame {
+                    src: Rect::new(column as f32 * w, row as f32 * h, w, h),
+                    duration: frame_duration,
+                })
+            })
+            .collect();
+        Ok(Animation::new(image, frames))
+    }
+
+    /// Loads an animation exported from Aseprite as a sprite sheet
+    /// with JSON data, in either the hash or the array format.  The
+    /// image is looked for relative to the JSON file.  Frame durations
+    /// and tags come from the file; tags loop, except for "ping-pong"
+    /// ones, and "reverse" ones play their frames backwards.
+    pub fn from_aseprite<P: AsRef<path::Path>>(ctx: &mut Context, path: P) -> GameResult<Animation> {
+        let path = path.as_ref();
+        let mut json = String::new();
+        let mut reader = ctx.filesystem.open(path)?;
+        reader.read_to_string(&mut json)?;
+        let sheet = parse_aseprite(&json)?;
+
+        let dir = path.parent().unwrap_or_else(|| path::Path::new("/"));
+        let image = Image::new(ctx, dir.join(&sheet.image))?;
+        let (width, height) = (image.width() as f32, image.height() as f32);
+        let frames = sheet
+            .frames
+            .iter()
+            .map(|&((x, y, w, h), duration)| Frame {
+                src: Rect::new(
+                    x as f32 / width,
+                    y as f32 / height,
+                    w as f32 / width,
+                    h as f32 / height,
+                ),
+                duration: duration,
+            })
+            .collect();
+        let mut animation = Animation::new(image, frames);
+        for (name, tag) in sheet.tags {
+            animation.add_tag(name, tag)?;
+        }
+        Ok(animation)
+    }
+
+    /// Adds a named run of frames that can be played with `play_tag()`,
+    /// replacing any existing tag with that name.
+    pub fn add_tag<S: Into<String>>(&mut self, name: S, tag: AnimationTag) -> GameResult<()> {
+        let name = name.into();
+        if let Some(&index) = tag.frames.iter().find(|&&i| i >= self.frames.len()) {
+            let msg = format!(
+                "Animation tag {:?} refers to frame {}, but there are only {} frames",
+                name,
+                index,
+                self.frames.len()
+            );
+            return Err(GameError::RenderError(msg));
+        }
+        self.tags.insert(name, tag);
+        Ok(())
+    }
+
+    /// Returns the tag with the given name.
+    pub fn get_tag(&self, name: &str) -> Option<&AnimationTag> {
+        self.tags.get(name)
+    }
+
+    /// Starts playing the tag with the given name from its first frame.
+    pub fn play_tag(&mut self, name: &str) -> GameResult<()> {
+        let tag = self.tags.get(name).ok_or_else(|| {
+            GameError::RenderError(format!("Animation has no tag named {:?}", name))
+        })?;
+        self.playback = Playback::new(tag.frames.clone(), tag.mode);
+        Ok(())
+    }
+
+    /// Starts playing all the frames from the first one, with
+    /// the given loop mode.
+    pub fn play_all(&mut self, mode: LoopMode) {
+        let sequence = (0..self.frames.len()).collect();
+        self.playback = Playback::new(sequence, mode);
+    }
+
+    /// Sets what happens at the end of the frames being played.
+    pub fn set_loop_mode(&mut self, mode: LoopMode) {
+        self.playback.mode = mode;
+    }
+
+    /// Returns what happens at the end of the frames being played.
+    pub fn get_loop_mode(&self) -> LoopMode {
+        self.playback.mode
+    }
+
+    /// Pauses or unpauses the animation.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.playback.paused = paused;
+    }
+
+    /// Returns whether the animation is paused.
+    pub fn is_paused(&self) -> bool {
+        self.playback.paused
+    }
+
+    /// Returns whether a `LoopMode::Once` animation has
+    /// reached its end.
+    pub fn is_finished(&self) -> bool {
+        self.playback.finished
+    }
+
+    /// Goes back to the first frame of whatever is playing.
+    pub fn rewind(&mut self) {
+        let sequence = self.playback.sequence.clone();
+        let mode = self.playback.mode;
+        let paused = self.playback.paused;
+        self.playback = Playback::new(sequence, mode);
+        self.playback.paused = paused;
+    }
+
+    /// Moves the animation along by the length of the last frame,
+    /// as given by `timer::get_delta()`.
+    pub fn update(&mut self, ctx: &Context) {
+        self.advance(timer::get_delta(ctx));
+    }
+
+    /// Moves the animation along by the given time.
+    pub fn advance(&mut self, dt: Duration) {
+        self.playback.advance(&self.frames, dt);
+    }
+
+    /// Returns the index of the frame currently shown, or `None`
+    /// if there are no frames to play.
+    pub fn current_frame(&self) -> Option<usize> {
+        self.playback.frame()
+    }
+
+    /// Returns all the frames of the animation.
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Returns the image the frames are drawn from.
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+}
+
+impl Drawable for Animation {
+    /// Draws the current frame.  `param.src` picks a part of
+    /// the frame, rather than of the whole image.
+    fn draw_ex(&self, ctx: &mut Context, param: DrawParam) -> GameResult<()> {
+        let frame = match self.current_frame() {
+            Some(index) => self.frames[index].src,
+            None => return Ok(()),
+        };
+        let mut param = param;
+        param.src = Rect {
+            x: frame.x + param.src.x * frame.w,
+            y: frame.y + param.src.y * frame.h,
+            w: param.src.w * frame.w,
+            h: param.src.h * frame.h,
+        };
+        self.image.draw_ex(ctx, param)
+    }
+}
+
+#[derive(Deserialize)]
+struct AsepriteRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct AsepriteFrame {
+    frame: AsepriteRect,
+    duration: u64,
+}
+
+#[derive(Deserialize)]
+struct AsepriteTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AsepriteMeta {
+    image: String,
+    #[serde(default)]
+    frame_tags: Vec<AsepriteTag>,
+}
+
+#[derive(Deserialize)]
+struct AsepriteFile {
+    frames: serde_json::Value,
+    meta: AsepriteMeta,
+}
+
+/// The useful parts of an Aseprite JSON file: the image file name,
+/// each frame's (x, y, width, height) in pixels and duration, and
+/// the tags.
+#[derive(Debug, PartialEq)]
+struct AsepriteSheet {
+    image: String,
+    frames: Vec<((u32, u32, u32, u32), Duration)>,
+    tags: Vec<(String, AnimationTag)>,
+}
+
+fn parse_aseprite(json: &str) -> GameResult<AsepriteSheet> {
+    let file: AsepriteFile = serde_json::from_str(json)?;
+    // In the hash format, frames are in the order they appear in,
+    // which is why `serde_json` has to preserve object order.
+    let raw_frames: Vec<serde_json::Value> = match file.frames {
+        serde_json::Value::Object(map) => map.into_iter().map(|(_, v)| v).collect(),
+        serde_json::Value::Array(frames) => frames,
+        _ => {
+            return Err(GameError::ResourceLoadError(
+                "Aseprite frames should be an object or an array".to_owned(),
+            ))
+        }
+    };
+    let mut frames = Vec::with_capacity(raw_frames.len());
+    for raw in raw_frames {
+        let f: AsepriteFrame = serde_json::from_value(raw)?;
+        let r = f.frame;
+        frames.push(((r.x, r.y, r.w, r.h), Duration::from_millis(f.duration)));
+    }
+
+    let mut tags = Vec::with_capacity(file.meta.frame_tags.len());
+    for tag in file.meta.frame_tags {
+        if tag.from > tag.to || tag.to >= frames.len() {
+            let msg = format!("Aseprite tag {:?} has invalid frames", tag.name);
+            return Err(GameError::ResourceLoadError(msg));
+        }
+        let forward: Vec<usize> = (tag.from..tag.to + 1).collect();
+        let (indices, mode) = match &tag.direction[..] {
+            "reverse" => (forward.into_iter().rev().collect(), LoopMode::Loop),
+            "pingpong" => (forward, LoopMode::PingPong),
+            _ => (forward, LoopMode::Loop),
+        };
+        tags.push((
+            tag.name,
+            AnimationTag {
+                frames: indices,
+                mode: mode,
+            },
+        ));
+    }
+    Ok(AsepriteSheet {
+        image: file.meta.image,
+        frames: frames,
+        tags: tags,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(durations: &[u64]) -> Vec<Frame> {
+        durations
+            .iter()
+            .map(|&ms| Frame {
+                src: Rect::one(),
+                duration: Duration::from_millis(ms),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_playback() {
+        let frames = frames(&[100, 50, 100]);
+        let ms = Duration::from_millis;
+
+        let mut looping = Playback::new(vec![0, 1, 2], LoopMode::Loop);
+        looping.advance(&frames, ms(99));
+        assert_eq!(looping.frame(), Some(0));
+        looping.advance(&frames, ms(1));
+        assert_eq!(looping.frame(), Some(1));
+        // Long steps skip frames.
+        looping.advance(&frames, ms(160));
+        assert_eq!(looping.frame(), Some(0));
+
+        let mut once = Playback::new(vec![0, 1, 2], LoopMode::Once);
+        once.advance(&frames, ms(1000));
+        assert_eq!(once.frame(), Some(2));
+        assert!(once.finished);
+
+        let mut ping_pong = Playback::new(vec![0, 1, 2], LoopMode::PingPong);
+        let mut seen = Vec::new();
+        for _ in 0..6 {
+            seen.push(ping_pong.frame().unwrap());
+            let duration = frames[ping_pong.frame().unwrap()].duration;
+            ping_pong.advance(&frames, duration);
+        }
+        assert_eq!(seen, vec![0, 1, 2, 1, 0, 1]);
+
+        // Frames that take no time don't hang.
+        let instant = self::frames(&[0, 0]);
+        let mut zero = Playback::new(vec![0, 1], LoopMode::Loop);
+        zero.advance(&instant, ms(10));
+    }
+
+    #[test]
+    fn test_parse_aseprite() {
+        let hash = r#"{
+            "frames": {
+                "walk 10.aseprite": {
+                    "frame": {"x": 0, "y": 0, "w": 16, "h": 16},
+                    "rotated": false, "trimmed": false,
+                    "duration": 100
+                },
+                "walk 2.aseprite": {
+                    "frame": {"x": 16, "y": 0, "w": 16, "h": 16},
+                    "rotated": false, "trimmed": false,
+                    "duration": 150
+                }
+            },
+            "meta": {
+                "app": "http://www.aseprite.org/",
+                "image": "walk.png",
+                "frameTags": [
+                    {"name": "step", "from": 0, "to": 1, "direction": "pingpong"},
+                    {"name": "back", "from": 0, "to": 1, "direction": "reverse"}
+                ]
+            }
+        }"#;
+        let sheet = parse_aseprite(hash).unwrap();
+        assert_eq!(sheet.image, "walk.png");
+        assert_eq!(
+            sheet.frames,
+            vec![
+                ((0, 0, 16, 16), Duration::from_millis(100)),
+                ((16, 0, 16, 16), Duration::from_millis(150)),
+            ]
+        );
+        assert_eq!(sheet.tags[0].1.mode, LoopMode::PingPong);
+        assert_eq!(sheet.tags[1].1.frames, vec![1, 0]);
+
+        let array = r#"{
+            "frames": [
+                {"filename": "a", "frame": {"x": 0, "y": 0, "w": 16, "h": 16}, "duration": 100},
+                {"filename": "b", "frame": {"x": 16, "y": 0, "w": 16, "h": 16}, "duration": 150}
+            ],
+            "meta": {"image": "walk.png"}
+        }"#;
+        let sheet2 = parse_aseprite(array).unwrap();
+        assert_eq!(sheet2.frames, sheet.frames);
+        assert!(sheet2.tags.is_empty());
+    }
+}
@@ -0,0 +1,483 @@
+//! Texture atlases: many small images packed into one big `Image`,
+//! so they can all be drawn without switching textures, for instance
+//! with a single `SpriteBatch`.
+//!
+//! An `AtlasBuilder` packs images at runtime, and
+//! `Atlas::from_texture_packer()` loads atlases packed ahead of time
+//! by TexturePacker, in either its JSON hash or JSON array format.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::collections::hash_map;
+use std::io::Read;
+use std::path;
+
+use image;
+use serde_json;
+
+use super::*;
+
+/// An `Image` containing many named regions, each of which can be
+/// drawn on its own by passing it as `DrawParam::src`.
+#[derive(Debug, Clone)]
+pub struct Atlas {
+    image: Image,
+    regions: HashMap<String, Rect>,
+}
+
+impl Atlas {
+    /// Creates an atlas out of an image and the regions in it, which
+    /// are given as fractions of the image, like `DrawParam::src`.
+    pub fn new(image: Image, regions: HashMap<String, Rect>) -> Atlas {
+        Atlas {
+            image: image,
+            regions: regions,
+        }
+    }
+
+    /// Loads a TexturePacker atlas, in the JSON hash or JSON array
+    /// format, along with its image, which is looked for relative to
+    /// the JSON file.
+    ///
+    /// Rotated frames aren't supported.  Trimmed frames are loaded as
+    /// their trimmed rectangle, without the offset to where they were
+    /// trimmed from.
+    pub fn from_texture_packer<P: AsRef<path::Path>>(
+        ctx: &mut Context,
+        path: P,
+    ) -> GameResult<Atlas> {
+        let path = path.as_ref();
+        let mut json = String::new();
+        let mut reader = ctx.filesystem.open(path)?;
+        reader.read_to_string(&mut json)?;
+        let (image_file, frames) = parse_texture_packer(&json)?;
+
+        let dir = path.parent().unwrap_or_else(|| path::Path::new("/"));
+        let image = Image::new(ctx, dir.join(image_file))?;
+        let (width, height) = (image.width() as f32, image.height() as f32);
+        let regions = frames
+            .into_iter()
+            .map(|(name, (x, y, w, h))| {
+                let src = Rect::new(
+                    x as f32 / width,
+                    y as f32 / height,
+                    w as f32 / width,
+                    h as f32 / height,
+                );
+                (name, src)
+            })
+            .collect();
+        Ok(Atlas::new(image, regions))
+    }
+
+    /// Returns the region with the given name, as a fraction of the
+    /// atlas image, ready to use as `DrawParam::src`.
+    pub fn get(&self, name: &str) -> Option<Rect> {
+        self.regions.get(name).cloned()
+    }
+
+    /// Returns the names of all the regions in the atlas.
+    pub fn names(&self) -> hash_map::Keys<String, Rect> {
+        self.regions.keys()
+    }
+
+    /// Returns the atlas image.
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Destroys the `Atlas` and returns the `Image` it contains.
+    pub fn into_inner(self) -> Image {
+        self.image
+    }
+}
+
+/// Packs images into an `Atlas` at runtime.
+///
+/// ```rust,ignore
+/// let mut builder = AtlasBuilder::new(1024, 1024);
+/// builder.add_file(ctx, "player", "/player.png")?;
+/// builder.add_file(ctx, "enemy", "/enemy.png")?;
+/// let atlas = builder.build(ctx)?;
+/// let param = DrawParam {
+///     src: atlas.get("player").unwrap(),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct AtlasBuilder {
+    max_width: u32,
+    max_height: u32,
+    padding: u32,
+    images: Vec<(String, ImageData)>,
+}
+
+impl AtlasBuilder {
+    /// Creates a new, empty builder for an atlas no larger than the
+    /// given size.  The atlas image ends up only as big as it needs
+    /// to be to fit everything.
+    pub fn new(max_width: u32, max_height: u32) -> AtlasBuilder {
+        AtlasBuilder {
+            max_width: max_width,
+            max_height: max_height,
+            padding: 1,
+            images: Vec::new(),
+        }
+    }
+
+    /// Sets the number of empty pixels to leave between images, so
+    /// that filtering doesn't bleed neighbouring images into each
+    /// other.  Default: 1.
+    pub fn padding(mut self, padding: u32) -> AtlasBuilder {
+        self.padding = padding;
+        self
+    }
+
+    /// Adds an image to the atlas under the given name, replacing
+    /// any image already added with that name.
+    pub fn add<S: Into<String>>(&mut self, name: S, data: ImageData) {
+        let name = name.into();
+        self.images.retain(|&(ref n, _)| *n != name);
+        self.images.push((name, data));
+    }
+
+    /// Loads an image file and adds it to the atlas under the given name.
+    pub fn add_file<S, P>(&mut self, ctx: &mut Context, name: S, path: P) -> GameResult<()>
+    where
+        S: Into<String>,
+        P: AsRef<path::Path>,
+    {
+        let img = {
+            let mut buf = Vec::new();
+            let mut reader = ctx.filesystem.open(path)?;
+            reader.read_to_end(&mut buf)?;
+            image::load_from_memory(&buf)?.to_rgba()
+        };
+        let (width, height) = img.dimensions();
+        self.add(
+            name,
+            ImageData {
+                width: width,
+                height: height,
+                rgba: img.into_vec(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Packs all the added images into a single `Image`.
+    ///
+    /// Returns an error if they don't all fit within the
+    /// maximum size.
+    pub fn build(&self, ctx: &mut Context) -> GameResult<Atlas> {
+        if self.max_width > u16::MAX as u32 || self.max_height > u16::MAX as u32 {
+            let msg = format!(
+                "Tried to build an atlas of up to {}x{}, each dimension must be <{}",
+                self.max_width,
+                self.max_height,
+                u16::MAX
+            );
+            return Err(GameError::RenderError(msg));
+        }
+        if let Some(&(ref name, _)) = self.images
+            .iter()
+            .find(|&&(_, ref data)| data.width == 0 || data.height == 0)
+        {
+            let msg = format!("Tried to add empty image {:?} to an atlas", name);
+            return Err(GameError::RenderError(msg));
+        }
+
+        // Padding goes on the right and bottom of each image, so it
+        // doesn't count against the edges of the atlas.
+        let sizes: Vec<(u32, u32)> = self.images
+            .iter()
+            .map(|&(_, ref data)| (data.width + self.padding, data.height + self.padding))
+            .collect();
+        let positions = pack(
+            &sizes,
+            self.max_width + self.padding,
+            self.max_height + self.padding,
+        ).ok_or_else(|| {
+            let msg = format!(
+                "Images don't all fit in a {}x{} atlas",
+                self.max_width,
+                self.max_height
+            );
+            GameError::RenderError(msg)
+        })?;
+
+        // Textures can't be empty.
+        let (mut width, mut height) = (1, 1);
+        for (&(_, ref data), &(x, y)) in self.images.iter().zip(&positions) {
+            width = cmp::max(width, x + data.width);
+            height = cmp::max(height, y + data.height);
+        }
+        let mut rgba = vec![0u8; (width * height * 4) as usize];
+        for (&(_, ref data), &(x, y)) in self.images.iter().zip(&positions) {
+            let row_len = (data.width * 4) as usize;
+            for row in 0..data.height {
+                let src = (row * data.width * 4) as usize;
+                let dest = (((y + row) * width + x) * 4) as usize;
+                rgba[dest..dest + row_len].copy_from_slice(&data.rgba[src..src + row_len]);
+            }
+        }
+        let image = Image::from_rgba8(ctx, width as u16, height as u16, &rgba)?;
+
+        let (w, h) = (width as f32, height as f32);
+        let regions = self.images
+            .iter()
+            .zip(&positions)
+            .map(|(&(ref name, ref data), &(x, y))| {
+                let src = Rect::new(
+                    x as f32 / w,
+                    y as f32 / h,
+                    data.width as f32 / w,
+                    data.height as f32 / h,
+                );
+                (name.clone(), src)
+            })
+            .collect();
+        Ok(Atlas::new(image, regions))
+    }
+}
+
+/// A horizontal stretch of the skyline: the top edge of
+/// everything packed so far.
+#[derive(Debug, Copy, Clone)]
+struct Segment {
+    x: u32,
+    y: u32,
+    width: u32,
+}
+
+/// Packs rectangles of the given sizes into an area of the given size
+/// with the skyline bottom-left heuristic: each rectangle, tallest
+/// first, goes wherever its top edge ends up lowest, then leftmost.
+///
+/// Returns the top-left corner of each rectangle, in the order they
+/// were given, or `None` if they don't all fit.
+fn pack(sizes: &[(u32, u32)], max_width: u32, max_height: u32) -> Option<Vec<(u32, u32)>> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|&a, &b| {
+        sizes[b]
+            .1
+            .cmp(&sizes[a].1)
+            .then(sizes[b].0.cmp(&sizes[a].0))
+    });
+
+    // The segments always cover the whole width, left to right.
+    let mut skyline = vec![
+        Segment {
+            x: 0,
+            y: 0,
+            width: max_width,
+        },
+    ];
+    let mut positions = vec![(0, 0); sizes.len()];
+    for i in order {
+        let (w, h) = sizes[i];
+        if w > max_width || h > max_height {
+            return None;
+        }
+        // Find the lowest spot, as (y, x, index of first segment).
+        let mut best: Option<(u32, u32, usize)> = None;
+        for start in 0..skyline.len() {
+            let x = skyline[start].x;
+            if x + w > max_width {
+                break;
+            }
+            // The rectangle rests on the highest segment under it.
+            let mut y = 0;
+            let mut covered = 0;
+            let mut j = start;
+            while covered < w {
+                y = cmp::max(y, skyline[j].y);
+                covered += skyline[j].width;
+                j += 1;
+            }
+            if y + h > max_height {
+                continue;
+            }
+            if best.map_or(true, |(best_y, best_x, _)| (y, x) < (best_y, best_x)) {
+                best = Some((y, x, start));
+            }
+        }
+        let (y, x, start) = best?;
+        positions[i] = (x, y);
+
+        // Raise the skyline under the rectangle: drop the segments
+        // it covers completely and trim the one it covers partly.
+        let end = x + w;
+        while start < skyline.len() && skyline[start].x < end {
+            let segment_end = skyline[start].x + skyline[start].width;
+            if segment_end <= end {
+                skyline.remove(start);
+            } else {
+                skyline[start].x = end;
+                skyline[start].width = segment_end - end;
+                break;
+            }
+        }
+        skyline.insert(
+            start,
+            Segment {
+                x: x,
+                y: y + h,
+                width: w,
+            },
+        );
+        // Merge neighbours at the same height.
+        let mut k = 0;
+        while k + 1 < skyline.len() {
+            if skyline[k].y == skyline[k + 1].y {
+                skyline[k].width += skyline[k + 1].width;
+                skyline.remove(k + 1);
+            } else {
+                k += 1;
+            }
+        }
+    }
+    Some(positions)
+}
+
+#[derive(Deserialize)]
+struct TexturePackerRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct TexturePackerFrame {
+    frame: TexturePackerRect,
+    #[serde(default)]
+    rotated: bool,
+}
+
+#[derive(Deserialize)]
+struct TexturePackerNamedFrame {
+    filename: String,
+    frame: TexturePackerRect,
+    #[serde(default)]
+    rotated: bool,
+}
+
+/// The "JSON hash" format keys frames by name, the "JSON array"
+/// format lists them with their names inside.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TexturePackerFrames {
+    Hash(HashMap<String, TexturePackerFrame>),
+    Array(Vec<TexturePackerNamedFrame>),
+}
+
+#[derive(Deserialize)]
+struct TexturePackerMeta {
+    image: String,
+}
+
+#[derive(Deserialize)]
+struct TexturePackerFile {
+    frames: TexturePackerFrames,
+    meta: TexturePackerMeta,
+}
+
+/// A frame's name and its (x, y, width, height) in pixels.
+type NamedFrame = (String, (u32, u32, u32, u32));
+
+/// Parses a TexturePacker JSON file into the name of its image
+/// and its frames.
+fn parse_texture_packer(json: &str) -> GameResult<(String, Vec<NamedFrame>)> {
+    let file: TexturePackerFile = serde_json::from_str(json)?;
+    let frames: Vec<(String, TexturePackerRect, bool)> = match file.frames {
+        TexturePackerFrames::Hash(frames) => frames
+            .into_iter()
+            .map(|(name, f)| (name, f.frame, f.rotated))
+            .collect(),
+        TexturePackerFrames::Array(frames) => frames
+            .into_iter()
+            .map(|f| (f.filename, f.frame, f.rotated))
+            .collect(),
+    };
+    let mut result = Vec::with_capacity(frames.len());
+    for (name, rect, rotated) in frames {
+        if rotated {
+            let msg = format!("Atlas frame {:?} is rotated, which isn't supported", name);
+            return Err(GameError::ResourceLoadError(msg));
+        }
+        result.push((name, (rect.x, rect.y, rect.w, rect.h)));
+    }
+    Ok((file.meta.image, result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack() {
+        let sizes = [(10, 20), (30, 5), (8, 8), (8, 8), (16, 16), (4, 30)];
+        let positions = pack(&sizes, 32, 64).unwrap();
+        let rects: Vec<_> = sizes
+            .iter()
+            .zip(&positions)
+            .map(|(&(w, h), &(x, y))| (x, y, x + w, y + h))
+            .collect();
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.2 <= 32 && a.3 <= 64);
+            for b in &rects[i + 1..] {
+                let overlaps = a.0 < b.2 && b.0 < a.2 && a.1 < b.3 && b.1 < a.3;
+                assert!(!overlaps, "{:?} overlaps {:?}", a, b);
+            }
+        }
+        assert_eq!(pack(&[(33, 1)], 32, 32), None);
+        assert_eq!(pack(&[(32, 20), (32, 20)], 32, 32), None);
+    }
+
+    #[test]
+    fn test_parse_texture_packer() {
+        let hash = r#"{
+            "frames": {
+                "a.png": {
+                    "frame": {"x": 0, "y": 0, "w": 16, "h": 8},
+                    "rotated": false,
+                    "trimmed": false,
+                    "sourceSize": {"w": 16, "h": 8}
+                },
+                "b.png": {
+                    "frame": {"x": 16, "y": 0, "w": 4, "h": 4},
+                    "rotated": false,
+                    "trimmed": false,
+                    "sourceSize": {"w": 4, "h": 4}
+                }
+            },
+            "meta": {"image": "sheet.png", "size": {"w": 32, "h": 8}, "scale": "1"}
+        }"#;
+        let (image, mut frames) = parse_texture_packer(hash).unwrap();
+        frames.sort();
+        assert_eq!(image, "sheet.png");
+        assert_eq!(
+            frames,
+            vec![
+                ("a.png".to_owned(), (0, 0, 16, 8)),
+                ("b.png".to_owned(), (16, 0, 4, 4)),
+            ]
+        );
+
+        let array = r#"{
+            "frames": [
+                {"filename": "a.png", "frame": {"x": 0, "y": 0, "w": 16, "h": 8}},
+                {"filename": "b.png", "frame": {"x": 16, "y": 0, "w": 4, "h": 4}}
+            ],
+            "meta": {"image": "sheet.png"}
+        }"#;
+        assert_eq!(parse_texture_packer(array).unwrap(), (image, frames));
+
+        let rotated = r#"{
+            "frames": [{"filename": "a.png", "frame": {"x": 0, "y": 0, "w": 1, "h": 1}, "rotated": true}],
+            "meta": {"image": "sheet.png"}
+        }"#;
+        assert!(parse_texture_packer(rotated).is_err());
+    }
+}
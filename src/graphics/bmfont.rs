@@ -0,0 +1,480 @@
+//! Loading for AngelCode BMFont (`.fnt`) bitmap fonts, in both the
+//! text and binary formats, and for fonts laid out in a fixed grid
+//! on a sprite sheet.
+//!
+//! See http://www.angelcode.com/products/bmfont/doc/file_format.html
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path;
+use std::str;
+
+use image;
+
+use context::Context;
+use GameError;
+use GameResult;
+
+/// Where a single character is on a `BMFontData`'s pages, and
+/// how to position it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BMFontChar {
+    /// Left edge of the glyph on its page, in pixels.
+    pub x: u32,
+    /// Top edge of the glyph on its page, in pixels.
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// How far right to draw the glyph from the current position.
+    pub xoffset: i32,
+    /// How far down to draw the glyph from the top of the line.
+    pub yoffset: i32,
+    /// How far to move the current position after drawing the glyph.
+    pub xadvance: i32,
+    /// Which page the glyph is on.
+    pub page: usize,
+}
+
+/// A page of glyphs of a `BMFontData`, as RGBA pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BMFontPage {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// The glyphs and metrics of a bitmap font.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BMFontData {
+    /// The distance between lines, in pixels.
+    pub line_height: u32,
+    /// The distance from the top of a line to the baseline, in pixels.
+    pub base: u32,
+    pub chars: HashMap<char, BMFontChar>,
+    /// Adjustments to the advance between pairs of characters.
+    pub kerning: HashMap<(char, char), i32>,
+    pub pages: Vec<BMFontPage>,
+}
+
+/// The contents of a `.fnt` file, before the pages are loaded.
+#[derive(Debug, Default, PartialEq)]
+struct BMFontFile {
+    line_height: u32,
+    base: u32,
+    page_files: Vec<String>,
+    chars: HashMap<char, BMFontChar>,
+    kerning: HashMap<(char, char), i32>,
+}
+
+impl BMFontData {
+    /// Loads a BMFont `.fnt` file, text or binary, and the page images
+    /// it refers to, which are looked for relative to it.
+    pub fn load<P: AsRef<path::Path>>(context: &mut Context, path: P) -> GameResult<BMFontData> {
+        let path = path.as_ref();
+        let mut buf = Vec::new();
+        let mut reader = context.filesystem.open(path)?;
+        reader.read_to_end(&mut buf)?;
+        let file = if buf.starts_with(b"BMF") {
+            parse_binary(&buf)?
+        } else {
+            let text = str::from_utf8(&buf).map_err(|e| {
+                GameError::FontError(format!("BMFont file {:?} is not valid UTF-8: {}", path, e))
+            })?;
+            parse_text(text)?
+        };
+
+        let dir = path.parent().unwrap_or_else(|| path::Path::new("/"));
+        let mut pages = Vec::with_capacity(file.page_files.len());
+        for page_file in &file.page_files {
+            pages.push(load_page(context, dir.join(page_file))?);
+        }
+        let data = BMFontData {
+            line_height: file.line_height,
+            base: file.base,
+            chars: file.chars,
+            kerning: file.kerning,
+            pages: pages,
+        };
+        data.check_pages()?;
+        Ok(data)
+    }
+
+    /// Loads a font from an image with glyphs of the same size laid out
+    /// in a grid, left to right and then top to bottom.  `glyphs` is the
+    /// characters in the image in that order.
+    pub fn load_grid<P: AsRef<path::Path>>(
+        context: &mut Context,
+        path: P,
+        glyphs: &str,
+        glyph_width: u32,
+        glyph_height: u32,
+    ) -> GameResult<BMFontData> {
+        let page = load_page(context, path)?;
+        BMFontData::from_grid(page, glyphs, glyph_width, glyph_height)
+    }
+
+    /// Creates a font from an already loaded grid image; see `load_grid()`.
+    pub fn from_grid(
+        page: BMFontPage,
+        glyphs: &str,
+        glyph_width: u32,
+        glyph_height: u32,
+    ) -> GameResult<BMFontData> {
+        if glyph_width == 0 || glyph_height == 0 || glyph_width > page.width {
+            let msg = format!(
+                "Invalid glyph size {}x{} for a {}x{} bitmap font image",
+                glyph_width,
+                glyph_height,
+                page.width,
+                page.height
+            );
+            return Err(GameError::FontError(msg));
+        }
+        let columns = page.width / glyph_width;
+        let mut chars = HashMap::new();
+        for (i, c) in glyphs.chars().enumerate() {
+            let i = i as u32;
+            chars.insert(
+                c,
+                BMFontChar {
+                    x: (i % columns) * glyph_width,
+                    y: (i / columns) * glyph_height,
+                    width: glyph_width,
+                    height: glyph_height,
+                    xoffset: 0,
+                    yoffset: 0,
+                    xadvance: glyph_width as i32,
+                    page: 0,
+                },
+            );
+        }
+        let data = BMFontData {
+            line_height: glyph_height,
+            base: glyph_height,
+            chars: chars,
+            kerning: HashMap::new(),
+            pages: vec![page],
+        };
+        data.check_pages()?;
+        Ok(data)
+    }
+
+    /// Returns the kerning between two characters, if any.
+    pub fn get_kerning(&self, first: char, second: char) -> i32 {
+        self.kerning.get(&(first, second)).cloned().unwrap_or(0)
+    }
+
+    /// Returns the width of a line of text in pixels, from the start
+    /// of the first character to the end of the last one's advance.
+    /// Does not handle line-breaks.
+    pub fn get_width(&self, text: &str) -> usize {
+        let mut width = 0;
+        let mut previous = None;
+        for c in text.chars() {
+            if let Some(ch) = self.chars.get(&c) {
+                if let Some(p) = previous {
+                    width += self.get_kerning(p, c);
+                }
+                width += ch.xadvance;
+                previous = Some(c);
+            }
+        }
+        width.max(0) as usize
+    }
+
+    /// Makes sure every glyph lies within its page, so rendering
+    /// doesn't have to check.
+    fn check_pages(&self) -> GameResult<()> {
+        for (c, ch) in &self.chars {
+            // The sizes come straight from the file, so they may
+            // be big enough to overflow.
+            let right = ch.x.checked_add(ch.width);
+            let bottom = ch.y.checked_add(ch.height);
+            let fits = match (self.pages.get(ch.page), right, bottom) {
+                (Some(p), Some(right), Some(bottom)) => right <= p.width && bottom <= p.height,
+                _ => false,
+            };
+            if !fits {
+                let msg = format!(
+                    "Glyph for {:?} lies outside of bitmap font page {}",
+                    c,
+                    ch.page
+                );
+                return Err(GameError::FontError(msg));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn load_page<P: AsRef<path::Path>>(context: &mut Context, path: P) -> GameResult<BMFontPage> {
+    let mut buf = Vec::new();
+    let mut reader = context.filesystem.open(path)?;
+    reader.read_to_end(&mut buf)?;
+    let img = image::load_from_memory(&buf)?.to_rgba();
+    let (width, height) = img.dimensions();
+    Ok(BMFontPage {
+        width: width,
+        height: height,
+        rgba: img.into_vec(),
+    })
+}
+
+fn char_from_id(id: u32) -> Option<char> {
+    ::std::char::from_u32(id)
+}
+
+/// Splits a line of the text format into its tag and key=value pairs.
+/// Values may be quoted, in which case they may contain spaces.
+fn parse_line(line: &str) -> (&str, HashMap<&str, &str>) {
+    let line = line.trim();
+    let (tag, mut rest) = match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim_left()),
+        None => (line, ""),
+    };
+    let mut values = HashMap::new();
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].trim();
+        let after = &rest[eq + 1..];
+        let (value, remainder) = if after.starts_with('"') {
+            match after[1..].find('"') {
+                Some(end) => (&after[1..end + 1], &after[end + 2..]),
+                None => (&after[1..], ""),
+            }
+        } else {
+            match after.find(char::is_whitespace) {
+                Some(end) => (&after[..end], &after[end..]),
+                None => (after, ""),
+            }
+        };
+        values.insert(key, value);
+        rest = remainder.trim_left();
+    }
+    (tag, values)
+}
+
+fn parse_text(text: &str) -> GameResult<BMFontFile> {
+    fn get<T: str::FromStr>(values: &HashMap<&str, &str>, key: &str) -> GameResult<T> {
+        values
+            .get(key)
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| GameError::FontError(format!("BMFont file has missing or invalid {:?}", key)))
+    }
+
+    let mut file = BMFontFile::default();
+    let mut pages = Vec::new();
+    for line in text.lines() {
+        let (tag, values) = parse_line(line);
+        match tag {
+            "common" => {
+                file.line_height = get(&values, "lineHeight")?;
+                file.base = get(&values, "base")?;
+            }
+            "page" => {
+                let id: usize = get(&values, "id")?;
+                let name: String = get(&values, "file")?;
+                pages.push((id, name));
+            }
+            "char" => {
+                let id: u32 = get(&values, "id")?;
+                let ch = BMFontChar {
+                    x: get(&values, "x")?,
+                    y: get(&values, "y")?,
+                    width: get(&values, "width")?,
+                    height: get(&values, "height")?,
+                    xoffset: get(&values, "xoffset")?,
+                    yoffset: get(&values, "yoffset")?,
+                    xadvance: get(&values, "xadvance")?,
+                    page: get(&values, "page")?,
+                };
+                if let Some(c) = char_from_id(id) {
+                    file.chars.insert(c, ch);
+                }
+            }
+            "kerning" => {
+                let first: u32 = get(&values, "first")?;
+                let second: u32 = get(&values, "second")?;
+                let amount: i32 = get(&values, "amount")?;
+                if let (Some(a), Some(b)) = (char_from_id(first), char_from_id(second)) {
+                    file.kerning.insert((a, b), amount);
+                }
+            }
+            _ => (),
+        }
+    }
+    pages.sort();
+    file.page_files = pages.into_iter().map(|(_, name)| name).collect();
+    Ok(file)
+}
+
+/// A little cursor for reading the little-endian binary format.
+struct BinaryReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BinaryReader<'a> {
+    fn bytes(&mut self, count: usize) -> GameResult<&'a [u8]> {
+        if self.pos + count > self.data.len() {
+            return Err(GameError::FontError(
+                "Binary BMFont file ends unexpectedly".to_owned(),
+            ));
+        }
+        let bytes = &self.data[self.pos..self.pos + count];
+        self.pos += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> GameResult<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> GameResult<u16> {
+        let b = self.bytes(2)?;
+        Ok(b[0] as u16 | (b[1] as u16) << 8)
+    }
+
+    fn i16(&mut self) -> GameResult<i16> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> GameResult<u32> {
+        let b = self.bytes(4)?;
+        Ok(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+    }
+}
+
+fn parse_binary(data: &[u8]) -> GameResult<BMFontFile> {
+    let mut reader = BinaryReader { data: data, pos: 0 };
+    let header = reader.bytes(4)?;
+    if &header[..3] != b"BMF" || header[3] != 3 {
+        return Err(GameError::FontError(
+            "Only version 3 of the binary BMFont format is supported".to_owned(),
+        ));
+    }
+    let mut file = BMFontFile::default();
+    while reader.pos < data.len() {
+        let block_type = reader.u8()?;
+        let size = reader.u32()? as usize;
+        let block = reader.bytes(size)?;
+        let mut block_reader = BinaryReader { data: block, pos: 0 };
+        match block_type {
+            2 => {
+                file.line_height = block_reader.u16()? as u32;
+                file.base = block_reader.u16()? as u32;
+            }
+            3 => {
+                // Null-terminated page file names.
+                for name in block.split(|&b| b == 0).filter(|n| !n.is_empty()) {
+                    let name = str::from_utf8(name).map_err(|e| {
+                        GameError::FontError(format!("Invalid BMFont page name: {}", e))
+                    })?;
+                    file.page_files.push(name.to_owned());
+                }
+            }
+            4 => {
+                for _ in 0..size / 20 {
+                    let id = block_reader.u32()?;
+                    let ch = BMFontChar {
+                        x: block_reader.u16()? as u32,
+                        y: block_reader.u16()? as u32,
+                        width: block_reader.u16()? as u32,
+                        height: block_reader.u16()? as u32,
+                        xoffset: block_reader.i16()? as i32,
+                        yoffset: block_reader.i16()? as i32,
+                        xadvance: block_reader.i16()? as i32,
+                        page: block_reader.u8()? as usize,
+                    };
+                    // Channel, which we don't use.
+                    block_reader.u8()?;
+                    if let Some(c) = char_from_id(id) {
+                        file.chars.insert(c, ch);
+                    }
+                }
+            }
+            5 => {
+                for _ in 0..size / 10 {
+                    let first = block_reader.u32()?;
+                    let second = block_reader.u32()?;
+                    let amount = block_reader.i16()? as i32;
+                    if let (Some(a), Some(b)) = (char_from_id(first), char_from_id(second)) {
+                        file.kerning.insert((a, b), amount);
+                    }
+                }
+            }
+            // Info block and anything unknown.
+            _ => (),
+        }
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_text_and_binary() {
+        let text = "info face=\"Some Font\" size=16 bold=0\n\
+                    common lineHeight=18 base=14 scaleW=64 scaleH=64 pages=1 packed=0\n\
+                    page id=0 file=\"some font.png\"\n\
+                    chars count=2\n\
+                    char id=65   x=1 y=2 width=8 height=10 xoffset=-1 yoffset=4 xadvance=9 page=0 chnl=15\n\
+                    char id=228  x=10 y=2 width=8 height=12 xoffset=0 yoffset=2 xadvance=9 page=0 chnl=15\n\
+                    kernings count=1\n\
+                    kerning first=65 second=228 amount=-2\n";
+        let parsed = parse_text(text).unwrap();
+        assert_eq!(parsed.line_height, 18);
+        assert_eq!(parsed.base, 14);
+        assert_eq!(parsed.page_files, vec!["some font.png".to_owned()]);
+        assert_eq!(parsed.chars.len(), 2);
+        assert_eq!(parsed.chars[&'A'].xoffset, -1);
+        assert_eq!(parsed.chars[&'ä'].x, 10);
+        assert_eq!(parsed.kerning[&('A', 'ä')], -2);
+
+        // The same font in the binary format.
+        let mut bin = b"BMF\x03".to_vec();
+        let block = |bin: &mut Vec<u8>, kind: u8, data: &[u8]| {
+            bin.push(kind);
+            let len = data.len() as u32;
+            bin.extend_from_slice(&[len as u8, (len >> 8) as u8, (len >> 16) as u8, (len >> 24) as u8]);
+            bin.extend_from_slice(data);
+        };
+        block(&mut bin, 1, &[16, 0, 0, 0, 100, 0, 1, 0, 0, 0, 0, 1, 1, 0, b'F', 0]);
+        block(&mut bin, 2, &[18, 0, 14, 0, 64, 0, 64, 0, 1, 0, 0, 0, 0, 0, 0]);
+        block(&mut bin, 3, b"some font.png\0");
+        block(
+            &mut bin,
+            4,
+            &[
+                65, 0, 0, 0, 1, 0, 2, 0, 8, 0, 10, 0, 0xff, 0xff, 4, 0, 9, 0, 0, 15,
+                228, 0, 0, 0, 10, 0, 2, 0, 8, 0, 12, 0, 0, 0, 2, 0, 9, 0, 0, 15,
+            ],
+        );
+        block(&mut bin, 5, &[65, 0, 0, 0, 228, 0, 0, 0, 0xfe, 0xff]);
+        let parsed_binary = parse_binary(&bin).unwrap();
+        assert_eq!(parsed, parsed_binary);
+    }
+
+    #[test]
+    fn test_grid() {
+        let page = BMFontPage {
+            width: 16,
+            height: 24,
+            rgba: vec![0; 16 * 24 * 4],
+        };
+        let font = BMFontData::from_grid(page, "abcdé", 8, 8).unwrap();
+        assert_eq!(font.chars[&'b'].x, 8);
+        assert_eq!(font.chars[&'c'].y, 8);
+        assert_eq!(font.chars[&'é'].y, 16);
+        assert_eq!(font.get_width("abé"), 24);
+        assert!(font.check_pages().is_ok());
+
+        let mut broken = font.clone();
+        broken.chars.get_mut(&'a').unwrap().x = u32::max_value() - 4;
+        assert!(broken.check_pages().is_err());
+        let mut broken = font.clone();
+        broken.chars.get_mut(&'a').unwrap().height = u32::max_value();
+        assert!(broken.check_pages().is_err());
+    }
+}
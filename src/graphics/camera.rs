@@ -0,0 +1,359 @@
+//! A 2D camera, for looking at part of a bigger world.
+//!
+//! A `Camera2D` says where in the world the middle of the screen
+//! is, how far it is zoomed in and how it is rotated.  It can follow
+//! a target, smoothly or not, stay inside the edges of the world, and
+//! shake.  `graphics::set_camera()` makes drawing go through it, and
+//! `screen_to_world()` turns window coordinates, such as from
+//! `mouse::get_position()`, into world coordinates.
+
+use std::time::Duration;
+
+use rand;
+
+use timer;
+
+use super::*;
+
+/// A 2D camera.
+///
+/// World coordinates have Y increasing downwards, the same as the
+/// default screen coordinates, so with no zoom or rotation a camera
+/// at (400, 300) in an 800x600 window shows the same as not having
+/// a camera at all.
+///
+/// ```rust,ignore
+/// let (w, h) = ctx.gfx_context.get_size();
+/// let mut camera = Camera2D::new(w as f32, h as f32);
+/// camera.set_bounds(Some(Rect::new(1000.0, 500.0, 2000.0, 1000.0)));
+/// camera.set_follow_speed(Some(5.0));
+///
+/// // In update():
+/// camera.follow(Some(player.position));
+/// camera.update(timer::get_delta(ctx));
+///
+/// // In draw():
+/// graphics::set_camera(ctx, &camera)?;
+/// let cursor = camera.screen_to_world(mouse::get_position(ctx)?);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Camera2D {
+    viewport: (f32, f32),
+    position: Point,
+    zoom: f32,
+    rotation: f32,
+    bounds: Option<Rect>,
+    target: Option<Point>,
+    follow_speed: Option<f32>,
+    shake_intensity: f32,
+    shake_duration: f32,
+    shake_left: f32,
+    shake_offset: Point,
+}
+
+impl Camera2D {
+    /// Creates a new camera for a window of the given size, looking
+    /// at the middle of the window as if there were no camera.
+    pub fn new(viewport_width: f32, viewport_height: f32) -> Self {
+        Camera2D {
+            viewport: (viewport_width, viewport_height),
+            position: Point::new(viewport_width / 2.0, viewport_height / 2.0),
+            zoom: 1.0,
+            rotation: 0.0,
+            bounds: None,
+            target: None,
+            follow_speed: None,
+            shake_intensity: 0.0,
+            shake_duration: 0.0,
+            shake_left: 0.0,
+            shake_offset: Point::zero(),
+        }
+    }
+
+    /// Sets the size of the window the camera draws to, such as
+    /// when it is resized.  The middle of the view stays put.
+    pub fn set_viewport_size(&mut self, width: f32, height: f32) {
+        self.viewport = (width, height);
+        self.clamp();
+    }
+
+    /// Returns the size of the window the camera draws to.
+    pub fn get_viewport_size(&self) -> (f32, f32) {
+        self.viewport
+    }
+
+    /// Moves the camera to look at the given point in the world,
+    /// kept inside the bounds if there are any.
+    pub fn set_position(&mut self, position: Point) {
+        self.position = position;
+        self.clamp();
+    }
+
+    /// Returns the point in the world the camera is looking at,
+    /// not counting any shake.
+    pub fn get_position(&self) -> Point {
+        self.position
+    }
+
+    /// Sets how far the camera is zoomed in; 2.0 makes everything
+    /// twice as big.  Default: 1.0.
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.max(::std::f32::EPSILON);
+        self.clamp();
+    }
+
+    /// Returns how far the camera is zoomed in.
+    pub fn get_zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Sets the camera's rotation, in radians.  Turning the camera
+    /// clockwise turns the world on the screen the other way.
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+        self.clamp();
+    }
+
+    /// Returns the camera's rotation, in radians.
+    pub fn get_rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    /// Sets the part of the world the camera has to stay inside, or
+    /// `None` to let it go anywhere.  As with `set_screen_coordinates()`,
+    /// the `Rect`'s x and y are its center.  If the bounds are smaller
+    /// than the view, the camera stays in their middle.
+    pub fn set_bounds(&mut self, bounds: Option<Rect>) {
+        self.bounds = bounds;
+        self.clamp();
+    }
+
+    /// Returns the part of the world the camera has to stay inside.
+    pub fn get_bounds(&self) -> Option<Rect> {
+        self.bounds
+    }
+
+    /// Sets a point for the camera to follow, or `None` to stop
+    /// following.  The camera moves towards it in `update()`.
+    pub fn follow(&mut self, target: Option<Point>) {
+        self.target = target;
+    }
+
+    /// Sets how quickly the camera catches up with what it's
+    /// following.  Each second it moves about `1 - e^-speed` of the
+    /// way there, so bigger is faster.  `None`, the default, makes it
+    /// jump straight there.
+    pub fn set_follow_speed(&mut self, speed: Option<f32>) {
+        self.follow_speed = speed;
+    }
+
+    /// Returns how quickly the camera catches up with what it's following.
+    pub fn get_follow_speed(&self) -> Option<f32> {
+        self.follow_speed
+    }
+
+    /// Shakes the camera by up to `intensity` world units in each
+    /// direction, dying down over the given time.  Replaces any
+    /// shake already going on.
+    pub fn shake(&mut self, intensity: f32, duration: Duration) {
+        self.shake_intensity = intensity;
+        self.shake_duration = timer::duration_to_f64(duration) as f32;
+        self.shake_left = self.shake_duration;
+    }
+
+    /// Returns whether the camera is shaking.
+    pub fn is_shaking(&self) -> bool {
+        self.shake_left > 0.0
+    }
+
+    /// Moves the camera towards what it's following and moves the
+    /// shake along, as if the given amount of time has passed.
+    /// Usually called once per frame with `timer::get_delta()`.
+    pub fn update(&mut self, dt: Duration) {
+        let dt = timer::duration_to_f64(dt) as f32;
+        if let Some(target) = self.target {
+            let position = match self.follow_speed {
+                Some(speed) => {
+                    let t = 1.0 - (-speed * dt).exp();
+                    Point::new(
+                        self.position.x + (target.x - self.position.x) * t,
+                        self.position.y + (target.y - self.position.y) * t,
+                    )
+                }
+                None => target,
+            };
+            self.position = position;
+            self.clamp();
+        }
+
+        self.shake_left = (self.shake_left - dt).max(0.0);
+        self.shake_offset = if self.shake_left > 0.0 && self.shake_duration > 0.0 {
+            let strength = self.shake_intensity * self.shake_left / self.shake_duration;
+            Point::new(
+                (rand::random::<f32>() * 2.0 - 1.0) * strength,
+                (rand::random::<f32>() * 2.0 - 1.0) * strength,
+            )
+        } else {
+            Point::zero()
+        };
+    }
+
+    /// The point in the world actually in the middle of
+    /// the screen, counting the shake.
+    fn eye(&self) -> Point {
+        Point::new(
+            self.position.x + self.shake_offset.x,
+            self.position.y + self.shake_offset.y,
+        )
+    }
+
+    /// Returns the size of the part of the world the camera shows,
+    /// before rotating it.
+    fn view_size(&self) -> (f32, f32) {
+        (self.viewport.0 / self.zoom, self.viewport.1 / self.zoom)
+    }
+
+    /// Returns the smallest rectangle around the part of the world
+    /// the camera shows, centered on what it is looking at.
+    pub fn get_visible_rect(&self) -> Rect {
+        let (w, h) = self.view_size();
+        let (sin, cos) = self.rotation.sin_cos();
+        let eye = self.eye();
+        Rect::new(
+            eye.x,
+            eye.y,
+            w * cos.abs() + h * sin.abs(),
+            w * sin.abs() + h * cos.abs(),
+        )
+    }
+
+    /// Turns a point in window coordinates, with (0, 0) at the
+    /// top-left corner, into a point in the world.
+    pub fn screen_to_world(&self, point: Point) -> Point {
+        let x = (point.x - self.viewport.0 / 2.0) / self.zoom;
+        let y = (point.y - self.viewport.1 / 2.0) / self.zoom;
+        let (sin, cos) = self.rotation.sin_cos();
+        let eye = self.eye();
+        Point::new(eye.x + x * cos - y * sin, eye.y + x * sin + y * cos)
+    }
+
+    /// Turns a point in the world into window coordinates.
+    pub fn world_to_screen(&self, point: Point) -> Point {
+        let eye = self.eye();
+        let (x, y) = (point.x - eye.x, point.y - eye.y);
+        let (sin, cos) = self.rotation.sin_cos();
+        Point::new(
+            (x * cos + y * sin) * self.zoom + self.viewport.0 / 2.0,
+            (-x * sin + y * cos) * self.zoom + self.viewport.1 / 2.0,
+        )
+    }
+
+    /// Keeps the view inside the bounds, if there are any.
+    fn clamp(&mut self) {
+        if let Some(bounds) = self.bounds {
+            let visible = self.get_visible_rect();
+            let (half_w, half_h) = (visible.w / 2.0, visible.h / 2.0);
+            let (top, bottom) = (bounds.top(), bounds.bottom());
+            self.position.x = clamp_axis(self.position.x, bounds.left(), bounds.right(), half_w);
+            self.position.y = clamp_axis(self.position.y, top.min(bottom), top.max(bottom), half_h);
+        }
+    }
+}
+
+/// Keeps a view `2 * half` long centered on `center` between `min`
+/// and `max`, or centers it between them if it doesn't fit.
+fn clamp_axis(center: f32, min: f32, max: f32, half: f32) -> f32 {
+    if max - min <= 2.0 * half {
+        (min + max) / 2.0
+    } else {
+        center.max(min + half).min(max - half)
+    }
+}
+
+/// Makes all subsequent drawing go through the given camera, until
+/// it is set again or `set_screen_coordinates()` is called.  Set the
+/// camera each frame after updating it.
+///
+/// `get_screen_coordinates()` then returns the smallest rectangle
+/// around the part of the world the camera shows, which is exact
+/// when the camera isn't rotated.  Scissor rectangles are given in
+/// those coordinates too, so they don't rotate with the camera.
+pub fn set_camera(ctx: &mut Context, camera: &Camera2D) -> GameResult<()> {
+    let gfx = &mut ctx.gfx_context;
+    let (w, h) = camera.view_size();
+    let eye = camera.eye();
+    let view: Matrix = super::ortho(-w / 2.0, w / 2.0, -h / 2.0, h / 2.0, 1.0, -1.0).into();
+    gfx.projection = view * Matrix::rotation(-camera.rotation) *
+        Matrix::translation(-eye.x, -eye.y);
+    // Y increases downwards, which the screen rect says with a
+    // negative height.
+    let visible = camera.get_visible_rect();
+    gfx.screen_rect = Rect::new(visible.x, visible.y, visible.w, -visible.h);
+    gfx.calculate_transform_matrix();
+    gfx.update_globals()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Point, b: Point) {
+        assert!(
+            (a.x - b.x).abs() < 0.001 && (a.y - b.y).abs() < 0.001,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn test_screen_to_world() {
+        let mut camera = Camera2D::new(800.0, 600.0);
+        let p = Point::new(100.0, 50.0);
+        assert_close(camera.screen_to_world(p), p);
+
+        camera.set_position(Point::new(1000.0, 1000.0));
+        camera.set_zoom(2.0);
+        assert_close(camera.screen_to_world(Point::new(400.0, 300.0)), Point::new(1000.0, 1000.0));
+        assert_close(camera.screen_to_world(Point::new(0.0, 0.0)), Point::new(800.0, 850.0));
+
+        // After a quarter turn clockwise, what is to the left of
+        // the camera in the world is below it on the screen.
+        camera.set_rotation(::std::f32::consts::PI / 2.0);
+        assert_close(camera.screen_to_world(Point::new(400.0, 400.0)), Point::new(950.0, 1000.0));
+        let world = Point::new(1234.0, 987.0);
+        assert_close(camera.screen_to_world(camera.world_to_screen(world)), world);
+    }
+
+    #[test]
+    fn test_bounds_and_follow() {
+        let mut camera = Camera2D::new(800.0, 600.0);
+        camera.set_bounds(Some(Rect::new(1000.0, 500.0, 2000.0, 1000.0)));
+        camera.set_position(Point::new(0.0, 2000.0));
+        assert_eq!(camera.get_position(), Point::new(400.0, 700.0));
+
+        // Zoomed out further than the bounds are tall.
+        camera.set_zoom(0.5);
+        assert_eq!(camera.get_position(), Point::new(800.0, 500.0));
+        camera.set_zoom(1.0);
+
+        camera.follow(Some(Point::new(1000.0, 500.0)));
+        camera.update(Duration::from_millis(16));
+        assert_eq!(camera.get_position(), Point::new(1000.0, 500.0));
+
+        camera.set_follow_speed(Some(2.0));
+        camera.follow(Some(Point::new(1200.0, 500.0)));
+        camera.update(Duration::from_millis(500));
+        let x = camera.get_position().x;
+        assert!(x > 1100.0 && x < 1200.0);
+
+        camera.follow(None);
+        camera.shake(10.0, Duration::from_millis(100));
+        camera.update(Duration::from_millis(50));
+        assert!(camera.is_shaking());
+        let p = camera.screen_to_world(Point::new(400.0, 300.0));
+        assert!((p.x - x).abs() <= 5.0);
+        camera.update(Duration::from_millis(50));
+        assert!(!camera.is_shaking());
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt;
+
+use gfx::Factory;
+use gfx::handle::{DepthStencilView, RenderTargetView};
+use gfx::memory::Typed;
+use gfx_device_gl;
+
+use super::*;
+
+/// A generic canvas independent of graphics backend. This type should
+/// probably never be used directly; use `Canvas` instead.
+#[derive(Clone)]
+pub struct CanvasGeneric<R>
+where
+    R: gfx::Resources,
+{
+    target: RenderTargetView<R, ColorFormat>,
+    depth: DepthStencilView<R, DepthFormat>,
+    image: ImageGeneric<R>,
+}
+
+/// A `Canvas` is an off-screen render target which can be drawn to
+/// instead of the window.  Once you're done drawing on it you can
+/// draw it to the screen (or another `Canvas`) like any other `Image`.
+///
+/// Use `graphics::set_canvas()` to select which target subsequent
+/// drawing goes to.
+pub type Canvas = CanvasGeneric<gfx_device_gl::Resources>;
+
+impl Canvas {
+    /// Create a new canvas with the given size.
+    pub fn new(ctx: &mut Context, width: u32, height: u32) -> GameResult<Canvas> {
+        if width == 0 || height == 0 || width > u16::MAX as u32 || height > u16::MAX as u32 {
+            let msg = format!(
+                "Tried to create a canvas of size {}x{}, each dimension must \
+                 be >0 and <={}",
+                width,
+                height,
+                u16::MAX
+            );
+            return Err(GameError::RenderError(msg));
+        }
+        let gfx = &mut ctx.gfx_context;
+        let (texture, resource, target) = gfx.factory
+            .create_render_target::<ColorFormat>(width as u16, height as u16)?;
+        // Each canvas gets its own stencil buffer, since the
+        // screen's one may not be the same size.
+        let depth = gfx.factory
+            .create_depth_stencil_view_only::<DepthFormat>(width as u16, height as u16)?;
+        Ok(Canvas {
+            target: target,
+            depth: depth,
+            image: Image {
+                texture: resource,
+                raw_texture: texture.raw().clone(),
+                sampler_info: gfx.default_sampler_info,
+                width: width,
+                height: height,
+                uv_scale: (1.0, 1.0),
+            },
+        })
+    }
+
+    /// Create a new canvas with the current window dimensions.
+    pub fn with_window_size(ctx: &mut Context) -> GameResult<Canvas> {
+        let (w, h) = ctx.gfx_context.get_drawable_size();
+        Canvas::new(ctx, w, h)
+    }
+
+    /// Gets the backing `Image` of the `Canvas`.
+    ///
+    /// Note that the image is stored upside-down, because OpenGL
+    /// puts the origin of textures in the bottom-left corner.  Drawing
+    /// the `Canvas` itself corrects for this, drawing the `Image`
+    /// directly does not.
+    pub fn get_image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Destroys the `Canvas` and returns the `Image` it contains.
+    pub fn into_inner(self) -> Image {
+        self.image
+    }
+
+    /// Return the width of the canvas.
+    pub fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    /// Return the height of the canvas.
+    pub fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    /// Returns the dimensions of the canvas.
+    pub fn get_dimensions(&self) -> Rect {
+        self.image.get_dimensions()
+    }
+
+    /// Get the filter mode for the canvas.
+    pub fn get_filter(&self) -> FilterMode {
+        self.image.get_filter()
+    }
+
+    /// Set the filter mode for the canvas.
+    pub fn set_filter(&mut self, mode: FilterMode) {
+        self.image.set_filter(mode)
+    }
+}
+
+impl fmt::Debug for Canvas {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<Canvas: {}x{}, {:p}, texture address {:p}>",
+            self.width(),
+            self.height(),
+            self,
+            &self.image.texture
+        )
+    }
+}
+
+impl Drawable for Canvas {
+    fn draw_ex(&self, ctx: &mut Context, param: DrawParam) -> GameResult<()> {
+        // Flip the source rect vertically, since the render target
+        // is upside-down relative to how we load images.
+        let mut flipped = param;
+        flipped.src.y = param.src.y + param.src.h;
+        flipped.src.h = -param.src.h;
+        self.image.draw_ex(ctx, flipped)
+    }
+}
+
+/// Set the `Canvas` to render to.  Specifying `Option::None` will cause
+/// all rendering to be done directly to the screen.
+///
+/// `clear()` clears whichever target is currently selected, and the
+/// current screen coordinates set with `set_screen_coordinates()` are
+/// used to map drawing onto it.
+pub fn set_canvas(ctx: &mut Context, target: Option<&Canvas>) {
+    let gfx = &mut ctx.gfx_context;
+    match target {
+        Some(surface) => {
+            gfx.data.out = surface.target.clone();
+            gfx.data.stencil.0 = surface.depth.clone();
+            gfx.target_texture = Some(surface.image.raw_texture.clone());
+        }
+        None => {
+            gfx.data.out = gfx.screen_render_target.clone();
+            gfx.data.stencil.0 = gfx.depth_view.clone();
+            gfx.target_texture = gfx.screen_texture.clone();
+        }
+    };
+}
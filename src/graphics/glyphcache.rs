@@ -0,0 +1,302 @@
+//! A glyph cache for drawing lots of frequently changing text cheaply.
+//!
+//! `Text` renders each string into its own texture, which is fine
+//! for text that rarely changes but wasteful for e.g. a score counter
+//! that changes every frame.  Instead, `queue_text()` lays out the
+//! glyphs of a string, and `draw_queued_text()` draws everything that
+//! has been queued as textured quads in a single draw call.  The glyphs
+//! themselves are rasterized into a texture atlas shared by all text,
+//! which grows as needed; glyphs that haven't been used in a while are
+//! evicted from it to make room for new ones.
+
+use std::cmp;
+use std::mem;
+
+use gfx::Factory;
+use gfx::memory::Typed;
+use gfx_device_gl;
+use rusttype::{self, gpu_cache};
+
+use super::*;
+
+/// The size the atlas starts out at, in pixels on each side.
+const INITIAL_ATLAS_SIZE: u32 = 256;
+
+/// The largest the atlas may grow to.  Every GPU ggez runs on
+/// should support textures at least this big.
+const MAX_ATLAS_SIZE: u32 = 4096;
+
+type AtlasFormat = gfx::format::R8_G8_B8_A8;
+
+/// A glyph waiting to be drawn by `draw_queued_text()`.
+struct QueuedGlyph {
+    font_id: FontId,
+    /// Rasterized at `dpi_scale` times its size on screen.
+    glyph: rusttype::PositionedGlyph<'static>,
+    dpi_scale: f32,
+    color: Color,
+}
+
+/// The glyph atlas and queue of glyphs to draw, as stored in
+/// the `GraphicsContext`.
+pub struct GlyphCache {
+    cache: gpu_cache::Cache,
+    texture: gfx::handle::Texture<gfx_device_gl::Resources, AtlasFormat>,
+    atlas: Image,
+    queue: Vec<QueuedGlyph>,
+    instances: Option<gfx::handle::Buffer<gfx_device_gl::Resources, RectProperties>>,
+    instance_capacity: usize,
+}
+
+impl fmt::Debug for GlyphCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<GlyphCache: {}x{}, {} glyphs queued>",
+            self.atlas.width,
+            self.atlas.height,
+            self.queue.len()
+        )
+    }
+}
+
+impl GlyphCache {
+    /// Creates a new, empty glyph cache.
+    pub fn new(factory: &mut gfx_device_gl::Factory) -> GameResult<GlyphCache> {
+        let (texture, atlas) = create_atlas(factory, INITIAL_ATLAS_SIZE)?;
+        Ok(GlyphCache {
+            cache: new_cache(INITIAL_ATLAS_SIZE),
+            texture: texture,
+            atlas: atlas,
+            queue: Vec::new(),
+            instances: None,
+            instance_capacity: 0,
+        })
+    }
+
+    /// Queues a glyph to be drawn by `draw_queued_text()`, rasterized
+    /// at `dpi_scale` times its size on screen.
+    pub fn queue_glyph(
+        &mut self,
+        font_id: FontId,
+        glyph: rusttype::PositionedGlyph<'static>,
+        dpi_scale: f32,
+        color: Color,
+    ) {
+        self.queue.push(QueuedGlyph {
+            font_id: font_id,
+            glyph: glyph,
+            dpi_scale: dpi_scale,
+            color: color,
+        });
+    }
+
+    /// Rasterizes every queued glyph that isn't in the atlas yet,
+    /// growing the atlas if they don't all fit.
+    fn cache_queued(
+        &mut self,
+        factory: &mut gfx_device_gl::Factory,
+        encoder: &mut gfx::Encoder<gfx_device_gl::Resources, gfx_device_gl::CommandBuffer>,
+    ) -> GameResult<()> {
+        loop {
+            let cache_result = {
+                for queued in &self.queue {
+                    self.cache.queue_glyph(queued.font_id, queued.glyph.clone());
+                }
+                let texture = &self.texture;
+                let mut update_error = None;
+                let cache_result = self.cache.cache_queued(|rect, data| {
+                    let info = texture::ImageInfoCommon {
+                        xoffset: rect.min.x as u16,
+                        yoffset: rect.min.y as u16,
+                        zoffset: 0,
+                        width: rect.width() as u16,
+                        height: rect.height() as u16,
+                        depth: 0,
+                        format: (),
+                        mipmap: 0,
+                    };
+                    // rusttype gives us coverage values; we want white
+                    // pixels with that alpha, so the color can tint them.
+                    let pixels: Vec<[u8; 4]> = data.iter().map(|&a| [255, 255, 255, a]).collect();
+                    let result = encoder
+                        .update_texture::<gfx::format::R8_G8_B8_A8, gfx::format::Srgba8>(
+                            texture,
+                            None,
+                            info,
+                            &pixels,
+                        );
+                    if let Err(e) = result {
+                        update_error = Some(format!("Glyph cache update error: {:?}", e));
+                    }
+                });
+                if let Some(msg) = update_error {
+                    return Err(GameError::RenderError(msg));
+                }
+                cache_result
+            };
+            match cache_result {
+                Ok(()) => return Ok(()),
+                Err(_) => self.grow(factory)?,
+            }
+        }
+    }
+
+    /// Throws away every glyph in the atlas, so they get rasterized
+    /// again as needed.  Queued glyphs are kept.
+    pub fn clear(&mut self) {
+        self.cache = new_cache(self.atlas.width);
+    }
+
+    /// Doubles the size of the atlas, throwing away everything in it.
+    fn grow(&mut self, factory: &mut gfx_device_gl::Factory) -> GameResult<()> {
+        let size = self.atlas.width * 2;
+        if size > MAX_ATLAS_SIZE {
+            let msg = format!(
+                "Queued text needs more room than the largest glyph cache \
+                 ({}x{}) has; try drawing the queued text more often",
+                MAX_ATLAS_SIZE,
+                MAX_ATLAS_SIZE
+            );
+            return Err(GameError::RenderError(msg));
+        }
+        let (texture, atlas) = create_atlas(factory, size)?;
+        self.cache = new_cache(size);
+        self.texture = texture;
+        self.atlas = atlas;
+        Ok(())
+    }
+
+    /// Turns the queued glyphs into instance data, emptying the queue.
+    fn take_instances(&mut self, screen_rect: Rect) -> GameResult<Vec<RectProperties>> {
+        let mut properties = Vec::with_capacity(self.queue.len());
+        for queued in mem::replace(&mut self.queue, Vec::new()) {
+            let rects = self.cache
+                .rect_for(queued.font_id, &queued.glyph)
+                .map_err(|e| GameError::RenderError(format!("Glyph cache error: {:?}", e)))?;
+            // Glyphs such as spaces have nothing to draw.
+            if let Some((uv, screen)) = rects {
+                // The glyph was rasterized at the DPI scale, so
+                // shrink it back down to its size on screen.
+                let scale = 1.0 / queued.dpi_scale;
+                let param = DrawParam {
+                    src: Rect::new(uv.min.x, uv.min.y, uv.width(), uv.height()),
+                    dest: Point::new(
+                        (screen.min.x + screen.max.x) as f32 / 2.0 * scale,
+                        (screen.min.y + screen.max.y) as f32 / 2.0 * scale,
+                    ),
+                    scale: Point::new(scale, scale),
+                    color: Some(queued.color),
+                    ..Default::default()
+                };
+                properties.push(self.atlas.quad_draw_param(param, screen_rect).into());
+            }
+        }
+        Ok(properties)
+    }
+}
+
+fn new_cache(size: u32) -> gpu_cache::Cache {
+    gpu_cache::Cache::new(size, size, 0.1, 0.1)
+}
+
+fn create_atlas(
+    factory: &mut gfx_device_gl::Factory,
+    size: u32,
+) -> GameResult<(gfx::handle::Texture<gfx_device_gl::Resources, AtlasFormat>, Image)> {
+    let kind = texture::Kind::D2(size as u16, size as u16, texture::AaMode::Single);
+    let texture = factory.create_texture::<AtlasFormat>(
+        kind,
+        1,
+        gfx::SHADER_RESOURCE,
+        gfx::memory::Usage::Dynamic,
+        Some(gfx::format::ChannelType::Srgb),
+    )?;
+    let view = factory.view_texture_as_shader_resource::<gfx::format::Srgba8>(
+        &texture,
+        (0, 0),
+        gfx::format::Swizzle::new(),
+    )?;
+    let image = Image {
+        texture: view,
+        raw_texture: texture.raw().clone(),
+        sampler_info: texture::SamplerInfo::new(
+            texture::FilterMethod::Bilinear,
+            texture::WrapMode::Clamp,
+        ),
+        width: size,
+        height: size,
+        uv_scale: (1.0, 1.0),
+    };
+    Ok((texture, image))
+}
+
+/// Draws all text queued with `queue_text()` in a single draw call,
+/// and empties the queue.
+///
+/// The given `DrawParam` is applied to all the queued text as a whole,
+/// as if it was one big image with its origin at (0,0); its color, if
+/// any, tints all of it.
+pub fn draw_queued_text(ctx: &mut Context, param: DrawParam) -> GameResult<()> {
+    let gfx = &mut ctx.gfx_context;
+    if gfx.glyph_cache.queue.is_empty() {
+        return Ok(());
+    }
+    if let Err(e) = gfx.glyph_cache
+        .cache_queued(&mut *gfx.factory, &mut gfx.encoder)
+    {
+        // Drop the glyphs that couldn't be cached, and start the
+        // atlas afresh, so later draws don't keep failing on them.
+        gfx.glyph_cache.queue.clear();
+        gfx.glyph_cache.clear();
+        return Err(e);
+    }
+    let properties = gfx.glyph_cache.take_instances(gfx.screen_rect)?;
+    if properties.is_empty() {
+        return Ok(());
+    }
+
+    let (buffer, atlas) = {
+        let cache = &mut gfx.glyph_cache;
+        if cache.instance_capacity < properties.len() {
+            let capacity = cmp::max(properties.len(), 64).next_power_of_two();
+            let buffer = gfx.factory.create_buffer(
+                capacity,
+                gfx::buffer::Role::Vertex,
+                gfx::memory::Usage::Dynamic,
+                gfx::memory::Bind::empty(),
+            )?;
+            cache.instances = Some(buffer);
+            cache.instance_capacity = capacity;
+        }
+        let buffer = cache
+            .instances
+            .clone()
+            .expect("Glyph cache instance buffer missing; should never happen!");
+        (buffer, cache.atlas.clone())
+    };
+    gfx.encoder.update_buffer(&buffer, &properties[..], 0)?;
+
+    let sampler = gfx.samplers
+        .get_or_insert(atlas.sampler_info, gfx.factory.as_mut());
+    gfx.data.vbuf = gfx.quad_vertex_buffer.clone();
+    gfx.data.tex = (atlas.texture.clone(), sampler);
+    let mut slice = gfx.quad_slice.clone();
+    slice.instances = Some((properties.len() as u32, 0));
+
+    let old_transform = gfx.get_transform();
+    let old_color = gfx.shader_globals.color;
+    gfx.set_transform(old_transform * Matrix::from(param));
+    if let Some(tint) = param.color {
+        let c = Color::from(old_color);
+        gfx.shader_globals.color = [c.r * tint.r, c.g * tint.g, c.b * tint.b, c.a * tint.a];
+    }
+    gfx.update_globals()?;
+    let old_buffer = mem::replace(&mut gfx.data.rect_properties, buffer);
+    let result = gfx.draw(&slice);
+    gfx.data.rect_properties = old_buffer;
+    gfx.set_transform(old_transform);
+    gfx.shader_globals.color = old_color;
+    gfx.update_globals()?;
+    result
+}
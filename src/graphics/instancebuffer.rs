@@ -0,0 +1,116 @@
+//! Per-instance data on the graphics card, for drawing many
+//! copies of one thing with a single instanced draw call.
+
+use super::*;
+
+/// A buffer of per-instance `RectProperties` on the graphics card,
+/// and the information needed to tell whether it's up to date.
+/// `SpriteBatch` and `ParticleSystem` both draw through one of these.
+#[derive(Debug)]
+pub struct InstanceBuffer {
+    buffer: Option<gfx::handle::Buffer<gfx_device_gl::Resources, RectProperties>>,
+    capacity: usize,
+    count: usize,
+    dirty: bool,
+    screen_rect: Rect,
+}
+
+impl InstanceBuffer {
+    pub fn new() -> Self {
+        InstanceBuffer {
+            buffer: None,
+            capacity: 0,
+            count: 0,
+            dirty: true,
+            screen_rect: Rect::zero(),
+        }
+    }
+
+    /// Returns how many instances fit in the buffer before
+    /// it has to be recreated.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns whether the instances have changed since
+    /// they were last uploaded.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Notes that the instances have changed, so they get
+    /// uploaded again before they are next drawn.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Returns whether the instances need uploading again before
+    /// drawing to a screen with the given coordinates.
+    ///
+    /// The per-instance scale depends on which way up the screen
+    /// coordinates are, so changing those means re-uploading too.
+    pub fn needs_upload(&self, screen_rect: Rect) -> bool {
+        self.dirty || self.screen_rect != screen_rect
+    }
+
+    /// Sends the given instances to the graphics card, growing the
+    /// buffer if it can't hold them, or `min_capacity`, already.
+    pub fn upload(
+        &mut self,
+        gfx: &mut GraphicsContext,
+        properties: &[RectProperties],
+        min_capacity: usize,
+    ) -> GameResult<()> {
+        if self.buffer.is_none() || self.capacity < properties.len() {
+            let wanted = cmp::max(properties.len(), min_capacity);
+            let capacity = wanted.next_power_of_two();
+            let buffer = gfx.factory.create_buffer(
+                capacity,
+                gfx::buffer::Role::Vertex,
+                gfx::memory::Usage::Dynamic,
+                gfx::memory::Bind::empty(),
+            )?;
+            self.buffer = Some(buffer);
+            self.capacity = capacity;
+        }
+        if let Some(ref buffer) = self.buffer {
+            gfx.encoder.update_buffer(buffer, properties, 0)?;
+        }
+        self.count = properties.len();
+        self.dirty = false;
+        self.screen_rect = gfx.screen_rect;
+        Ok(())
+    }
+
+    /// Draws one copy of whatever is bound per instance, with the
+    /// given `DrawParam` applied on top of all of them and its color,
+    /// if any, tinting them all.
+    pub fn draw(
+        &self,
+        gfx: &mut GraphicsContext,
+        mut slice: gfx::Slice<gfx_device_gl::Resources>,
+        param: DrawParam,
+    ) -> GameResult<()> {
+        let buffer = match self.buffer {
+            Some(ref buffer) if self.count > 0 => buffer.clone(),
+            _ => return Ok(()),
+        };
+        slice.instances = Some((self.count as u32, 0));
+
+        let old_transform = gfx.get_transform();
+        let old_color = gfx.shader_globals.color;
+        gfx.set_transform(old_transform * Matrix::from(param));
+        if let Some(tint) = param.color {
+            let c = Color::from(old_color);
+            gfx.shader_globals.color = [c.r * tint.r, c.g * tint.g, c.b * tint.b, c.a * tint.a];
+        }
+        gfx.update_globals()?;
+        let old_buffer = mem::replace(&mut gfx.data.rect_properties, buffer);
+        let result = gfx.draw(&slice);
+        gfx.data.rect_properties = old_buffer;
+        gfx.set_transform(old_transform);
+        gfx.shader_globals.color = old_color;
+        gfx.update_globals()?;
+        result
+    }
+}
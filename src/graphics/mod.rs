@@ -6,16 +6,24 @@
 //! The default coordinate system has the origin in the upper-left
 //! corner of the screen.
 
+use std::cell::RefCell;
+use std::cmp;
+use std::mem;
 use std::fmt;
 use std::path;
 use std::convert::From;
 use std::collections::HashMap;
 use std::io::Read;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
 use std::u16;
 
 use sdl2;
+use euclid;
 use image;
+use rusttype;
 use gfx;
+use gfx::memory::Typed;
 use gfx::texture;
 use gfx::traits::Device;
 use gfx::traits::FactoryExt;
@@ -28,12 +36,41 @@ use context::Context;
 use GameError;
 use GameResult;
 
+mod animation;
+mod atlas;
+mod bmfont;
+mod camera;
+mod canvas;
+mod glyphcache;
+mod instancebuffer;
+mod ninepatch;
+mod particles;
+mod screenshot;
+mod shader;
+mod svg;
 mod text;
+mod tilemap;
 mod types;
+mod vectorpath;
 pub mod spritebatch;
 
+pub use self::animation::*;
+pub use self::atlas::*;
+pub use self::bmfont::*;
+pub use self::camera::*;
+pub use self::canvas::*;
+pub use self::glyphcache::draw_queued_text;
+pub use self::ninepatch::*;
+pub use self::particles::*;
+pub use self::screenshot::*;
+pub use self::shader::*;
+pub use self::svg::*;
 pub use self::text::*;
+pub use self::tilemap::*;
 pub use self::types::*;
+pub use self::vectorpath::*;
+
+use self::instancebuffer::InstanceBuffer;
 
 const GL_MAJOR_VERSION: u8 = 3;
 const GL_MINOR_VERSION: u8 = 2;
@@ -44,34 +81,45 @@ const QUAD_VERTS: [Vertex; 4] = [
     Vertex {
         pos: [-0.5, -0.5],
         uv: [0.0, 0.0],
+        color: [1.0, 1.0, 1.0, 1.0],
     },
     Vertex {
         pos: [0.5, -0.5],
         uv: [1.0, 0.0],
+        color: [1.0, 1.0, 1.0, 1.0],
     },
     Vertex {
         pos: [0.5, 0.5],
         uv: [1.0, 1.0],
+        color: [1.0, 1.0, 1.0, 1.0],
     },
     Vertex {
         pos: [-0.5, 0.5],
         uv: [0.0, 1.0],
+        color: [1.0, 1.0, 1.0, 1.0],
     },
 ];
 
 const QUAD_INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
 
+/// How closely round points follow a true circle.
+const POINT_TOLERANCE: f32 = 0.1;
+
+const DEFAULT_VERTEX_SHADER: &[u8] = include_bytes!("shader/basic_150.glslv");
+const DEFAULT_PIXEL_SHADER: &[u8] = include_bytes!("shader/basic_150.glslf");
+
 type ColorFormat = gfx::format::Srgba8;
-// I don't know why this gives a dead code warning
-// since this type is definitely used... oh well.
-#[allow(dead_code)]
 type DepthFormat = gfx::format::DepthStencil;
 
 gfx_defines!{
-    /// Internal structure containing vertex data.
+    /// A vertex of a `Mesh`: its position, its texture coordinates,
+    /// from (0, 0) at the top left of the mesh's image to (1, 1) at
+    /// the bottom right, and its color.  The default shader passes
+    /// the color to the pixel shader as part of `v_Color`.
     vertex Vertex {
         pos: [f32; 2] = "a_Pos",
         uv: [f32; 2] = "a_Uv",
+        color: [f32; 4] = "a_VertColor",
     }
 
     /// Internal structure containing global shader state.
@@ -80,23 +128,29 @@ gfx_defines!{
         color: [f32; 4] = "u_Color",
     }
 
-    /// Internal structure containing values that are different for each rect.
-    constant RectProperties {
-        src: [f32; 4] = "u_Src",
-        dest: [f32; 2] = "u_Dest",
-        scale: [f32;2] = "u_Scale",
-        offset: [f32;2] = "u_Offset",
-        shear: [f32;2] = "u_Shear",
-        rotation: f32 = "u_Rotation",
+    /// Internal structure containing values that are different for each
+    /// drawn rect.  These are sent to the shader as per-instance vertex
+    /// attributes, so a whole `SpriteBatch` can be drawn in one call.
+    vertex RectProperties {
+        src: [f32; 4] = "a_Src",
+        dest: [f32; 2] = "a_Dest",
+        scale: [f32;2] = "a_Scale",
+        offset: [f32;2] = "a_Offset",
+        shear: [f32;2] = "a_Shear",
+        rotation: f32 = "a_Rotation",
+        color: [f32; 4] = "a_Color",
     }
 
     pipeline pipe {
         vbuf: gfx::VertexBuffer<Vertex> = (),
         tex: gfx::TextureSampler<[f32; 4]> = "t_Texture",
         globals: gfx::ConstantBuffer<Globals> = "Globals",
-        rect_properties: gfx::ConstantBuffer<RectProperties> = "RectProperties",
+        rect_properties: gfx::InstanceBuffer<RectProperties> = (),
+        user_consts: gfx::RawConstantBuffer = "Consts",
         out: gfx::BlendTarget<ColorFormat> =
           ("Target0", gfx::state::MASK_ALL, gfx::preset::blend::ALPHA),
+        stencil: gfx::StencilTarget<DepthFormat> = StencilMode::Off.into(),
+        scissor: gfx::Scissor = (),
     }
 }
 
@@ -109,6 +163,7 @@ impl Default for RectProperties {
             offset: [0.0, 0.0],
             shear: [0.0, 0.0],
             rotation: 0.0,
+            color: types::WHITE.into(),
         }
     }
 }
@@ -122,10 +177,22 @@ impl From<DrawParam> for RectProperties {
             offset: p.offset.into(),
             shear: p.shear.into(),
             rotation: p.rotation,
+            color: p.color.unwrap_or(types::WHITE).into(),
         }
     }
 }
 
+impl From<DrawParam> for Matrix {
+    /// Turns the given `DrawParam` into the equivalent transform,
+    /// the same way the shader applies it to each vertex:
+    /// scale, shear, offset, rotation and then translation.
+    fn from(p: DrawParam) -> Self {
+        Matrix::translation(p.dest.x, p.dest.y) * Matrix::rotation(p.rotation) *
+            Matrix::translation(p.offset.x, p.offset.y) *
+            Matrix::shear(p.shear.x, p.shear.y) * Matrix::scale(p.scale.x, p.scale.y)
+    }
+}
+
 /// A structure for conveniently storing Sampler's, based off
 /// their `SamplerInfo`.
 ///
@@ -163,6 +230,129 @@ where
     }
 }
 
+/// Which way a pipeline uses the stencil buffer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum StencilMode {
+    /// Stencil is neither tested nor written.
+    Off,
+    /// Draws write the reference value into the stencil buffer
+    /// instead of drawing anything to the color buffer.
+    Write,
+    /// Draws only happen where the stencil test passes.
+    Test(StencilCompare),
+}
+
+impl From<StencilMode> for gfx::state::Stencil {
+    fn from(mode: StencilMode) -> Self {
+        use gfx::state::{Comparison, Stencil, StencilOp};
+        let keep = (StencilOp::Keep, StencilOp::Keep, StencilOp::Keep);
+        match mode {
+            StencilMode::Off => Stencil::new(Comparison::Always, 0xFF, keep),
+            StencilMode::Write => Stencil::new(
+                Comparison::Always,
+                0xFF,
+                (StencilOp::Keep, StencilOp::Keep, StencilOp::Replace),
+            ),
+            StencilMode::Test(compare) => Stencil::new(compare, 0xFF, keep),
+        }
+    }
+}
+
+/// The compiled pipelines of a shader along with the buffer
+/// holding its user-defined uniforms, as stored in the
+/// `GraphicsContext`.  The `Shader` type is the user-facing
+/// handle to one of these.
+///
+/// There is one pipeline per combination of `BlendMode` and
+/// `StencilMode`, all sharing the same compiled shader program.
+/// The ones for plain drawing are created up front so switching
+/// blend modes is cheap; the stencil ones are created the
+/// first time they are needed.
+struct ShaderProgram<R>
+where
+    R: gfx::Resources,
+{
+    shader_set: gfx::ShaderSet<R>,
+    consts_name: String,
+    psos: HashMap<(BlendMode, StencilMode), gfx::PipelineState<R, pipe::Meta>>,
+    buffer: gfx::handle::RawBuffer<R>,
+}
+
+/// Shader IDs are unique across all contexts, so a `Shader` used
+/// with a `Context` it wasn't created on is never mistaken for
+/// one of that context's own shaders.
+static NEXT_SHADER_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+const BLEND_MODES: [BlendMode; 8] = [
+    BlendMode::Add,
+    BlendMode::Subtract,
+    BlendMode::Alpha,
+    BlendMode::Multiply,
+    BlendMode::Replace,
+    BlendMode::Premultiplied,
+    BlendMode::Lighten,
+    BlendMode::Darken,
+];
+
+impl ShaderProgram<gfx_device_gl::Resources> {
+    /// Compiles the given shader sources and creates a pipeline
+    /// for each `BlendMode` from them.
+    ///
+    /// `consts_name` is the name of the uniform block that the
+    /// shader's user-defined constants are bound to, which are
+    /// read from `buffer`.
+    fn new(
+        factory: &mut gfx_device_gl::Factory,
+        vertex_source: &[u8],
+        pixel_source: &[u8],
+        consts_name: &str,
+        buffer: gfx::handle::RawBuffer<gfx_device_gl::Resources>,
+    ) -> GameResult<Self> {
+        let shader_set = factory.create_shader_set(vertex_source, pixel_source)?;
+        let mut program = ShaderProgram {
+            shader_set: shader_set,
+            consts_name: consts_name.to_owned(),
+            psos: HashMap::new(),
+            buffer: buffer,
+        };
+        for mode in &BLEND_MODES {
+            program.get_or_create_pso(factory, *mode, StencilMode::Off)?;
+        }
+        Ok(program)
+    }
+
+    /// Returns the pipeline for the given modes, creating it if necessary.
+    fn get_or_create_pso(
+        &mut self,
+        factory: &mut gfx_device_gl::Factory,
+        blend_mode: BlendMode,
+        stencil_mode: StencilMode,
+    ) -> GameResult<&gfx::PipelineState<gfx_device_gl::Resources, pipe::Meta>> {
+        let key = (blend_mode, stencil_mode);
+        if !self.psos.contains_key(&key) {
+            // Writing to the stencil buffer shouldn't draw anything visible.
+            let color_mask = match stencil_mode {
+                StencilMode::Write => gfx::state::MASK_NONE,
+                _ => gfx::state::MASK_ALL,
+            };
+            let init = pipe::Init {
+                user_consts: &self.consts_name,
+                out: ("Target0", color_mask, blend_mode.into()),
+                stencil: stencil_mode.into(),
+                ..pipe::new()
+            };
+            let pso = factory.create_pipeline_state(
+                &self.shader_set,
+                gfx::Primitive::TriangleList,
+                gfx::state::Rasterizer::new_fill(),
+                init,
+            )?;
+            self.psos.insert(key, pso);
+        }
+        Ok(&self.psos[&key])
+    }
+}
+
 /// A structure that contains graphics state.
 /// For instance, background and foreground colors,
 /// window info, DPI, rendering pipeline state, etc.
@@ -180,27 +370,46 @@ where
     background_color: Color,
     shader_globals: Globals,
     white_image: Image,
-    line_width: f32,
+    line_style: LineStyle,
     point_size: f32,
+    point_style: PointStyle,
     screen_rect: Rect,
+    projection: Matrix,
+    transform_stack: Vec<Matrix>,
+    blend_mode: BlendMode,
+    stencil_mode: StencilMode,
+    scissor: Option<Rect>,
     dpi: (f32, f32, f32),
+    hidpi: bool,
+    dpi_scale: f32,
+    power_of_two_textures: bool,
 
+    headless: bool,
     window: sdl2::video::Window,
     #[allow(dead_code)]
     gl_context: sdl2::video::GLContext,
     device: Box<D>,
     factory: Box<F>,
     encoder: gfx::Encoder<R, C>,
-    // color_view: gfx::handle::RenderTargetView<R, gfx::format::Srgba8>,
-    #[allow(dead_code)]
-    depth_view: gfx::handle::DepthStencilView<R, gfx::format::DepthStencil>,
-
-    pso: gfx::PipelineState<R, pipe::Meta>,
+    screen_render_target: gfx::handle::RenderTargetView<R, ColorFormat>,
+    /// The texture behind the current render target, if it has one;
+    /// the window's framebuffer doesn't.
+    target_texture: Option<gfx::handle::RawTexture<R>>,
+    screen_texture: Option<gfx::handle::RawTexture<R>>,
+    depth_view: gfx::handle::DepthStencilView<R, DepthFormat>,
+
+    shaders: HashMap<ShaderId, ShaderProgram<R>>,
+    default_shader: ShaderId,
+    /// IDs of `Shader`s that have been dropped, whose programs are
+    /// freed the next time a shader is added or drawn with.
+    dropped_shaders: Rc<RefCell<Vec<ShaderId>>>,
+    current_shader: Rc<RefCell<Option<ShaderId>>>,
     data: pipe::Data<R>,
     quad_slice: gfx::Slice<R>,
     quad_vertex_buffer: gfx::handle::Buffer<R, Vertex>,
     default_sampler_info: texture::SamplerInfo,
     samplers: SamplerCache<R>,
+    glyph_cache: glyphcache::GlyphCache,
 }
 
 impl<R, F, C, D> fmt::Debug for GraphicsContextGeneric<R, F, C, D>
@@ -264,6 +473,9 @@ impl GraphicsContext {
         screen_height: u32,
         vsync: bool,
         resize: bool,
+        headless: bool,
+        hidpi: bool,
+        power_of_two_textures: bool,
     ) -> GameResult<GraphicsContext> {
         // WINDOW SETUP
         let gl = video.gl_attr();
@@ -277,37 +489,71 @@ impl GraphicsContext {
         if resize {
             window_builder.resizable();
         }
-        let (window, gl_context, device, mut factory, color_view, depth_view) =
+        if headless {
+            window_builder.hidden();
+        }
+        if hidpi {
+            window_builder.allow_highdpi();
+        }
+        let (window, gl_context, device, mut factory, mut color_view, mut depth_view) =
             gfx_window_sdl::init(window_builder)?;
 
         // println!("Vsync enabled: {}", vsync);
-        let vsync_int = if vsync { 1 } else { 0 };
+        let vsync_int = if vsync && !headless { 1 } else { 0 };
         video.gl_set_swap_interval(vsync_int);
 
-        let display_index = window.display_index()?;
-        let dpi = window.subsystem().display_dpi(display_index)?;
+        let dpi = if headless {
+            // There's no real display to ask, so pretend.
+            (96.0, 96.0, 96.0)
+        } else {
+            let display_index = window.display_index()?;
+            window.subsystem().display_dpi(display_index)?
+        };
+
+        // Without a window to show things in, the "screen" is
+        // just an off-screen render target of the same size.
+        let mut screen_texture = None;
+        if headless {
+            let (w, h) = (screen_width as u16, screen_height as u16);
+            let (texture, _, target) = factory.create_render_target::<ColorFormat>(w, h)?;
+            screen_texture = Some(texture.raw().clone());
+            color_view = target;
+            depth_view = factory.create_depth_stencil_view_only::<DepthFormat>(w, h)?;
+        }
 
         // GFX SETUP
         let encoder: gfx::Encoder<gfx_device_gl::Resources, gfx_device_gl::CommandBuffer> =
             factory.create_command_buffer().into();
 
-        let pso = factory.create_pipeline_simple(
-            include_bytes!("shader/basic_150.glslv"),
-            include_bytes!("shader/basic_150.glslf"),
-            pipe::new(),
+        // The default shader doesn't read any user-defined
+        // uniforms, but the pipeline data still needs a buffer.
+        let default_consts = factory.create_constant_buffer::<EmptyConst>(1);
+        let default_shader = ShaderProgram::new(
+            &mut factory,
+            DEFAULT_VERTEX_SHADER,
+            DEFAULT_PIXEL_SHADER,
+            "Consts",
+            default_consts.raw().clone(),
         )?;
 
-        let (quad_vertex_buffer, quad_slice) =
+        let (quad_vertex_buffer, mut quad_slice) =
             factory.create_vertex_buffer_with_slice(&QUAD_VERTS, &QUAD_INDICES[..]);
+        quad_slice.instances = Some((1, 0));
 
-        let rect_props = factory.create_constant_buffer(1);
+        let rect_props = factory.create_buffer(
+            1,
+            gfx::buffer::Role::Vertex,
+            gfx::memory::Usage::Dynamic,
+            gfx::memory::Bind::empty(),
+        )?;
         let globals_buffer = factory.create_constant_buffer(1);
         let mut samplers: SamplerCache<gfx_device_gl::Resources> = SamplerCache::new();
         let sampler_info =
             texture::SamplerInfo::new(texture::FilterMethod::Bilinear, texture::WrapMode::Clamp);
         let sampler = samplers.get_or_insert(sampler_info, &mut factory);
         let white_image =
-            Image::make_raw(&mut factory, &sampler_info, 1, 1, &[255, 255, 255, 255])?;
+            Image::make_raw(&mut factory, &sampler_info, 1, 1, &[255, 255, 255, 255], false)?;
+        let glyph_cache = glyphcache::GlyphCache::new(&mut factory)?;
         let texture = white_image.texture.clone();
 
         let data = pipe::Data {
@@ -315,7 +561,15 @@ impl GraphicsContext {
             tex: (texture, sampler),
             rect_properties: rect_props,
             globals: globals_buffer,
-            out: color_view,
+            user_consts: default_shader.buffer.clone(),
+            out: color_view.clone(),
+            stencil: (depth_view.clone(), (0, 0)),
+            scissor: gfx::Rect {
+                x: 0,
+                y: 0,
+                w: screen_width as u16,
+                h: screen_height as u16,
+            },
         };
 
         // Set initial uniform values
@@ -328,28 +582,49 @@ impl GraphicsContext {
             color: types::WHITE.into(),
         };
 
+        let default_shader_id = NEXT_SHADER_ID.fetch_add(1, Ordering::Relaxed);
+        let mut shaders = HashMap::new();
+        shaders.insert(default_shader_id, default_shader);
+
         let mut gfx = GraphicsContext {
             background_color: Color::new(0.1, 0.2, 0.3, 1.0),
             shader_globals: globals,
-            line_width: 1.0,
+            line_style: LineStyle::default(),
             point_size: 1.0,
+            point_style: PointStyle::Square,
             white_image: white_image,
             screen_rect: Rect::new(left, bottom, (right - left), (top - bottom)),
+            projection: Matrix::identity(),
+            transform_stack: vec![Matrix::identity()],
+            blend_mode: BlendMode::default(),
+            stencil_mode: StencilMode::Off,
+            scissor: None,
             dpi: dpi,
+            hidpi: hidpi,
+            dpi_scale: 1.0,
+            power_of_two_textures: power_of_two_textures,
+            headless: headless,
 
             window: window,
             gl_context: gl_context,
             device: Box::new(device),
             factory: Box::new(factory),
             encoder: encoder,
+            screen_render_target: color_view,
+            screen_texture: screen_texture.clone(),
+            target_texture: screen_texture,
             depth_view: depth_view,
 
-            pso: pso,
+            shaders: shaders,
+            default_shader: default_shader_id,
+            dropped_shaders: Rc::new(RefCell::new(Vec::new())),
+            current_shader: Rc::new(RefCell::new(None)),
             data: data,
             quad_slice: quad_slice,
             quad_vertex_buffer: quad_vertex_buffer,
             default_sampler_info: sampler_info,
             samplers: samplers,
+            glyph_cache: glyph_cache,
         };
 
 
@@ -363,6 +638,7 @@ impl GraphicsContext {
         };
         gfx.set_graphics_rect(rect);
         gfx.update_globals()?;
+        gfx.dpi_scale = gfx.calculate_dpi_scale();
         Ok(gfx)
     }
 
@@ -379,6 +655,89 @@ impl GraphicsContext {
         Ok(())
     }
 
+    /// Draws the given slice with whichever shader is currently
+    /// active, falling back to the built-in one, using the
+    /// current blend mode, stencil and scissor settings.
+    fn draw(&mut self, slice: &gfx::Slice<gfx_device_gl::Resources>) -> GameResult<()> {
+        self.data.scissor = self.scissor_rect();
+        self.free_dropped_shaders();
+        let id = (*self.current_shader.borrow()).unwrap_or(self.default_shader);
+        let shader = match self.shaders.get_mut(&id) {
+            Some(shader) => shader,
+            None => {
+                let msg = format!(
+                    "Tried to draw with shader {}, which has been dropped \
+                     or was created on another Context",
+                    id
+                );
+                return Err(GameError::RenderError(msg));
+            }
+        };
+        self.data.user_consts = shader.buffer.clone();
+        let pso = shader.get_or_create_pso(&mut *self.factory, self.blend_mode, self.stencil_mode)?;
+        self.encoder.draw(slice, pso, &self.data);
+        Ok(())
+    }
+
+    /// Stores a newly compiled shader program, returning its ID.
+    fn add_shader(&mut self, program: ShaderProgram<gfx_device_gl::Resources>) -> ShaderId {
+        self.free_dropped_shaders();
+        let id = NEXT_SHADER_ID.fetch_add(1, Ordering::Relaxed);
+        self.shaders.insert(id, program);
+        id
+    }
+
+    /// Frees the programs of any `Shader`s that have been dropped.
+    fn free_dropped_shaders(&mut self) {
+        for id in self.dropped_shaders.borrow_mut().drain(..) {
+            self.shaders.remove(&id);
+        }
+    }
+
+    /// Turns the current scissor rect into the form gfx wants:
+    /// in pixels, corner-based, Y pointing up and clamped to the
+    /// current render target.  With no scissor rect, it covers
+    /// the whole render target.
+    fn scissor_rect(&self) -> gfx::Rect {
+        let (target_w, target_h, _, _) = self.data.out.get_dimensions();
+        let rect = match self.scissor {
+            None => {
+                return gfx::Rect {
+                    x: 0,
+                    y: 0,
+                    w: target_w,
+                    h: target_h,
+                }
+            }
+            Some(rect) => rect,
+        };
+        // Map the corners from screen coordinates to pixels the
+        // same way the projection matrix does.  The screen rect
+        // may be flipped either way, so sort the results afterwards.
+        let screen = self.screen_rect;
+        let to_pixel_x = |x: f32| {
+            let px = (x - (screen.x - screen.w / 2.0)) / screen.w * target_w as f32;
+            px.max(0.0).min(target_w as f32)
+        };
+        let to_pixel_y = |y: f32| {
+            let py = ((screen.y + screen.h / 2.0) - y) / screen.h * target_h as f32;
+            py.max(0.0).min(target_h as f32)
+        };
+        let x1 = to_pixel_x(rect.x - rect.w / 2.0);
+        let x2 = to_pixel_x(rect.x + rect.w / 2.0);
+        let y1 = to_pixel_y(rect.y - rect.h / 2.0);
+        let y2 = to_pixel_y(rect.y + rect.h / 2.0);
+        let (left, right) = (x1.min(x2), x1.max(x2));
+        let (bottom, top) = (y1.min(y2), y1.max(y2));
+        gfx::Rect {
+            x: left.round() as u16,
+            y: bottom.round() as u16,
+            w: (right - left).round() as u16,
+            h: (top - bottom).round() as u16,
+        }
+    }
+
+
     /// Returns a reference to the SDL window.
     /// Ideally you should not need to use this because ggez
     /// would provide all the functions you need without having
@@ -404,6 +763,72 @@ impl GraphicsContext {
         self.window.drawable_size()
     }
 
+    /// Works out how many drawable pixels there are per unit of window
+    /// size.  Without `Conf::hidpi` the drawable is always the size of
+    /// the window.
+    fn calculate_dpi_scale(&self) -> f32 {
+        if !self.hidpi {
+            return 1.0;
+        }
+        let (window_w, _) = self.window.size();
+        let (drawable_w, _) = self.window.drawable_size();
+        if window_w == 0 {
+            1.0
+        } else {
+            drawable_w as f32 / window_w as f32
+        }
+    }
+
+    /// Re-reads the display's DPI and the window's drawable size,
+    /// which change when the window is resized or moved to another
+    /// display, and resizes the screen's render target to match.
+    /// If the DPI scale changed, the glyph cache is emptied so text
+    /// gets rasterized again at the new scale; `Text` objects notice
+    /// the next time they are drawn.
+    ///
+    /// Returns the new DPI scale if it changed.  Without a window,
+    /// nothing ever changes.  If SDL can't tell the new display's DPI,
+    /// the old one is kept; the DPI scale doesn't depend on it.
+    ///
+    /// This is only public so the event loop can call it.
+    #[doc(hidden)]
+    pub fn update_dpi(&mut self) -> GameResult<Option<f32>> {
+        if self.headless {
+            return Ok(None);
+        }
+        let display_index = self.window.display_index()?;
+        if let Ok(dpi) = self.window.subsystem().display_dpi(display_index) {
+            self.dpi = dpi;
+        }
+
+        let (w, h) = self.window.drawable_size();
+        let (old_w, old_h, _, _) = self.screen_render_target.get_dimensions();
+        if (w as u16, h as u16) != (old_w, old_h) {
+            let dim = (w as u16, h as u16, 1, texture::AaMode::Single);
+            let (color_view, depth_view) = gfx_device_gl::create_main_targets_raw(
+                dim,
+                <ColorFormat as gfx::format::Formatted>::get_format().0,
+                <DepthFormat as gfx::format::Formatted>::get_format().0,
+            );
+            self.screen_render_target = Typed::new(color_view);
+            self.depth_view = Typed::new(depth_view);
+            // A selected `Canvas` stays selected; `set_canvas(None)`
+            // picks up the new views later.
+            if self.target_texture.is_none() {
+                self.data.out = self.screen_render_target.clone();
+                self.data.stencil.0 = self.depth_view.clone();
+            }
+        }
+
+        let scale = self.calculate_dpi_scale();
+        if (scale - self.dpi_scale).abs() < 0.001 {
+            return Ok(None);
+        }
+        self.dpi_scale = scale;
+        self.glyph_cache.clear();
+        Ok(Some(scale))
+    }
+
     /// EXPERIMENTAL function to get the gfx-rs `Factory` object.
     pub fn get_factory(&mut self) -> &mut gfx_device_gl::Factory {
         &mut self.factory
@@ -440,17 +865,45 @@ impl GraphicsContext {
 
     /// Shortcut function to set the screen rect ortho mode
     /// to a given `Rect`.
-    /// 
+    ///
     /// Call `update_globals()` to apply them after calling this.
     fn set_graphics_rect(&mut self, rect: Rect) {
         self.screen_rect = rect;
         let half_width = rect.w / 2.0;
         let half_height = rect.h / 2.0;
-        self.shader_globals.transform = ortho(
+        self.projection = ortho(
             rect.x - half_width, rect.x + half_width, 
             rect.y + half_height, rect.y - half_height, 
             1.0, -1.0
-        );
+        ).into();
+        self.calculate_transform_matrix();
+    }
+
+    /// Returns the current top of the transform stack.
+    fn get_transform(&self) -> Matrix {
+        *self.transform_stack
+            .last()
+            .expect("Transform stack is empty; should never happen!")
+    }
+
+    /// Replaces the top of the transform stack with the given matrix.
+    ///
+    /// Call `update_globals()` to apply it after calling this.
+    fn set_transform(&mut self, transform: Matrix) {
+        {
+            let top = self.transform_stack
+                .last_mut()
+                .expect("Transform stack is empty; should never happen!");
+            *top = transform;
+        }
+        self.calculate_transform_matrix();
+    }
+
+    /// Combines the projection and the current transform into
+    /// the matrix actually sent to the shader.
+    fn calculate_transform_matrix(&mut self) {
+        let final_transform = self.projection * self.get_transform();
+        self.shader_globals.transform = final_transform.into();
     }
 }
 
@@ -494,10 +947,15 @@ fn ortho(left: f32, right: f32, top: f32, bottom: f32, far: f32, near: f32) -> [
 
 
 /// Clear the screen to the background color.
+/// If a `Canvas` is currently selected with `set_canvas()`,
+/// the `Canvas` is cleared instead.
+///
+/// This also clears the stencil buffer.
 pub fn clear(ctx: &mut Context) {
     let gfx = &mut ctx.gfx_context;
     gfx.encoder
         .clear(&gfx.data.out, gfx.background_color.into());
+    gfx.encoder.clear_stencil(&gfx.data.stencil.0, 0);
 }
 
 /// Draws the given `Drawable` object to the screen by calling its
@@ -521,24 +979,37 @@ pub fn present(ctx: &mut Context) {
     // to do their own gfx drawing.  HOWEVER, the whole pipeline type
     // thing is a bigger hurdle, so this is fine for now.
     gfx.encoder.flush(&mut *gfx.device);
-    gfx.window.gl_swap_window();
+    if !gfx.headless {
+        gfx.window.gl_swap_window();
+    }
     gfx.device.cleanup();
 }
 
-/*
-// Draw an arc.
-// Punting on this until later.
-pub fn arc(_ctx: &mut Context,
-           _mode: DrawMode,
-           _point: Point,
-           _radius: f32,
-           _angle1: f32,
-           _angle2: f32,
-           _segments: u32)
-           -> GameResult<()> {
-    unimplemented!();
+/// Returns whether the `Context` was created without a visible
+/// window, as set by `Conf::headless`.  In that case everything
+/// that would be drawn to the screen is drawn to an off-screen
+/// render target instead, and `present()` doesn't show it anywhere.
+pub fn is_headless(ctx: &Context) -> bool {
+    ctx.gfx_context.headless
+}
+
+/// Draw an arc of a circle centered on `point`, going clockwise
+/// from `angle1` to `angle2` (in radians, with 0 pointing right).
+/// `arc_type` specifies how the ends of the arc are connected.
+#[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
+pub fn arc(
+    ctx: &mut Context,
+    mode: DrawMode,
+    arc_type: ArcType,
+    point: Point,
+    radius: f32,
+    angle1: f32,
+    angle2: f32,
+    tolerance: f32,
+) -> GameResult<()> {
+    let m = Mesh::new_arc(ctx, mode, arc_type, point, radius, angle1, angle2, tolerance)?;
+    m.draw(ctx, Point::default(), 0.0)
 }
-*/
 
 /// Draw a circle.
 pub fn circle(
@@ -567,28 +1038,87 @@ pub fn ellipse(
 
 /// Draws a line of one or more connected segments.
 pub fn line(ctx: &mut Context, points: &[Point]) -> GameResult<()> {
-    let w = ctx.gfx_context.line_width;
+    let w = ctx.gfx_context.line_style.width;
     let m = Mesh::new_line(ctx, points, w)?;
     m.draw(ctx, Point::default(), 0.0)
 }
 
-/// Draws points.
+/// Draws a line of one or more connected segments in the given
+/// line style, instead of the current one.
+pub fn line_ex(ctx: &mut Context, points: &[Point], style: &LineStyle) -> GameResult<()> {
+    draw_outline(ctx, style, |builder| {
+        builder.polyline(DrawMode::Line, points, WHITE);
+    })
+}
+
+/// Draws points, as squares or circles `point_size` across;
+/// see `set_point_style()`.
 pub fn points(ctx: &mut Context, points: &[Point]) -> GameResult<()> {
+    if points.is_empty() {
+        return Ok(());
+    }
     let size = ctx.gfx_context.point_size;
-    for p in points {
-        let r = Rect::new(p.x, p.y, size, size);
-        rectangle(ctx, DrawMode::Fill, r)?;
+    let style = ctx.gfx_context.point_style;
+    let mut builder = MeshBuilder::new();
+    for &p in points {
+        match style {
+            PointStyle::Square => {
+                builder.rectangle(DrawMode::Fill, Rect::new(p.x, p.y, size, size), WHITE)
+            }
+            PointStyle::Round => builder.circle(DrawMode::Fill, p, size / 2.0, POINT_TOLERANCE, WHITE),
+        };
     }
-    Ok(())
+    let m = builder.build(ctx)?;
+    m.draw(ctx, Point::default(), 0.0)
 }
 
 /// Draws a closed polygon
 pub fn polygon(ctx: &mut Context, mode: DrawMode, vertices: &[Point]) -> GameResult<()> {
-    let w = ctx.gfx_context.line_width;
+    let w = ctx.gfx_context.line_style.width;
     let m = Mesh::new_polygon(ctx, mode, vertices, w)?;
     m.draw(ctx, Point::default(), 0.0)
 }
 
+/// Outlines a closed polygon in the given line style,
+/// instead of the current one.
+pub fn polygon_ex(ctx: &mut Context, vertices: &[Point], style: &LineStyle) -> GameResult<()> {
+    draw_outline(ctx, style, |builder| {
+        builder.polygon(DrawMode::Line, vertices, WHITE);
+    })
+}
+
+/// Outlines a circle in the given line style, instead of the current one.
+pub fn circle_ex(
+    ctx: &mut Context,
+    point: Point,
+    radius: f32,
+    tolerance: f32,
+    style: &LineStyle,
+) -> GameResult<()> {
+    draw_outline(ctx, style, |builder| {
+        builder.circle(DrawMode::Line, point, radius, tolerance, WHITE);
+    })
+}
+
+/// Outlines a rectangle in the given line style, instead of the current one.
+pub fn rectangle_ex(ctx: &mut Context, rect: Rect, style: &LineStyle) -> GameResult<()> {
+    draw_outline(ctx, style, |builder| {
+        builder.rectangle(DrawMode::Line, rect, WHITE);
+    })
+}
+
+/// Draws the outlines `add` adds to a `MeshBuilder` in the given style.
+fn draw_outline<F>(ctx: &mut Context, style: &LineStyle, add: F) -> GameResult<()>
+where
+    F: FnOnce(&mut MeshBuilder),
+{
+    let mut builder = MeshBuilder::new();
+    builder.set_line_style(style);
+    add(&mut builder);
+    let m = builder.build(ctx)?;
+    m.draw(ctx, Point::default(), 0.0)
+}
+
 // Renders text with the default font.
 // Not terribly efficient as it re-renders the text with each call,
 // but good enough for debugging.
@@ -602,6 +1132,49 @@ pub fn polygon(ctx: &mut Context, mode: DrawMode, vertices: &[Point]) -> GameRes
 // }
 
 
+/// Draws a rectangle with rounded corners of the given radius.
+pub fn rounded_rectangle(
+    ctx: &mut Context,
+    mode: DrawMode,
+    rect: Rect,
+    radius: f32,
+    tolerance: f32,
+) -> GameResult<()> {
+    let m = Mesh::new_rounded_rectangle(ctx, mode, rect, radius, tolerance)?;
+    m.draw(ctx, Point::default(), 0.0)
+}
+
+/// Draws a quadratic bezier curve from `start` to `end`.
+/// Filling it fills the area between the curve and a straight
+/// line from `end` back to `start`.
+pub fn quadratic_bezier(
+    ctx: &mut Context,
+    mode: DrawMode,
+    start: Point,
+    control: Point,
+    end: Point,
+    tolerance: f32,
+) -> GameResult<()> {
+    let m = Mesh::new_quadratic_bezier(ctx, mode, start, control, end, tolerance)?;
+    m.draw(ctx, Point::default(), 0.0)
+}
+
+/// Draws a cubic bezier curve from `start` to `end`.
+/// Filling it fills the area between the curve and a straight
+/// line from `end` back to `start`.
+pub fn cubic_bezier(
+    ctx: &mut Context,
+    mode: DrawMode,
+    start: Point,
+    control1: Point,
+    control2: Point,
+    end: Point,
+    tolerance: f32,
+) -> GameResult<()> {
+    let m = Mesh::new_cubic_bezier(ctx, mode, start, control1, control2, end, tolerance)?;
+    m.draw(ctx, Point::default(), 0.0)
+}
+
 /// Draws a rectangle.
 pub fn rectangle(ctx: &mut Context, mode: DrawMode, rect: Rect) -> GameResult<()> {
     let x = rect.x;
@@ -630,6 +1203,11 @@ pub fn get_background_color(ctx: &Context) -> Color {
     ctx.gfx_context.background_color
 }
 
+/// Returns the current blend mode.
+pub fn get_blend_mode(ctx: &Context) -> BlendMode {
+    ctx.gfx_context.blend_mode
+}
+
 /// Returns the current foreground color.
 pub fn get_color(ctx: &Context) -> Color {
     ctx.gfx_context.shader_globals.color.into()
@@ -644,7 +1222,12 @@ pub fn get_default_filter(ctx: &Context) -> FilterMode {
 
 /// Get the current width for drawing lines and stroked polygons.
 pub fn get_line_width(ctx: &Context) -> f32 {
-    ctx.gfx_context.line_width
+    ctx.gfx_context.line_style.width
+}
+
+/// Get the current style for drawing lines and stroked polygons.
+pub fn get_line_style(ctx: &Context) -> &LineStyle {
+    &ctx.gfx_context.line_style
 }
 
 
@@ -653,6 +1236,11 @@ pub fn get_point_size(ctx: &Context) -> f32 {
     ctx.gfx_context.point_size
 }
 
+/// Get the current shape for drawing points.
+pub fn get_point_style(ctx: &Context) -> PointStyle {
+    ctx.gfx_context.point_style
+}
+
 /// Returns a string that tells a little about the obtained rendering mode.
 /// It is supposed to be human-readable and will change; do not try to parse
 /// information out of it!
@@ -671,6 +1259,18 @@ pub fn get_renderer_info(ctx: &Context) -> GameResult<String> {
     ))
 }
 
+/// Returns the number of pixels drawn per unit of window size, such
+/// as 2.0 on a retina display.  This is always 1.0 unless
+/// `Conf::hidpi` is set.
+///
+/// Screen coordinates are mapped onto the whole drawable, so drawing
+/// comes out the same size either way.  Text in TTF fonts, both
+/// `Text` objects and text queued with `queue_text()`, is rasterized
+/// at this scale, and again whenever it changes.
+pub fn get_dpi_scale(ctx: &Context) -> f32 {
+    ctx.gfx_context.dpi_scale
+}
+
 /// Returns a rectangle defining the coordinate system of the screen.
 /// It will be `Rect { x: center_x, y: cenyer_y, w: width, h: height }`
 ///
@@ -685,6 +1285,12 @@ pub fn set_background_color(ctx: &mut Context, color: Color) {
     ctx.gfx_context.background_color = color;
 }
 
+/// Sets the blend mode used for all subsequent drawing,
+/// including any user-defined `Shader`.  Default: `BlendMode::Alpha`.
+pub fn set_blend_mode(ctx: &mut Context, mode: BlendMode) {
+    ctx.gfx_context.blend_mode = mode;
+}
+
 /// Sets the foreground color, which will be used for drawing
 /// rectangles, lines, etc.  Default: white.
 pub fn set_color(ctx: &mut Context, color: Color) -> GameResult<()> {
@@ -708,7 +1314,13 @@ pub fn set_default_filter(ctx: &mut Context, mode: FilterMode) {
 
 /// Set the current width for drawing lines and stroked polygons.
 pub fn set_line_width(ctx: &mut Context, width: f32) {
-    ctx.gfx_context.line_width = width;
+    ctx.gfx_context.line_style.width = width;
+}
+
+/// Set the current style for drawing lines and stroked polygons,
+/// width included.
+pub fn set_line_style(ctx: &mut Context, style: LineStyle) {
+    ctx.gfx_context.line_style = style;
 }
 
 /// Set the current size for drawing points.
@@ -716,6 +1328,83 @@ pub fn set_point_size(ctx: &mut Context, size: f32) {
     ctx.gfx_context.point_size = size;
 }
 
+/// Set the current shape for drawing points.  Default: square.
+pub fn set_point_style(ctx: &mut Context, style: PointStyle) {
+    ctx.gfx_context.point_style = style;
+}
+
+/// Sets the scissor rectangle, outside of which nothing is drawn.
+/// `None` turns scissoring off.
+///
+/// The rect is given in screen coordinates, as set by
+/// `set_screen_coordinates()`, and like all ggez `Rect`s has
+/// `x` and `y` at its center.  It is not affected by the
+/// current transform.
+pub fn set_scissor(ctx: &mut Context, rect: Option<Rect>) {
+    ctx.gfx_context.scissor = rect;
+}
+
+/// Returns the current scissor rectangle, if any.
+pub fn get_scissor(ctx: &Context) -> Option<Rect> {
+    ctx.gfx_context.scissor
+}
+
+/// Draws a stencil mask.  Everything drawn by `draw_mask` is not
+/// drawn to the screen, but instead writes `value` into the stencil
+/// buffer wherever it covers, after clearing the stencil buffer.
+///
+/// Use `set_stencil_test()` to then only draw where the mask
+/// was (or wasn't) drawn.
+pub fn stencil<F>(ctx: &mut Context, value: u8, draw_mask: F) -> GameResult<()>
+where
+    F: FnOnce(&mut Context) -> GameResult<()>,
+{
+    let (old_mode, old_value) = {
+        let gfx = &mut ctx.gfx_context;
+        gfx.encoder.clear_stencil(&gfx.data.stencil.0, 0);
+        let old = (gfx.stencil_mode, gfx.data.stencil.1);
+        gfx.stencil_mode = StencilMode::Write;
+        gfx.data.stencil.1 = (value, value);
+        old
+    };
+    let result = draw_mask(ctx);
+    let gfx = &mut ctx.gfx_context;
+    gfx.stencil_mode = old_mode;
+    gfx.data.stencil.1 = old_value;
+    result
+}
+
+/// Sets the stencil test for subsequent drawing: things are only
+/// drawn where comparing `value` to what is in the stencil buffer
+/// with `compare` passes.  Note that, as in OpenGL, `value` is the
+/// left-hand side of the comparison, so `StencilCompare::Equal`
+/// with the same value passed to `stencil()` draws only inside the
+/// mask, and `StencilCompare::Less` with a value of 0 draws only
+/// where anything was written.
+///
+/// `None` turns the stencil test off.
+pub fn set_stencil_test(ctx: &mut Context, test: Option<(StencilCompare, u8)>) {
+    let gfx = &mut ctx.gfx_context;
+    match test {
+        Some((compare, value)) => {
+            gfx.stencil_mode = StencilMode::Test(compare);
+            gfx.data.stencil.1 = (value, value);
+        }
+        None => {
+            gfx.stencil_mode = StencilMode::Off;
+        }
+    }
+}
+
+/// Returns the current stencil test, if any.
+pub fn get_stencil_test(ctx: &Context) -> Option<(StencilCompare, u8)> {
+    let gfx = &ctx.gfx_context;
+    match gfx.stencil_mode {
+        StencilMode::Test(compare) => Some((compare, gfx.data.stencil.1 .0)),
+        _ => None,
+    }
+}
+
 /// Sets the bounds of the screen viewport.
 ///
 /// The default coordinate system has (0,0) at the top-left corner
@@ -728,6 +1417,9 @@ pub fn set_point_size(ctx: &mut Context, size: f32) {
 /// so if you wanted a coordinate system from (0,0) at the bottom-left
 /// to (640, 480) at the top-right, you would call this function with
 /// a `Rect{x: 320, y: 240, w: 640, h: 480}`
+///
+/// To look around a world bigger than the screen, a `Camera2D` with
+/// `set_camera()` is usually easier.
 pub fn set_screen_coordinates(
     context: &mut Context,
     rect: Rect,
@@ -737,6 +1429,93 @@ pub fn set_screen_coordinates(
     gfx.update_globals()
 }
 
+/// Pushes a copy of the current transform onto the transform stack,
+/// so it can be restored later with `pop()`.
+pub fn push(ctx: &mut Context) {
+    let gfx = &mut ctx.gfx_context;
+    let top = gfx.get_transform();
+    gfx.transform_stack.push(top);
+}
+
+/// Pops the current transform off the transform stack, restoring
+/// the one saved by the matching `push()`.
+///
+/// Returns an error if there is nothing left to pop.
+pub fn pop(ctx: &mut Context) -> GameResult<()> {
+    let gfx = &mut ctx.gfx_context;
+    if gfx.transform_stack.len() <= 1 {
+        let msg = "Tried to pop the transform stack more times than it was pushed".to_owned();
+        return Err(GameError::RenderError(msg));
+    }
+    gfx.transform_stack.pop();
+    gfx.calculate_transform_matrix();
+    gfx.update_globals()
+}
+
+/// Resets the current transform to the identity, so drawing
+/// happens directly in screen coordinates again.
+pub fn origin(ctx: &mut Context) -> GameResult<()> {
+    let gfx = &mut ctx.gfx_context;
+    gfx.set_transform(Matrix::identity());
+    gfx.update_globals()
+}
+
+/// Multiplies the given matrix into the current transform.
+///
+/// Like in Love2D, the most recently applied transform is the one
+/// applied to drawn objects first.
+pub fn apply_transform(ctx: &mut Context, transform: Matrix) -> GameResult<()> {
+    let gfx = &mut ctx.gfx_context;
+    let new_transform = gfx.get_transform() * transform;
+    gfx.set_transform(new_transform);
+    gfx.update_globals()
+}
+
+/// Translates the coordinate system by the given amounts.
+pub fn translate(ctx: &mut Context, dx: f32, dy: f32) -> GameResult<()> {
+    apply_transform(ctx, Matrix::translation(dx, dy))
+}
+
+/// Rotates the coordinate system by the given angle, in radians.
+pub fn rotate(ctx: &mut Context, angle: f32) -> GameResult<()> {
+    apply_transform(ctx, Matrix::rotation(angle))
+}
+
+/// Scales the coordinate system by the given factors.
+pub fn scale(ctx: &mut Context, sx: f32, sy: f32) -> GameResult<()> {
+    apply_transform(ctx, Matrix::scale(sx, sy))
+}
+
+/// Shears the coordinate system by the given factors.
+pub fn shear(ctx: &mut Context, kx: f32, ky: f32) -> GameResult<()> {
+    apply_transform(ctx, Matrix::shear(kx, ky))
+}
+
+/// Returns the current transform, not including the projection
+/// set by `set_screen_coordinates()`.
+pub fn get_transform(ctx: &Context) -> Matrix {
+    ctx.gfx_context.get_transform()
+}
+
+/// Converts a point from local coordinates, as used for drawing,
+/// into screen coordinates by applying the current transform.
+pub fn transform_point(ctx: &Context, point: Point) -> Point {
+    ctx.gfx_context.get_transform().transform_point(point)
+}
+
+/// Converts a point from screen coordinates into local coordinates
+/// by applying the inverse of the current transform.  Handy for
+/// figuring out what a mouse click is pointing at.
+///
+/// If the current transform cannot be inverted (for instance, it
+/// scales by zero) the point is returned unchanged.
+pub fn inverse_transform_point(ctx: &Context, point: Point) -> Point {
+    match ctx.gfx_context.get_transform().inverse() {
+        Some(inverse) => inverse.transform_point(point),
+        None => point,
+    }
+}
+
 /// Sets the window mode, such as the size and other properties.
 ///
 /// Setting the window mode may have side effects, such as clearing
@@ -807,6 +1586,8 @@ pub fn get_display_count(context: &Context) -> GameResult<i32> {
 /// * `scale` - x/y scale factors expressed as a `Point`.
 /// * `offset` - specifies an offset from the center for transform operations like scale/rotation.
 /// * `shear` - x/y shear factors expressed as a `Point`.
+/// * `color` - a color to tint the drawable with, multiplied with the
+///    color set by `set_color()`.  If `None`, the drawable is not tinted.
 ///
 /// This struct implements the `Default` trait, so you can just do:
 ///
@@ -819,6 +1600,7 @@ pub struct DrawParam {
     pub scale: Point,
     pub offset: Point,
     pub shear: Point,
+    pub color: Option<Color>,
 }
 
 impl Default for DrawParam {
@@ -830,6 +1612,7 @@ impl Default for DrawParam {
             scale: Point::new(1.0, 1.0),
             offset: Point::new(0.0, 0.0),
             shear: Point::new(0.0, 0.0),
+            color: None,
         }
     }
 }
@@ -870,9 +1653,13 @@ where
     R: gfx::Resources,
 {
     texture: gfx::handle::ShaderResourceView<R, [f32; 4]>,
+    raw_texture: gfx::handle::RawTexture<R>,
     sampler_info: gfx::texture::SamplerInfo,
     width: u32,
     height: u32,
+    /// How much of the texture the image covers, which is less than
+    /// all of it if it was padded out to a power of two.
+    uv_scale: (f32, f32),
 }
 
 /// In-GPU-memory image data available to be drawn on the screen,
@@ -937,6 +1724,7 @@ impl Image {
             width,
             height,
             rgba,
+            context.gfx_context.power_of_two_textures,
         )
     }
     /// A helper function that just takes a factory directly so we can make an image
@@ -948,40 +1736,47 @@ impl Image {
         width: u16,
         height: u16,
         rgba: &[u8],
+        pad_to_power_of_two: bool,
     ) -> GameResult<Image> {
-        // Check if the texture is not power of 2, and if not, pad it out.
-        let view = if false {
-            // let view = if !(width.is_power_of_two() && height.is_power_of_two()) {
-            let (width, height, rgba) = scale_rgba_up_to_power_of_2(width, height, rgba);
+        if width == 0 || height == 0 {
+            let msg = format!(
+                "Tried to create a texture of size {}x{}, each dimension must \
+                 be >0",
+                width,
+                height
+            );
+            return Err(GameError::ResourceLoadError(msg));
+        }
+        // GL 3.2 handles textures of any size, so only pad them
+        // out to a power of 2 if asked to.
+        let is_power_of_two = width.is_power_of_two() && height.is_power_of_two();
+        let (raw_texture, view, uv_scale) = if pad_to_power_of_two && !is_power_of_two {
+            let (tex_width, tex_height, rgba) = scale_rgba_up_to_power_of_2(width, height, rgba);
             let rgba = &rgba;
-            assert_eq!((width as usize) * (height as usize) * 4, rgba.len());
-            let kind = gfx::texture::Kind::D2(width, height, gfx::texture::AaMode::Single);
+            assert_eq!((tex_width as usize) * (tex_height as usize) * 4, rgba.len());
+            let kind = gfx::texture::Kind::D2(tex_width, tex_height, gfx::texture::AaMode::Single);
             // The slice containing rgba is NOT rows x columns, it is a slice of
             // MIPMAP LEVELS.  Augh!
-            let (_, view) = factory
+            let (texture, view) = factory
                 .create_texture_immutable_u8::<gfx::format::Srgba8>(kind, &[rgba])?;
-            view
+            let uv_scale = (
+                width as f32 / tex_width as f32,
+                height as f32 / tex_height as f32,
+            );
+            (texture.raw().clone(), view, uv_scale)
         } else {
-            if width == 0 || height == 0 {
-                let msg = format!(
-                    "Tried to create a texture of size {}x{}, each dimension must \
-                     be >0",
-                    width,
-                    height
-                );
-                return Err(GameError::ResourceLoadError(msg));
-            }
             let kind = gfx::texture::Kind::D2(width, height, gfx::texture::AaMode::Single);
-            let (_, view) = factory
+            let (texture, view) = factory
                 .create_texture_immutable_u8::<gfx::format::Srgba8>(kind, &[rgba])?;
-            view
-
+            (texture.raw().clone(), view, (1.0, 1.0))
         };
         Ok(Image {
             texture: view,
+            raw_texture: raw_texture,
             sampler_info: *sampler_info,
             width: width as u32,
             height: height as u32,
+            uv_scale: uv_scale,
         })
     }
 
@@ -1051,9 +1846,10 @@ impl fmt::Debug for Image {
 }
 
 
-impl Drawable for Image {
-    fn draw_ex(&self, ctx: &mut Context, param: DrawParam) -> GameResult<()> {
-        let gfx = &mut ctx.gfx_context;
+impl Image {
+    /// Adjusts the given `DrawParam` so that drawing the unit quad
+    /// with it comes out at the image's actual size in pixels.
+    fn quad_draw_param(&self, param: DrawParam, screen_rect: Rect) -> DrawParam {
         let src_width = param.src.w;
         let src_height = param.src.h;
         // We have to mess with the scale to make everything
@@ -1062,42 +1858,80 @@ impl Drawable for Image {
         // are "upside down", because by default we present the
         // illusion that the screen is addressed in pixels.
         // BUGGO: Which I rather regret now.
-        let invert_y = if gfx.screen_rect.h < 0.0 { 1.0 } else { -1.0 };
+        let invert_y = if screen_rect.h < 0.0 { 1.0 } else { -1.0 };
         let real_scale = Point {
             x: src_width * param.scale.x * self.width as f32,
             y: src_height * param.scale.y * self.height as f32 * invert_y,
         };
         let mut new_param = param;
         new_param.scale = real_scale;
+        // `src` is a fraction of the image, which may not be
+        // the whole texture.
+        let (u_scale, v_scale) = self.uv_scale;
+        new_param.src = Rect {
+            x: param.src.x * u_scale,
+            y: param.src.y * v_scale,
+            w: param.src.w * u_scale,
+            h: param.src.h * v_scale,
+        };
         // Not entirely sure why the inversion is necessary, but oh well.
         new_param.offset.x *= -1.0 * param.scale.x;
         new_param.offset.y *= param.scale.y;
-        gfx.update_rect_properties(new_param)?;
+        new_param
+    }
+
+    /// Binds the unit quad and the image's texture for drawing,
+    /// returning the slice to draw the quad with.
+    fn bind(&self, gfx: &mut GraphicsContext) -> gfx::Slice<gfx_device_gl::Resources> {
         let sampler = gfx.samplers
             .get_or_insert(self.sampler_info, gfx.factory.as_mut());
         gfx.data.vbuf = gfx.quad_vertex_buffer.clone();
         gfx.data.tex = (self.texture.clone(), sampler);
-        gfx.encoder.draw(&gfx.quad_slice, &gfx.pso, &gfx.data);
-        Ok(())
+        gfx.quad_slice.clone()
     }
 }
 
-/// 2D polygon mesh
-#[derive(Debug, Clone, PartialEq)]
+impl Drawable for Image {
+    fn draw_ex(&self, ctx: &mut Context, param: DrawParam) -> GameResult<()> {
+        let gfx = &mut ctx.gfx_context;
+        let new_param = self.quad_draw_param(param, gfx.screen_rect);
+        gfx.update_rect_properties(new_param)?;
+        let quad_slice = self.bind(gfx);
+        gfx.draw(&quad_slice)
+    }
+}
+
+/// A 2D polygon mesh, made of triangles.
+///
+/// Each vertex has its own color, which is multiplied with the color
+/// the mesh is drawn with, so a single mesh can hold shapes of many
+/// colors; see `MeshBuilder`.  A mesh may also have an `Image`, which
+/// is mapped onto it by the vertices' texture coordinates.  Meshes
+/// without one are drawn in solid color.
+#[derive(Debug, Clone)]
 pub struct Mesh {
     buffer: gfx::handle::Buffer<gfx_device_gl::Resources, Vertex>,
     slice: gfx::Slice<gfx_device_gl::Resources>,
+    vertex_count: usize,
+    image: Option<Image>,
 }
 
+use lyon;
+use lyon::bezier;
+use lyon::path_builder::{BaseBuilder, FlatteningBuilder, PathBuilder};
 use lyon::tessellation as t;
 
-struct VertexBuilder;
+/// Turns lyon's vertices into ours, all of one color.
+struct VertexBuilder {
+    color: [f32; 4],
+}
 
 impl t::VertexConstructor<t::FillVertex, Vertex> for VertexBuilder {
     fn new_vertex(&mut self, vertex: t::FillVertex) -> Vertex {
         Vertex {
             pos: [vertex.position.x, vertex.position.y],
             uv: [0.0, 0.0],
+            color: self.color,
         }
     }
 }
@@ -1107,34 +1941,356 @@ impl t::VertexConstructor<t::StrokeVertex, Vertex> for VertexBuilder {
         Vertex {
             pos: [vertex.position.x, vertex.position.y],
             uv: [0.0, 0.0],
+            color: self.color,
         }
     }
 }
 
-impl Mesh {
-    fn from_vbuf(
-        ctx: &mut Context,
-        buffer: &t::geometry_builder::VertexBuffers<Vertex>,
-    ) -> GameResult<Mesh> {
-        let (vbuf, slice) = ctx.gfx_context
-            .factory
-            .create_vertex_buffer_with_slice(&buffer.vertices[..], &buffer.indices[..]);
+/// Converts a point to lyon's kind.
+fn to_lyon(p: Point) -> t::math::Point {
+    t::math::point(p.x, p.y)
+}
 
-        Ok(Mesh {
-            buffer: vbuf,
-            slice: slice,
+/// lyon's path builder, set up to flatten curves as they are added.
+type FlatteningPathBuilder = FlatteningBuilder<lyon::path::Builder>;
+
+/// Creates one of lyon's path builders that flattens curves
+/// to within `tolerance` as they are added.
+fn flattening_builder(tolerance: f32) -> FlatteningPathBuilder {
+    // lyon never finishes flattening a curve with no tolerance at all.
+    lyon::path::Path::builder().flattened(tolerance.max(0.001))
+}
+
+/// Returns the points of the line that the curves `add` adds to
+/// lyon's path builder, starting from `start`, are flattened into,
+/// never more than `tolerance` away from them.  Both ends are included.
+fn flatten_curve<F>(start: Point, tolerance: f32, add: F) -> Vec<Point>
+where
+    F: FnOnce(&mut FlatteningPathBuilder),
+{
+    let mut builder = flattening_builder(tolerance);
+    builder.move_to(to_lyon(start));
+    add(&mut builder);
+    builder
+        .build()
+        .points()
+        .iter()
+        .map(|p| Point::new(p.x, p.y))
+        .collect()
+}
+
+/// Adds an arc of a circle going from `angle1` to `angle2` to lyon's
+/// path builder, which must be at the start of it already.  Arcs
+/// going round more than once are drawn as a full circle.
+fn add_arc<B: PathBuilder>(builder: &mut B, center: Point, radius: f32, angle1: f32, angle2: f32) {
+    use std::f32::consts::PI;
+    let sweep = (angle2 - angle1).max(-2.0 * PI).min(2.0 * PI);
+    if radius == 0.0 || sweep == 0.0 {
+        let end = Point::new(
+            center.x + radius * angle2.cos(),
+            center.y + radius * angle2.sin(),
+        );
+        builder.line_to(to_lyon(end));
+        return;
+    }
+    // lyon's arcs can't go all the way round, so
+    // they're added in pieces of half a turn at most.
+    let pieces = ((sweep.abs() / PI).ceil() as usize).max(1);
+    let step = sweep / pieces as f32;
+    for i in 0..pieces {
+        let arc = bezier::Arc {
+            center: to_lyon(center),
+            radii: t::math::vec2(radius, radius),
+            start_angle: euclid::Radians::new(angle1 + step * i as f32),
+            sweep_angle: euclid::Radians::new(step),
+            x_rotation: euclid::Radians::new(0.0),
+        };
+        arc.to_quadratic_beziers(&mut |control, to| builder.quadratic_bezier_to(control, to));
+    }
+}
+
+/// Returns the points along an arc, close enough together that
+/// the straight lines between them are never more than `tolerance`
+/// away from the curves lyon approximates it with.  Both ends are
+/// included.
+fn flatten_arc(center: Point, radius: f32, angle1: f32, angle2: f32, tolerance: f32) -> Vec<Point> {
+    let start = Point::new(
+        center.x + radius * angle1.cos(),
+        center.y + radius * angle1.sin(),
+    );
+    flatten_curve(start, tolerance, |builder| {
+        add_arc(builder, center, radius, angle1, angle2)
+    })
+}
+
+/// Returns points along a quadratic bezier curve, within `tolerance`
+/// of the real curve.  Both ends are included.
+fn flatten_quadratic_bezier(start: Point, control: Point, end: Point, tolerance: f32) -> Vec<Point> {
+    flatten_curve(start, tolerance, |builder| {
+        builder.quadratic_bezier_to(to_lyon(control), to_lyon(end))
+    })
+}
+
+/// Returns points along a cubic bezier curve, within `tolerance`
+/// of the real curve.  Both ends are included.
+fn flatten_cubic_bezier(
+    start: Point,
+    control1: Point,
+    control2: Point,
+    end: Point,
+    tolerance: f32,
+) -> Vec<Point> {
+    flatten_curve(start, tolerance, |builder| {
+        builder.cubic_bezier_to(to_lyon(control1), to_lyon(control2), to_lyon(end))
+    })
+}
+
+/// Returns the points around an ellipse, close enough together that
+/// the straight lines between them are never more than `tolerance`
+/// away from it.  The first point isn't repeated at the end.
+fn flatten_ellipse(center: Point, radius1: f32, radius2: f32, tolerance: f32) -> Vec<Point> {
+    use std::f32::consts::PI;
+    let radius = radius1.abs().max(radius2.abs());
+    let mut points = flatten_arc(Point::new(0.0, 0.0), radius, 0.0, 2.0 * PI, tolerance);
+    points.pop();
+    let (scale_x, scale_y) = if radius > 0.0 {
+        (radius1 / radius, radius2 / radius)
+    } else {
+        (0.0, 0.0)
+    };
+    points
+        .iter()
+        .map(|p| Point::new(center.x + p.x * scale_x, center.y + p.y * scale_y))
+        .collect()
+}
+
+/// A piece of a dashed line.
+#[derive(Debug, Clone, PartialEq)]
+enum Dash {
+    /// A dash along the given points.
+    Line(Vec<Point>),
+    /// A dash of no length at a point, on a part of
+    /// the line going in the given direction.
+    Dot(Point, Point),
+}
+
+/// Cuts a line into dashes, following a pattern of dash and gap
+/// lengths that starts `offset` along it.  Dashes of no length are
+/// left out, unless `keep_dots` is set.  Returns `None` if the
+/// pattern has no length, or any negative lengths.
+fn dash_polyline(
+    points: &[Point],
+    closed: bool,
+    pattern: &[f32],
+    offset: f32,
+    keep_dots: bool,
+) -> Option<Vec<Dash>> {
+    let mut pattern = pattern.to_vec();
+    if pattern.len() % 2 == 1 {
+        let repeat = pattern.clone();
+        pattern.extend(repeat);
+    }
+    let total: f32 = pattern.iter().sum();
+    if points.is_empty() || total <= 0.0 || pattern.iter().any(|&length| length < 0.0) {
+        return None;
+    }
+    let mut vertices = points.to_vec();
+    if closed {
+        vertices.push(points[0]);
+    }
+
+    // Find where in the pattern the line starts.
+    let mut index = 0;
+    let mut remaining = pattern[0];
+    let mut skip = offset % total;
+    if skip < 0.0 {
+        skip += total;
+    }
+    while skip > remaining {
+        skip -= remaining;
+        index = (index + 1) % pattern.len();
+        remaining = pattern[index];
+    }
+    remaining -= skip;
+
+    // Even indices are dashes, odd ones gaps.
+    let mut dashes = Vec::new();
+    let mut dash = if index % 2 == 0 {
+        vec![vertices[0]]
+    } else {
+        Vec::new()
+    };
+    let (mut a, mut b) = (vertices[0], vertices[0]);
+    for pair in vertices.windows(2) {
+        a = pair[0];
+        b = pair[1];
+        let length = ((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt();
+        let mut travelled = 0.0;
+        while length - travelled > remaining {
+            travelled += remaining;
+            let t = travelled / length;
+            dash.push(Point::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t));
+            if index % 2 == 0 {
+                let finished = mem::replace(&mut dash, Vec::new());
+                push_dash(&mut dashes, finished, a, b, keep_dots);
+            }
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+        }
+        remaining -= length - travelled;
+        if index % 2 == 0 {
+            dash.push(b);
+        }
+    }
+    push_dash(&mut dashes, dash, a, b, keep_dots);
+    Some(dashes)
+}
+
+/// Adds a finished dash along `points` to `dashes`.  Dashes of no
+/// length have no direction of their own, so if `keep_dots` is set
+/// they're added as dots going the way the line from `a` to `b` does.
+fn push_dash(dashes: &mut Vec<Dash>, points: Vec<Point>, a: Point, b: Point, keep_dots: bool) {
+    if points.windows(2).any(|pair| pair[0] != pair[1]) {
+        dashes.push(Dash::Line(points));
+    } else if keep_dots && !points.is_empty() {
+        dashes.push(Dash::Dot(points[0], Point::new(b.x - a.x, b.y - a.y)));
+    }
+}
+
+/// Meshes are equal if they draw the same vertex buffer with the
+/// same image, as a mesh and its clones do.
+impl PartialEq for Mesh {
+    fn eq(&self, other: &Mesh) -> bool {
+        let same_image = match (&self.image, &other.image) {
+            (&Some(ref a), &Some(ref b)) => a.texture == b.texture && a.sampler_info == b.sampler_info,
+            (&None, &None) => true,
+            _ => false,
+        };
+        self.buffer == other.buffer && self.slice == other.slice && same_image
+    }
+}
+
+impl Mesh {
+    /// Create a new mesh from vertices and the indices of the
+    /// triangles they make up, three per triangle.  If there are no
+    /// indices, every three vertices make a triangle.
+    ///
+    /// The mesh is drawn with `image` mapped onto it by the vertices'
+    /// `uv` coordinates, or in solid color if it's `None`.
+    pub fn from_raw(
+        ctx: &mut Context,
+        vertices: &[Vertex],
+        indices: &[u32],
+        image: Option<Image>,
+    ) -> GameResult<Mesh> {
+        if let Some(&i) = indices.iter().find(|&&i| i as usize >= vertices.len()) {
+            let msg = format!("Mesh index {} is out of range for {} vertices", i, vertices.len());
+            return Err(GameError::RenderError(msg));
+        }
+        let gfx = &mut ctx.gfx_context;
+        // Dynamic, so set_vertices() can change it later.
+        let buffer = gfx.factory.create_buffer(
+            cmp::max(vertices.len(), 1),
+            gfx::buffer::Role::Vertex,
+            gfx::memory::Usage::Dynamic,
+            gfx::memory::Bind::empty(),
+        )?;
+        gfx.encoder.update_buffer(&buffer, vertices, 0)?;
+        let (index_buffer, end) = if indices.is_empty() {
+            (gfx::IndexBuffer::Auto, vertices.len())
+        } else {
+            let index_buffer = gfx.factory.create_buffer_immutable(
+                indices,
+                gfx::buffer::Role::Index,
+                gfx::memory::Bind::empty(),
+            )?;
+            (gfx::IndexBuffer::Index32(index_buffer), indices.len())
+        };
+        let slice = gfx::Slice {
+            start: 0,
+            end: end as u32,
+            base_vertex: 0,
+            instances: Some((1, 0)),
+            buffer: index_buffer,
+        };
+
+        Ok(Mesh {
+            buffer: buffer,
+            slice: slice,
+            vertex_count: vertices.len(),
+            image: image,
         })
     }
 
+    /// Replaces the mesh's vertices, for geometry that changes
+    /// from frame to frame.  There must be as many new vertices as
+    /// the mesh already has; the triangles they make stay the same.
+    ///
+    /// Clones of a `Mesh` share its vertex buffer on the graphics
+    /// card, so they all see the new vertices too.
+    pub fn set_vertices(&mut self, ctx: &mut Context, vertices: &[Vertex]) -> GameResult<()> {
+        if vertices.len() != self.vertex_count {
+            let msg = format!(
+                "Mesh has {} vertices, can't replace them with {}",
+                self.vertex_count,
+                vertices.len()
+            );
+            return Err(GameError::RenderError(msg));
+        }
+        ctx.gfx_context
+            .encoder
+            .update_buffer(&self.buffer, vertices, 0)?;
+        Ok(())
+    }
+
+    /// Returns the number of vertices in the mesh.
+    pub fn get_vertex_count(&self) -> usize {
+        self.vertex_count
+    }
+
+    /// Returns the image drawn on the mesh, if any.
+    pub fn get_image(&self) -> Option<&Image> {
+        self.image.as_ref()
+    }
+
+    /// Adjusts `param.src` for the part of the image's texture the
+    /// image actually takes up.
+    fn texture_draw_param(&self, param: DrawParam) -> DrawParam {
+        match self.image {
+            Some(ref image) => {
+                let (u, v) = image.uv_scale;
+                let src = param.src;
+                DrawParam {
+                    src: Rect::new(src.x * u, src.y * v, src.w * u, src.h * v),
+                    ..param
+                }
+            }
+            None => param,
+        }
+    }
+
+    /// Binds the mesh's vertices and texture for drawing.
+    fn bind(&self, gfx: &mut GraphicsContext) {
+        gfx.data.vbuf = self.buffer.clone();
+        match self.image {
+            Some(ref image) => {
+                let sampler = gfx.samplers
+                    .get_or_insert(image.sampler_info, gfx.factory.as_mut());
+                gfx.data.tex = (image.texture.clone(), sampler);
+            }
+            None => gfx.data.tex.0 = gfx.white_image.texture.clone(),
+        }
+    }
 
     /// Create a new mesh for a line of one or more connected segments.
-    /// WIP, sorry
     pub fn new_line(ctx: &mut Context, points: &[Point], width: f32) -> GameResult<Mesh> {
-        Mesh::new_polyline(ctx, DrawMode::Line, points, width)
+        MeshBuilder::new()
+            .set_line_style(&ctx.gfx_context.line_style)
+            .line(points, width, WHITE)
+            .build(ctx)
     }
 
     /// Create a new mesh for a circle.
-    /// Stroked circles are still WIP, sorry.
     pub fn new_circle(
         ctx: &mut Context,
         mode: DrawMode,
@@ -1142,41 +2298,13 @@ impl Mesh {
         radius: f32,
         tolerance: f32,
     ) -> GameResult<Mesh> {
-        {
-            let buffers: &mut t::geometry_builder::VertexBuffers<_> = &mut t::VertexBuffers::new();
-            match mode {
-                DrawMode::Fill => {
-                    // These builders have to be in separate match arms 'cause they're actually
-                    // different types; one is GeometryBuilder<StrokeVertex> and the other is
-                    // GeometryBuilder<FillVertex>
-                    let builder = &mut t::BuffersBuilder::new(buffers, VertexBuilder);
-                    t::basic_shapes::fill_circle(
-                        t::math::point(point.x, point.y),
-                        radius,
-                        tolerance,
-                        builder,
-                    );
-                }
-                DrawMode::Line => {
-                    let builder = &mut t::BuffersBuilder::new(buffers, VertexBuilder);
-                    let options = t::StrokeOptions::default()
-                        .with_line_width(ctx.gfx_context.line_width)
-                        .with_tolerance(tolerance);
-                    t::basic_shapes::stroke_circle(
-                        t::math::point(point.x, point.y),
-                        radius,
-                        &options,
-                        builder,
-                    );
-                }
-            };
-            Mesh::from_vbuf(ctx, buffers)
-        }
-
+        MeshBuilder::new()
+            .set_line_style(&ctx.gfx_context.line_style)
+            .circle(mode, point, radius, tolerance, WHITE)
+            .build(ctx)
     }
 
     /// Create a new mesh for an ellipse.
-    /// Stroked ellipses are still WIP, sorry.
     pub fn new_ellipse(
         ctx: &mut Context,
         mode: DrawMode,
@@ -1185,37 +2313,10 @@ impl Mesh {
         radius2: f32,
         tolerance: f32,
     ) -> GameResult<Mesh> {
-            use euclid::Length;
-            let buffers: &mut t::geometry_builder::VertexBuffers<_> = &mut t::VertexBuffers::new();
-            match mode {
-                DrawMode::Fill => {
-                    // These builders have to be in separate match arms 'cause they're actually
-                    // different types; one is GeometryBuilder<StrokeVertex> and the other is
-                    // GeometryBuilder<FillVertex>
-                    let builder = &mut t::BuffersBuilder::new(buffers, VertexBuilder);
-                    t::basic_shapes::fill_ellipse(
-                        t::math::point(point.x, point.y),
-                        t::math::vec2(radius1, radius2),
-                        Length::new(0.0),
-                        tolerance,
-                        builder,
-                    );
-                }
-                DrawMode::Line => {
-                    let builder = &mut t::BuffersBuilder::new(buffers, VertexBuilder);
-                    let options = t::StrokeOptions::default()
-                        .with_line_width(ctx.gfx_context.line_width)
-                        .with_tolerance(tolerance);
-                    t::basic_shapes::stroke_ellipse(
-                        t::math::point(point.x, point.y),
-                        t::math::vec2(radius1, radius2),
-                        Length::new(0.0),
-                        &options,
-                        builder,
-                    );
-                }
-            };
-            Mesh::from_vbuf(ctx, buffers)
+        MeshBuilder::new()
+            .set_line_style(&ctx.gfx_context.line_style)
+            .ellipse(mode, point, radius1, radius2, tolerance, WHITE)
+            .build(ctx)
     }
 
     /// Create a new mesh for series of connected lines
@@ -1223,126 +2324,797 @@ impl Mesh {
         ctx: &mut Context,
         mode: DrawMode,
         points: &[Point],
-        width: f32)
-        -> GameResult<Mesh> {
-            let buffers: &mut t::geometry_builder::VertexBuffers<_> = &mut t::VertexBuffers::new();
-            let points = points
-                .into_iter()
-                .map(|ggezpoint| t::math::point(ggezpoint.x, ggezpoint.y));
-            match mode {
-                DrawMode::Fill => {
-                    // These builders have to be in separate match arms 'cause they're actually
-                    // different types; one is GeometryBuilder<StrokeVertex> and the other is
-                    // GeometryBuilder<FillVertex>
-                    let builder = &mut t::BuffersBuilder::new(buffers, VertexBuilder);
-                    let tessellator = &mut t::FillTessellator::new();
-                    let options = t::FillOptions::default();
-                    t::basic_shapes::fill_polyline(
-                        points,
-                        tessellator,
-                        &options,
-                        builder,
-                    ).unwrap();
-                }
-                DrawMode::Line => {
-                    let builder = &mut t::BuffersBuilder::new(buffers, VertexBuilder);
-                    let options = t::StrokeOptions::default()
-                        .with_line_width(width);
-                    t::basic_shapes::stroke_polyline(
-                        points,
-                        false,
-                        &options,
-                        builder,
-                    );
-                }
-            };
-            Mesh::from_vbuf(ctx, buffers)
+        width: f32,
+    ) -> GameResult<Mesh> {
+        MeshBuilder::new()
+            .set_line_style(&ctx.gfx_context.line_style)
+            .set_line_width(width)
+            .polyline(mode, points, WHITE)
+            .build(ctx)
     }
 
-
     /// Create a new mesh for closed polygon
     pub fn new_polygon(
         ctx: &mut Context,
         mode: DrawMode,
         points: &[Point],
-        width: f32)
-        -> GameResult<Mesh> {
-            let buffers: &mut t::geometry_builder::VertexBuffers<_> = &mut t::VertexBuffers::new();
-            let points = points
-                .into_iter()
-                .map(|ggezpoint| t::math::point(ggezpoint.x, ggezpoint.y));
-            match mode {
-                DrawMode::Fill => {
-                    // These builders have to be in separate match arms 'cause they're actually
-                    // different types; one is GeometryBuilder<StrokeVertex> and the other is
-                    // GeometryBuilder<FillVertex>
-                    let builder = &mut t::BuffersBuilder::new(buffers, VertexBuilder);
-                    let tessellator = &mut t::FillTessellator::new();
-                    let options = t::FillOptions::default();
-                    t::basic_shapes::fill_polyline(
-                        points,
-                        tessellator,
-                        &options,
-                        builder,
-                    ).unwrap();
-                }
-                DrawMode::Line => {
-                    let builder = &mut t::BuffersBuilder::new(buffers, VertexBuilder);
-                    let options = t::StrokeOptions::default()
-                        .with_line_width(width);
-                    t::basic_shapes::stroke_polyline(
-                        points,
-                        true,
-                        &options,
-                        builder,
-                    );
-                }
-            };
-            Mesh::from_vbuf(ctx, buffers)
+        width: f32,
+    ) -> GameResult<Mesh> {
+        MeshBuilder::new()
+            .set_line_style(&ctx.gfx_context.line_style)
+            .set_line_width(width)
+            .polygon(mode, points, WHITE)
+            .build(ctx)
     }
 
-    /// Create a new `Mesh` from a raw list of triangles.
-    ///
-    /// Currently does not support UV's or indices.
-    pub fn from_triangles(ctx: &mut Context, triangles: &[Point]) -> GameResult<Mesh> {
-        // This is kind of non-ideal but works for now.
-        let points: Vec<Vertex> = triangles
-            .into_iter()
-            .map(|p| {
-                Vertex {
-                    pos: (*p).into(),
-                    uv: (*p).into(),
-                }
-            })
-            .collect();
-        let (vbuf, slice) = ctx.gfx_context
-            .factory
-            .create_vertex_buffer_with_slice(&points[..], ());
+    /// Create a new mesh for an arc of a circle; see `graphics::arc()`.
+    #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
+    pub fn new_arc(
+        ctx: &mut Context,
+        mode: DrawMode,
+        arc_type: ArcType,
+        point: Point,
+        radius: f32,
+        angle1: f32,
+        angle2: f32,
+        tolerance: f32,
+    ) -> GameResult<Mesh> {
+        let mut points = flatten_arc(point, radius, angle1, angle2, tolerance);
+        let width = ctx.gfx_context.line_style.width;
+        match (mode, arc_type) {
+            (_, ArcType::Pie) => {
+                points.push(point);
+                Mesh::new_polygon(ctx, mode, &points, width)
+            }
+            (DrawMode::Line, ArcType::Open) => Mesh::new_polyline(ctx, mode, &points, width),
+            _ => Mesh::new_polygon(ctx, mode, &points, width),
+        }
+    }
 
-        Ok(Mesh {
-            buffer: vbuf,
-            slice: slice,
-        })
+    /// Create a new mesh for a rectangle with rounded corners.
+    /// The radius is clamped so the corners never overlap.
+    pub fn new_rounded_rectangle(
+        ctx: &mut Context,
+        mode: DrawMode,
+        rect: Rect,
+        radius: f32,
+        tolerance: f32,
+    ) -> GameResult<Mesh> {
+        use std::f32::consts::PI;
+        let half_w = rect.w.abs() / 2.0;
+        let half_h = rect.h.abs() / 2.0;
+        let r = radius.max(0.0).min(half_w).min(half_h);
+        let (left, right) = (rect.x - half_w + r, rect.x + half_w - r);
+        let (top, bottom) = (rect.y - half_h + r, rect.y + half_h - r);
+        // Corner centers, going clockwise from the top left, with the
+        // angle each corner's arc starts at.
+        let corners = [
+            (Point::new(left, top), PI),
+            (Point::new(right, top), PI * 1.5),
+            (Point::new(right, bottom), 0.0),
+            (Point::new(left, bottom), PI * 0.5),
+        ];
+        let mut points = Vec::new();
+        for &(center, start) in &corners {
+            if r > 0.0 {
+                points.extend(flatten_arc(center, r, start, start + PI / 2.0, tolerance));
+            } else {
+                points.push(center);
+            }
+        }
+        let width = ctx.gfx_context.line_style.width;
+        Mesh::new_polygon(ctx, mode, &points, width)
+    }
+
+    /// Create a new mesh for a quadratic bezier curve;
+    /// see `graphics::quadratic_bezier()`.
+    pub fn new_quadratic_bezier(
+        ctx: &mut Context,
+        mode: DrawMode,
+        start: Point,
+        control: Point,
+        end: Point,
+        tolerance: f32,
+    ) -> GameResult<Mesh> {
+        let points = flatten_quadratic_bezier(start, control, end, tolerance);
+        let width = ctx.gfx_context.line_style.width;
+        Mesh::new_polyline(ctx, mode, &points, width)
+    }
+
+    /// Create a new mesh for a cubic bezier curve;
+    /// see `graphics::cubic_bezier()`.
+    pub fn new_cubic_bezier(
+        ctx: &mut Context,
+        mode: DrawMode,
+        start: Point,
+        control1: Point,
+        control2: Point,
+        end: Point,
+        tolerance: f32,
+    ) -> GameResult<Mesh> {
+        let points = flatten_cubic_bezier(start, control1, control2, end, tolerance);
+        let width = ctx.gfx_context.line_style.width;
+        Mesh::new_polyline(ctx, mode, &points, width)
+    }
+
+    /// Create a new `Mesh` from a raw list of triangles,
+    /// three points each.
+    pub fn from_triangles(ctx: &mut Context, triangles: &[Point]) -> GameResult<Mesh> {
+        MeshBuilder::new().triangles(triangles, WHITE).build(ctx)
     }
 }
 
 impl Drawable for Mesh {
     fn draw_ex(&self, ctx: &mut Context, param: DrawParam) -> GameResult<()> {
         let gfx = &mut ctx.gfx_context;
-        gfx.update_rect_properties(param)?;
+        gfx.update_rect_properties(self.texture_draw_param(param))?;
+        self.bind(gfx);
+        gfx.draw(&self.slice)
+    }
+}
 
-        gfx.data.vbuf = self.buffer.clone();
-        gfx.data.tex.0 = gfx.white_image.texture.clone();
+/// Puts several shapes, each in its own color, together into
+/// one `Mesh`, so they can all be drawn at once.
+///
+/// Outlines are drawn in the builder's `LineStyle`; see
+/// `set_line_style()` and `set_line_width()`.
+/// Shapes that can't be tessellated are reported by `build()`.
+///
+/// ```rust,ignore
+/// let mesh = MeshBuilder::new()
+///     .rectangle(DrawMode::Fill, Rect::new(100.0, 100.0, 80.0, 40.0), Color::new(1.0, 0.0, 0.0, 1.0))
+///     .set_line_width(3.0)
+///     .circle(DrawMode::Line, Point::new(100.0, 100.0), 50.0, 0.5, WHITE)
+///     .build(ctx)?;
+/// graphics::draw(ctx, &mesh, Point::new(0.0, 0.0), 0.0)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct MeshBuilder {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    line_style: LineStyle,
+    error: Option<String>,
+}
 
-        gfx.encoder.draw(&self.slice, &gfx.pso, &gfx.data);
+impl Default for MeshBuilder {
+    fn default() -> Self {
+        MeshBuilder {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            line_style: LineStyle::default(),
+            error: None,
+        }
+    }
+}
 
-        Ok(())
+impl MeshBuilder {
+    /// Creates a new, empty `MeshBuilder`.
+    pub fn new() -> Self {
+        MeshBuilder::default()
+    }
+
+    /// Sets how wide outlines added after this are.  Default: 1.0.
+    pub fn set_line_width(&mut self, width: f32) -> &mut Self {
+        self.line_style.width = width;
+        self
+    }
+
+    /// Sets how outlines added after this look, width included.
+    pub fn set_line_style(&mut self, style: &LineStyle) -> &mut Self {
+        self.line_style = style.clone();
+        self
+    }
+
+    /// Adds a line of one or more connected segments,
+    /// `width` wide but otherwise in the builder's line style.
+    pub fn line(&mut self, points: &[Point], width: f32, color: Color) -> &mut Self {
+        let style = LineStyle {
+            width: width,
+            ..self.line_style.clone()
+        };
+        self.stroke_polyline(points, false, &style, color)
+    }
+
+    /// Adds a series of connected lines, or the shape they enclose
+    /// if filled.
+    pub fn polyline(&mut self, mode: DrawMode, points: &[Point], color: Color) -> &mut Self {
+        match mode {
+            DrawMode::Fill => self.fill_polyline(points, color),
+            DrawMode::Line => {
+                let style = self.line_style.clone();
+                self.stroke_polyline(points, false, &style, color)
+            }
+        }
+    }
+
+    /// Adds a closed polygon.
+    pub fn polygon(&mut self, mode: DrawMode, points: &[Point], color: Color) -> &mut Self {
+        match mode {
+            DrawMode::Fill => self.fill_polyline(points, color),
+            DrawMode::Line => {
+                let style = self.line_style.clone();
+                self.stroke_polyline(points, true, &style, color)
+            }
+        }
+    }
+
+    /// Adds a rectangle.
+    pub fn rectangle(&mut self, mode: DrawMode, rect: Rect, color: Color) -> &mut Self {
+        let (x1, x2) = (rect.x - rect.w / 2.0, rect.x + rect.w / 2.0);
+        let (y1, y2) = (rect.y - rect.h / 2.0, rect.y + rect.h / 2.0);
+        let points = [
+            Point::new(x1, y1),
+            Point::new(x2, y1),
+            Point::new(x2, y2),
+            Point::new(x1, y2),
+        ];
+        self.polygon(mode, &points, color)
+    }
+
+    /// Adds a circle.
+    pub fn circle(
+        &mut self,
+        mode: DrawMode,
+        point: Point,
+        radius: f32,
+        tolerance: f32,
+        color: Color,
+    ) -> &mut Self {
+        self.ellipse(mode, point, radius, radius, tolerance, color)
+    }
+
+    /// Adds an ellipse.
+    pub fn ellipse(
+        &mut self,
+        mode: DrawMode,
+        point: Point,
+        radius1: f32,
+        radius2: f32,
+        tolerance: f32,
+        color: Color,
+    ) -> &mut Self {
+        use euclid::Length;
+        match mode {
+            DrawMode::Fill => self.tessellate(color, |buffers, vertex_builder| {
+                let builder = &mut t::BuffersBuilder::new(buffers, vertex_builder);
+                t::basic_shapes::fill_ellipse(
+                    t::math::point(point.x, point.y),
+                    t::math::vec2(radius1, radius2),
+                    Length::new(0.0),
+                    tolerance,
+                    builder,
+                );
+                Ok(())
+            }),
+            DrawMode::Line => {
+                // Outlined as a polygon, so it can be dashed.
+                let points = flatten_ellipse(point, radius1, radius2, tolerance);
+                let style = self.line_style.clone();
+                self.stroke_polyline(&points, true, &style, color)
+            }
+        }
+    }
+
+    /// Adds triangles from a list of points, three per triangle.
+    pub fn triangles(&mut self, triangles: &[Point], color: Color) -> &mut Self {
+        if triangles.len() % 3 != 0 {
+            let msg = format!(
+                "Can't make triangles out of {} points; need a multiple of 3",
+                triangles.len()
+            );
+            return self.fail(msg);
+        }
+        let color = color.into();
+        let vertices: Vec<Vertex> = triangles
+            .iter()
+            .map(|p| Vertex {
+                pos: [p.x, p.y],
+                uv: [0.0, 0.0],
+                color: color,
+            })
+            .collect();
+        let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+        self.raw(&vertices, &indices)
+    }
+
+    /// Adds vertices as they are, with indices of the triangles they
+    /// make up counted from the first of them.
+    pub fn raw(&mut self, vertices: &[Vertex], indices: &[u32]) -> &mut Self {
+        if let Some(&i) = indices.iter().find(|&&i| i as usize >= vertices.len()) {
+            let msg = format!("Mesh index {} is out of range for {} vertices", i, vertices.len());
+            return self.fail(msg);
+        }
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(vertices);
+        self.indices.extend(indices.iter().map(|i| base + i));
+        self
+    }
+
+    /// Creates a `Mesh` out of everything added so far.
+    pub fn build(&self, ctx: &mut Context) -> GameResult<Mesh> {
+        if let Some(ref msg) = self.error {
+            return Err(GameError::RenderError(msg.clone()));
+        }
+        Mesh::from_raw(ctx, &self.vertices, &self.indices, None)
+    }
+
+    fn fill_polyline(&mut self, points: &[Point], color: Color) -> &mut Self {
+        self.tessellate(color, |buffers, vertex_builder| {
+            let points = points.iter().map(|p| t::math::point(p.x, p.y));
+            let builder = &mut t::BuffersBuilder::new(buffers, vertex_builder);
+            let tessellator = &mut t::FillTessellator::new();
+            let options = t::FillOptions::default();
+            t::basic_shapes::fill_polyline(points, tessellator, &options, builder)
+                .map(|_| ())
+                .map_err(|_| "Could not tessellate polygon".to_owned())
+        })
+    }
+
+    fn stroke_polyline(
+        &mut self,
+        points: &[Point],
+        closed: bool,
+        style: &LineStyle,
+        color: Color,
+    ) -> &mut Self {
+        let dashes = if style.dashes.is_empty() {
+            None
+        } else {
+            let keep_dots = style.cap != LineCap::Butt;
+            dash_polyline(points, closed, &style.dashes, style.dash_offset, keep_dots)
+        };
+        match dashes {
+            Some(dashes) => {
+                for dash in dashes {
+                    match dash {
+                        Dash::Line(points) => self.tessellate_stroke(&points, false, style, color),
+                        Dash::Dot(point, direction) => self.stroke_dot(point, direction, style, color),
+                    };
+                }
+                self
+            }
+            None => self.tessellate_stroke(points, closed, style, color),
+        }
+    }
+
+    /// Adds the caps of a line of no length at `point`, going in
+    /// `direction`, which is all there is to draw of it.
+    fn stroke_dot(
+        &mut self,
+        point: Point,
+        direction: Point,
+        style: &LineStyle,
+        color: Color,
+    ) -> &mut Self {
+        let half_width = style.width / 2.0;
+        match style.cap {
+            LineCap::Butt => self,
+            LineCap::Round => self.circle(DrawMode::Fill, point, half_width, POINT_TOLERANCE, color),
+            LineCap::Square => {
+                // Half a line width along the direction, and across it.
+                let length = (direction.x * direction.x + direction.y * direction.y).sqrt();
+                let (dx, dy) = if length > 0.0 {
+                    (direction.x / length * half_width, direction.y / length * half_width)
+                } else {
+                    (half_width, 0.0)
+                };
+                let corners = [
+                    Point::new(point.x + dx - dy, point.y + dy + dx),
+                    Point::new(point.x + dx + dy, point.y + dy - dx),
+                    Point::new(point.x - dx + dy, point.y - dy - dx),
+                    Point::new(point.x - dx - dy, point.y - dy + dx),
+                ];
+                self.fill_polyline(&corners, color)
+            }
+        }
+    }
+
+    fn tessellate_stroke(
+        &mut self,
+        points: &[Point],
+        closed: bool,
+        style: &LineStyle,
+        color: Color,
+    ) -> &mut Self {
+        // lyon can neither bevel corners nor limit how far
+        // it miters them, so those are built by hand.
+        if style.join != LineJoin::Round {
+            return self.stroke_with_corners(points, closed, style, color);
+        }
+        self.tessellate(color, |buffers, vertex_builder| {
+            let points = points.iter().map(|p| t::math::point(p.x, p.y));
+            let builder = &mut t::BuffersBuilder::new(buffers, vertex_builder);
+            let options = t::StrokeOptions::from(style);
+            t::basic_shapes::stroke_polyline(points, closed, &options, builder);
+            Ok(())
+        })
+    }
+
+    /// Adds a line with mitered or beveled corners.  Each segment is
+    /// a quad, cut short on the inside of the corners it meets, and
+    /// the gap left on the outside is filled by a triangle for a
+    /// bevel, or two out to the tip of the miter.
+    fn stroke_with_corners(
+        &mut self,
+        points: &[Point],
+        closed: bool,
+        style: &LineStyle,
+        color: Color,
+    ) -> &mut Self {
+        use std::f32::consts::PI;
+        let mut points = points.to_vec();
+        points.dedup();
+        if closed && points.len() > 1 && points[0] == points[points.len() - 1] {
+            points.pop();
+        }
+        if points.len() < 2 {
+            return match points.first() {
+                Some(&point) if !closed => {
+                    self.stroke_dot(point, Point::new(1.0, 0.0), style, color)
+                }
+                _ => self,
+            };
+        }
+
+        let half_width = style.width / 2.0;
+        let count = if closed { points.len() } else { points.len() - 1 };
+        let offset = |p: Point, v: Point, by: f32| Point::new(p.x + v.x * by, p.y + v.y * by);
+        let mut lengths = Vec::with_capacity(count);
+        let mut directions = Vec::with_capacity(count);
+        // The corners of each segment: the start and end on the side
+        // its normal points to, then the end and start on the other.
+        let mut quads = Vec::with_capacity(count);
+        for i in 0..count {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            let length = ((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt();
+            let d = Point::new((b.x - a.x) / length, (b.y - a.y) / length);
+            let n = Point::new(-d.y, d.x);
+            lengths.push(length);
+            directions.push(d);
+            quads.push([
+                offset(a, n, half_width),
+                offset(b, n, half_width),
+                offset(b, n, -half_width),
+                offset(a, n, -half_width),
+            ]);
+        }
+
+        let mut triangles = Vec::new();
+        let corners = if closed { 0..points.len() } else { 1..points.len() - 1 };
+        for i in corners {
+            let (before, after) = ((i + count - 1) % count, i % count);
+            let (d0, d1) = (directions[before], directions[after]);
+            let cross = d0.x * d1.y - d0.y * d1.x;
+            let dot = d0.x * d1.x + d0.y * d1.y;
+            // Straight on, or straight back, where there's nothing
+            // outside the ends of the segments to fill in.
+            if cross == 0.0 {
+                continue;
+            }
+            let corner = points[i];
+            // Which way the normals point to the outside of the corner.
+            let outwards = if cross > 0.0 { -1.0 } else { 1.0 };
+            let (n0, n1) = (Point::new(-d0.y, d0.x), Point::new(-d1.y, d1.x));
+            // The miter's tip is this far out from the corner, in
+            // half line widths, and the inner edges of the segments
+            // meet as far out on the other side.
+            let miter = Point::new((n0.x + n1.x) / (1.0 + dot), (n0.y + n1.y) / (1.0 + dot));
+            let inside = offset(corner, miter, -outwards * half_width);
+            let (back, on) = (
+                (corner.x - inside.x) * d0.x + (corner.y - inside.y) * d0.y,
+                (inside.x - corner.x) * d1.x + (inside.y - corner.y) * d1.y,
+            );
+            // Segments too short to cut are left to overlap.
+            let center = if back <= lengths[before] && on <= lengths[after] {
+                let (end, start) = if outwards > 0.0 { (2, 3) } else { (1, 0) };
+                quads[before][end] = inside;
+                quads[after][start] = inside;
+                inside
+            } else {
+                corner
+            };
+            let outer0 = offset(corner, n0, outwards * half_width);
+            let outer1 = offset(corner, n1, outwards * half_width);
+            let miter_length = (miter.x * miter.x + miter.y * miter.y).sqrt();
+            if style.join == LineJoin::Miter && miter_length <= style.miter_limit.max(1.0) {
+                let tip = offset(corner, miter, outwards * half_width);
+                triangles.extend_from_slice(&[center, outer0, tip, center, tip, outer1]);
+            } else {
+                triangles.extend_from_slice(&[center, outer0, outer1]);
+            }
+        }
+
+        let mut caps = Vec::new();
+        if !closed {
+            let (first, last) = (directions[0], directions[count - 1]);
+            match style.cap {
+                LineCap::Butt => (),
+                LineCap::Square => {
+                    for &i in &[0, 3] {
+                        quads[0][i] = offset(quads[0][i], first, -half_width);
+                    }
+                    for &i in &[1, 2] {
+                        quads[count - 1][i] = offset(quads[count - 1][i], last, half_width);
+                    }
+                }
+                LineCap::Round => {
+                    // Half circles from one side of the line round to
+                    // the other, through the way it comes from or goes.
+                    let start = first.x.atan2(-first.y);
+                    let end = last.x.atan2(-last.y);
+                    let (a, b) = (points[0], points[points.len() - 1]);
+                    caps.push(flatten_arc(a, half_width, start + PI, start, POINT_TOLERANCE));
+                    caps.push(flatten_arc(b, half_width, end, end - PI, POINT_TOLERANCE));
+                }
+            }
+        }
+
+        for q in &quads {
+            triangles.extend_from_slice(&[q[0], q[1], q[2], q[0], q[2], q[3]]);
+        }
+        self.triangles(&triangles, color);
+        for cap in &caps {
+            self.fill_polyline(cap, color);
+        }
+        self
+    }
+
+    /// Runs one of lyon's tessellators and adds what it makes.
+    fn tessellate<F>(&mut self, color: Color, f: F) -> &mut Self
+    where
+        F: FnOnce(&mut t::VertexBuffers<Vertex>, VertexBuilder) -> Result<(), String>,
+    {
+        let mut buffers = t::VertexBuffers::new();
+        let vertex_builder = VertexBuilder {
+            color: color.into(),
+        };
+        match f(&mut buffers, vertex_builder) {
+            Ok(()) => {
+                let base = self.vertices.len() as u32;
+                self.vertices.extend(buffers.vertices);
+                self.indices
+                    .extend(buffers.indices.iter().map(|&i| base + u32::from(i)));
+                self
+            }
+            Err(msg) => self.fail(msg),
+        }
+    }
+
+    /// Remembers the first thing that went wrong, for `build()`.
+    fn fail(&mut self, msg: String) -> &mut Self {
+        if self.error.is_none() {
+            self.error = Some(msg);
+        }
+        self
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[test]
+    fn test_flatten_curves() {
+        use std::f32::consts::PI;
+        let center = Point::new(10.0, 20.0);
+        let points = flatten_arc(center, 100.0, 0.0, PI, 0.1);
+        assert!(points.len() > 3);
+        let first = points[0];
+        let last = points[points.len() - 1];
+        assert!((first.x - 110.0).abs() < 0.001 && (first.y - 20.0).abs() < 0.001);
+        assert!((last.x - -90.0).abs() < 0.001 && (last.y - 20.0).abs() < 0.001);
+        // lyon's curves stray from the circle by a fraction of a percent.
+        for p in &points {
+            let d = ((p.x - center.x).powi(2) + (p.y - center.y).powi(2)).sqrt();
+            assert!((d - 100.0).abs() < 0.5);
+        }
+        // Whole turns go all the way round.
+        let circle = flatten_arc(center, 100.0, 0.0, 2.0 * PI, 0.1);
+        assert!(circle.len() > points.len());
+        let last = circle[circle.len() - 1];
+        assert!((last.x - 110.0).abs() < 0.001 && (last.y - 20.0).abs() < 0.001);
+        // Going round more than once draws the circle just once.
+        assert_eq!(flatten_arc(center, 100.0, 0.0, 1.0e9, 0.1).len(), circle.len());
+
+        let start = Point::new(0.0, 0.0);
+        let end = Point::new(100.0, 0.0);
+        let straight = flatten_quadratic_bezier(start, Point::new(50.0, 0.0), end, 0.1);
+        assert_eq!(straight, vec![start, end]);
+        let curved = flatten_cubic_bezier(start, Point::new(0.0, 100.0), Point::new(100.0, 100.0), end, 0.1);
+        assert!(curved.len() > 2);
+        assert_eq!(curved[curved.len() - 1], end);
+    }
+
+    #[test]
+    fn test_mesh_builder() {
+        let red = Color::new(1.0, 0.0, 0.0, 1.0);
+        let blue = Color::new(0.0, 0.0, 1.0, 1.0);
+        let mut builder = MeshBuilder::new();
+        builder
+            .triangles(&[Point::new(0.0, 0.0), Point::new(1.0, 0.0), Point::new(0.0, 1.0)], red)
+            .rectangle(DrawMode::Fill, Rect::new(10.0, 10.0, 4.0, 4.0), blue);
+        assert_eq!(&builder.indices[..3], &[0, 1, 2]);
+        // The rectangle's indices count from its own first vertex.
+        assert!(builder.indices[3..].iter().all(|&i| i >= 3));
+        assert!(builder.vertices[..3].iter().all(|v| v.color == [1.0, 0.0, 0.0, 1.0]));
+        assert!(builder.vertices[3..].iter().all(|v| v.color == [0.0, 0.0, 1.0, 1.0]));
+        assert!(builder.error.is_none());
+
+        let vertex = builder.vertices[0];
+        builder.raw(&[vertex], &[1]).triangles(&[Point::new(0.0, 0.0)], red);
+        assert!(builder.error.unwrap().contains("out of range"));
+    }
+
+    #[test]
+    fn test_dash_polyline() {
+        let p = Point::new;
+        let line = [p(0.0, 0.0), p(10.0, 0.0)];
+        let dashes = dash_polyline(&line, false, &[2.0, 3.0], 0.0, false).unwrap();
+        assert_eq!(
+            dashes,
+            vec![
+                Dash::Line(vec![p(0.0, 0.0), p(2.0, 0.0)]),
+                Dash::Line(vec![p(5.0, 0.0), p(7.0, 0.0)]),
+            ]
+        );
+
+        let dashes = dash_polyline(&line, false, &[2.0, 3.0], 1.0, false).unwrap();
+        assert_eq!(dashes.len(), 3);
+        assert_eq!(dashes[0], Dash::Line(vec![p(0.0, 0.0), p(1.0, 0.0)]));
+        assert_eq!(dashes[2], Dash::Line(vec![p(9.0, 0.0), p(10.0, 0.0)]));
+
+        // A dash keeps the corners it goes around.
+        let corner = [p(0.0, 0.0), p(2.0, 0.0), p(2.0, 2.0)];
+        let dashes = dash_polyline(&corner, false, &[3.0, 1.0], 0.0, false).unwrap();
+        assert_eq!(dashes[0], Dash::Line(vec![p(0.0, 0.0), p(2.0, 0.0), p(2.0, 1.0)]));
+
+        // Odd patterns repeat, so dashes and gaps alternate.
+        let dashes = dash_polyline(&line, false, &[1.0], 0.0, false).unwrap();
+        assert_eq!(dashes.len(), 5);
+
+        // Dashes of no length are dots, going the way the line does.
+        let dots = dash_polyline(&corner, false, &[0.0, 3.0], 0.0, true).unwrap();
+        assert_eq!(
+            dots,
+            vec![Dash::Dot(p(0.0, 0.0), p(2.0, 0.0)), Dash::Dot(p(2.0, 1.0), p(0.0, 2.0))]
+        );
+        assert!(dash_polyline(&corner, false, &[0.0, 3.0], 0.0, false).unwrap().is_empty());
+
+        assert!(dash_polyline(&line, false, &[0.0, 0.0], 0.0, false).is_none());
+        assert!(dash_polyline(&line, false, &[-1.0, 2.0], 0.0, false).is_none());
+    }
+
+    #[test]
+    fn test_dotted_lines() {
+        let line = [Point::new(0.0, 0.0), Point::new(25.0, 0.0)];
+        let mut style = LineStyle {
+            width: 4.0,
+            cap: LineCap::Round,
+            dashes: vec![0.0, 10.0],
+            ..Default::default()
+        };
+        let mut round = MeshBuilder::new();
+        round.set_line_style(&style).polyline(DrawMode::Line, &line, WHITE);
+        // Three dots, at 0, 10 and 20, each a circle 4 across.
+        assert!(round.error.is_none());
+        assert!(!round.vertices.is_empty());
+        for v in &round.vertices {
+            let nearest = (v.pos[0] / 10.0).round() * 10.0;
+            assert!((v.pos[0] - nearest).abs() <= 2.001 && v.pos[1].abs() <= 2.001);
+        }
+
+        style.cap = LineCap::Square;
+        let mut square = MeshBuilder::new();
+        square.set_line_style(&style).polyline(DrawMode::Line, &line, WHITE);
+        assert!(!square.vertices.is_empty());
+        // A square half a line width around each dot.
+        assert!(square.vertices.iter().any(|v| {
+            (v.pos[0] - 2.0).abs() < 0.001 && (v.pos[1] - 2.0).abs() < 0.001
+        }));
+
+        style.cap = LineCap::Butt;
+        let mut butt = MeshBuilder::new();
+        butt.set_line_style(&style).polyline(DrawMode::Line, &line, WHITE);
+        assert!(butt.vertices.is_empty());
+    }
+
+    /// The area the builder's triangles cover, counting overlaps twice.
+    fn covered_area(builder: &MeshBuilder) -> f32 {
+        builder
+            .indices
+            .chunks(3)
+            .map(|t| {
+                let (a, b, c) = (
+                    builder.vertices[t[0] as usize].pos,
+                    builder.vertices[t[1] as usize].pos,
+                    builder.vertices[t[2] as usize].pos,
+                );
+                ((b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])).abs() / 2.0
+            })
+            .sum()
+    }
+
+    fn has_vertex(builder: &MeshBuilder, x: f32, y: f32) -> bool {
+        builder
+            .vertices
+            .iter()
+            .any(|v| (v.pos[0] - x).abs() < 0.001 && (v.pos[1] - y).abs() < 0.001)
+    }
+
+    #[test]
+    fn test_line_joins() {
+        let p = Point::new;
+        let corner = [p(0.0, 0.0), p(10.0, 0.0), p(10.0, 10.0)];
+        let mut style = LineStyle {
+            width: 2.0,
+            ..Default::default()
+        };
+        let mut miter = MeshBuilder::new();
+        miter.set_line_style(&style).polyline(DrawMode::Line, &corner, WHITE);
+        // The outside of the corner goes out to a point, and the
+        // segments are cut short on the inside so they don't overlap.
+        assert!(has_vertex(&miter, 11.0, -1.0));
+        assert!(has_vertex(&miter, 9.0, 1.0));
+        assert!((covered_area(&miter) - 40.0).abs() < 0.001);
+
+        style.join = LineJoin::Bevel;
+        let mut bevel = MeshBuilder::new();
+        bevel.set_line_style(&style).polyline(DrawMode::Line, &corner, WHITE);
+        assert!(has_vertex(&bevel, 10.0, -1.0) && has_vertex(&bevel, 11.0, 0.0));
+        assert!(!has_vertex(&bevel, 11.0, -1.0));
+        assert!((covered_area(&bevel) - 39.5).abs() < 0.001);
+
+        // A miter sticking out more than the limit is beveled instead.
+        style.join = LineJoin::Miter;
+        style.miter_limit = 1.0;
+        let mut limited = MeshBuilder::new();
+        limited.set_line_style(&style).polyline(DrawMode::Line, &corner, WHITE);
+        assert_eq!(limited.vertices, bevel.vertices);
+
+        let sharp = [p(0.0, 0.0), p(10.0, 0.0), p(0.0, 1.0)];
+        style.miter_limit = 4.0;
+        let mut short = MeshBuilder::new();
+        short.set_line_style(&style).polyline(DrawMode::Line, &sharp, WHITE);
+        assert!(short.vertices.iter().all(|v| v.pos[0] <= 11.001));
+        style.miter_limit = 100.0;
+        let mut long = MeshBuilder::new();
+        long.set_line_style(&style).polyline(DrawMode::Line, &sharp, WHITE);
+        assert!(long.vertices.iter().any(|v| v.pos[0] > 25.0));
+
+        // Every corner of an outline is joined, and it has no ends.
+        style.miter_limit = 4.0;
+        let square = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let mut outline = MeshBuilder::new();
+        outline.set_line_style(&style).rectangle(DrawMode::Line, square, WHITE);
+        for &(x, y) in &[(-6.0, -6.0), (6.0, -6.0), (6.0, 6.0), (-6.0, 6.0)] {
+            assert!(has_vertex(&outline, x, y));
+        }
+        assert!((covered_area(&outline) - 80.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_line_caps() {
+        let line = [Point::new(0.0, 0.0), Point::new(10.0, 0.0)];
+        let mut style = LineStyle {
+            width: 2.0,
+            cap: LineCap::Square,
+            ..Default::default()
+        };
+        let mut square = MeshBuilder::new();
+        square.set_line_style(&style).polyline(DrawMode::Line, &line, WHITE);
+        assert!(has_vertex(&square, -1.0, -1.0) && has_vertex(&square, 11.0, 1.0));
+        assert!((covered_area(&square) - 24.0).abs() < 0.001);
+
+        style.cap = LineCap::Round;
+        let mut round = MeshBuilder::new();
+        round.set_line_style(&style).polyline(DrawMode::Line, &line, WHITE);
+        assert!(round.vertices.iter().any(|v| v.pos[0] < -0.99));
+        assert!(round.vertices.iter().any(|v| v.pos[0] > 10.99));
+        for v in &round.vertices {
+            let x = v.pos[0].max(0.0).min(10.0);
+            assert!((v.pos[0] - x).hypot(v.pos[1]) <= 1.001);
+        }
+    }
+
     #[test]
     fn test_image_scaling_up() {
         let mut from: Vec<u8> = Vec::new();
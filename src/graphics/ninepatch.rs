@@ -0,0 +1,284 @@
+//! Nine-patches, for UI panels and buttons that can be drawn at
+//! any size without stretching their borders.
+//!
+//! The image is cut into nine pieces by four insets: the corners are
+//! drawn as they are, the edges are stretched or tiled along their
+//! length, and the middle is stretched or tiled both ways.  All nine
+//! pieces are drawn with a single `SpriteBatch`.
+
+use std::cell::{Cell, RefCell};
+
+use super::*;
+use super::spritebatch::SpriteBatch;
+
+/// How a `NinePatch` fills its edges and middle.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PatchMode {
+    /// Stretches them to fit.
+    Stretch,
+    /// Repeats them at their original size, cutting off the last one.
+    Tile,
+}
+
+impl Default for PatchMode {
+    fn default() -> Self {
+        PatchMode::Stretch
+    }
+}
+
+/// An `Image` cut into nine pieces so it can be drawn at any size,
+/// keeping its borders the same.
+///
+/// The size to draw it at is given by `DrawParam::scale`, as for any
+/// other image: a scale of (2.0, 3.0) draws a panel twice as wide and
+/// three times as high as the image, with borders the same size as in
+/// the image.  `get_scale_for_size()` works out the scale for a size
+/// in pixels.  Like an image, it is drawn centered on `DrawParam::dest`.
+///
+/// ```rust,ignore
+/// let panel = NinePatch::new(image, 8, 8, 8, 8)?;
+/// let param = DrawParam {
+///     dest: Point::new(200.0, 150.0),
+///     scale: panel.get_scale_for_size(300.0, 120.0),
+///     ..Default::default()
+/// };
+/// graphics::draw_ex(ctx, &panel, param)?;
+/// ```
+#[derive(Debug)]
+pub struct NinePatch {
+    image: Image,
+    left: u32,
+    right: u32,
+    top: u32,
+    bottom: u32,
+    mode: PatchMode,
+    batch: RefCell<SpriteBatch>,
+    /// The size and Y direction the batch was last laid out for.
+    laid_out_for: Cell<Option<(f32, f32, bool)>>,
+}
+
+impl NinePatch {
+    /// Creates a new nine-patch from an image and the widths of its
+    /// left, right, top and bottom borders, in pixels.
+    pub fn new(image: Image, left: u32, right: u32, top: u32, bottom: u32) -> GameResult<NinePatch> {
+        if !borders_fit(left, right, image.width()) || !borders_fit(top, bottom, image.height()) {
+            let msg = format!(
+                "Nine-patch borders {}+{} by {}+{} don't fit in a {}x{} image",
+                left,
+                right,
+                top,
+                bottom,
+                image.width(),
+                image.height()
+            );
+            return Err(GameError::RenderError(msg));
+        }
+        Ok(NinePatch {
+            batch: RefCell::new(SpriteBatch::with_capacity(image.clone(), 9)),
+            image: image,
+            left: left,
+            right: right,
+            top: top,
+            bottom: bottom,
+            mode: PatchMode::default(),
+            laid_out_for: Cell::new(None),
+        })
+    }
+
+    /// Sets how the edges and middle are filled.  Default: stretched.
+    pub fn set_mode(&mut self, mode: PatchMode) {
+        self.mode = mode;
+        self.laid_out_for.set(None);
+    }
+
+    /// Returns how the edges and middle are filled.
+    pub fn get_mode(&self) -> PatchMode {
+        self.mode
+    }
+
+    /// Returns the scale to put in a `DrawParam` to draw
+    /// the nine-patch at the given size in pixels.
+    pub fn get_scale_for_size(&self, width: f32, height: f32) -> Point {
+        Point::new(
+            width / self.image.width() as f32,
+            height / self.image.height() as f32,
+        )
+    }
+
+    /// Returns the image the nine-patch is cut from.
+    pub fn get_image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Fills the batch with the pieces for a panel of the given size.
+    fn lay_out(&self, width: f32, height: f32, y_down: bool) {
+        let (image_w, image_h) = (self.image.width() as f32, self.image.height() as f32);
+        let columns = axis_spans(
+            image_w,
+            self.left as f32,
+            self.right as f32,
+            width,
+            self.mode,
+        );
+        let rows = axis_spans(
+            image_h,
+            self.top as f32,
+            self.bottom as f32,
+            height,
+            self.mode,
+        );
+        // Pieces are laid out from the top-left corner, but drawn
+        // around the center, and upside down if Y points up.
+        let y_sign = if y_down { 1.0 } else { -1.0 };
+        let mut batch = self.batch.borrow_mut();
+        batch.clear();
+        for row in &rows {
+            for column in &columns {
+                let src = Rect::new(
+                    column.src / image_w,
+                    row.src / image_h,
+                    column.src_len / image_w,
+                    row.src_len / image_h,
+                );
+                let center = Point::new(
+                    column.dest + column.dest_len / 2.0 - width / 2.0,
+                    (row.dest + row.dest_len / 2.0 - height / 2.0) * y_sign,
+                );
+                batch.add(DrawParam {
+                    src: src,
+                    dest: center,
+                    scale: Point::new(
+                        column.dest_len / column.src_len,
+                        row.dest_len / row.src_len,
+                    ),
+                    ..Default::default()
+                });
+            }
+        }
+        self.laid_out_for.set(Some((width, height, y_down)));
+    }
+}
+
+impl Drawable for NinePatch {
+    fn draw_ex(&self, ctx: &mut Context, param: DrawParam) -> GameResult<()> {
+        let width = self.image.width() as f32 * param.scale.x.abs();
+        let height = self.image.height() as f32 * param.scale.y.abs();
+        let y_down = get_screen_coordinates(ctx).h < 0.0;
+        if self.laid_out_for.get() != Some((width, height, y_down)) {
+            self.lay_out(width, height, y_down);
+        }
+        // The size is taken care of by the layout; only
+        // flipping is left for the scale to do.
+        let mut batch_param = param;
+        batch_param.scale = Point::new(param.scale.x.signum(), param.scale.y.signum());
+        self.batch.borrow().draw_ex(ctx, batch_param)
+    }
+}
+
+/// A stretch of image along one axis and where it goes in the panel,
+/// in pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Span {
+    src: f32,
+    src_len: f32,
+    dest: f32,
+    dest_len: f32,
+}
+
+/// Works out the spans along one axis of a nine-patch: the start
+/// border, the middle, which may be tiled, and the end border.
+/// Borders that don't fit in the target length are shrunk to fit.
+/// Spans that would take up no room are left out.
+fn axis_spans(image_len: f32, start: f32, end: f32, target: f32, mode: PatchMode) -> Vec<Span> {
+    let (dest_start, dest_end) = if start + end > target {
+        let shrink = target / (start + end);
+        (start * shrink, end * shrink)
+    } else {
+        (start, end)
+    };
+    let middle_src = image_len - start - end;
+    let middle_dest = target - dest_start - dest_end;
+
+    let mut spans = vec![
+        Span {
+            src: 0.0,
+            src_len: start,
+            dest: 0.0,
+            dest_len: dest_start,
+        },
+    ];
+    if middle_src > 0.0 {
+        match mode {
+            PatchMode::Stretch => spans.push(Span {
+                src: start,
+                src_len: middle_src,
+                dest: dest_start,
+                dest_len: middle_dest,
+            }),
+            PatchMode::Tile => {
+                let mut position = 0.0;
+                while position < middle_dest {
+                    let len = middle_src.min(middle_dest - position);
+                    spans.push(Span {
+                        src: start,
+                        src_len: len,
+                        dest: dest_start + position,
+                        dest_len: len,
+                    });
+                    position += middle_src;
+                }
+            }
+        }
+    }
+    spans.push(Span {
+        src: image_len - end,
+        src_len: end,
+        dest: target - dest_end,
+        dest_len: dest_end,
+    });
+    spans.retain(|s| s.src_len > 0.0 && s.dest_len > 0.0);
+    spans
+}
+
+/// Whether borders of the given sizes fit side by side in an image
+/// `size` pixels across, without overflowing when added up.
+fn borders_fit(first: u32, second: u32, size: u32) -> bool {
+    first.checked_add(second).map_or(false, |sum| sum <= size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_axis_spans() {
+        let stretched = axis_spans(32.0, 8.0, 4.0, 100.0, PatchMode::Stretch);
+        let lengths: Vec<_> = stretched.iter().map(|s| (s.src_len, s.dest_len)).collect();
+        assert_eq!(lengths, vec![(8.0, 8.0), (20.0, 88.0), (4.0, 4.0)]);
+        assert_eq!(stretched[2].dest, 96.0);
+
+        // 88 pixels of middle is four 20 pixel tiles and a cut-off 8.
+        let tiled = axis_spans(32.0, 8.0, 4.0, 100.0, PatchMode::Tile);
+        assert_eq!(tiled.len(), 7);
+        assert_eq!(tiled[5].dest, 88.0);
+        assert_eq!(tiled[5].src_len, 8.0);
+        assert_eq!(tiled[5].dest_len, 8.0);
+
+        // Too small for the borders: they shrink and the middle goes.
+        let squashed = axis_spans(32.0, 8.0, 4.0, 6.0, PatchMode::Stretch);
+        let lengths: Vec<_> = squashed.iter().map(|s| (s.src_len, s.dest_len)).collect();
+        assert_eq!(lengths, vec![(8.0, 4.0), (4.0, 2.0)]);
+
+        // No borders at all is just a stretched image.
+        let plain = axis_spans(32.0, 0.0, 0.0, 64.0, PatchMode::Stretch);
+        assert_eq!(plain.len(), 1);
+        assert_eq!(plain[0].dest_len, 64.0);
+    }
+
+    #[test]
+    fn test_borders_fit() {
+        assert!(borders_fit(8, 4, 12));
+        assert!(!borders_fit(8, 5, 12));
+        assert!(!borders_fit(::std::u32::MAX, 1, ::std::u32::MAX));
+    }
+}
@@ -0,0 +1,829 @@
+//! Particle systems, for effects like fire, smoke, sparks and
+//! explosions, modeled on Love2D's `ParticleSystem`.
+//!
+//! A `ParticleSystem` emits particles from an emitter, moves them
+//! along under whatever forces it's set up with, and changes their
+//! size and color as they age.  All the particles are copies of one
+//! `Image` or `Mesh`, and are drawn with a single instanced draw call,
+//! the same way a `SpriteBatch` is.
+
+use std::cell::RefCell;
+use std::cmp;
+use std::f32::consts::PI;
+use std::fmt;
+use std::time::Duration;
+
+use rand::{self, Rng, XorShiftRng};
+
+use timer;
+
+use super::*;
+
+/// The area new particles appear in, centered on the
+/// `ParticleSystem`'s position.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EmitterShape {
+    /// All particles start at the position itself.
+    Point,
+    /// Anywhere in a rectangle of the given size.
+    Rect { width: f32, height: f32 },
+    /// Anywhere in an ellipse of the given width and height.
+    Ellipse { width: f32, height: f32 },
+    /// Anywhere on the outline of an ellipse of the given width
+    /// and height, such as for a ring of sparks.
+    EllipseBorder { width: f32, height: f32 },
+}
+
+impl Default for EmitterShape {
+    fn default() -> Self {
+        EmitterShape::Point
+    }
+}
+
+/// What each particle of a `ParticleSystem` is drawn as.
+#[derive(Debug, Clone)]
+pub enum ParticleGraphic {
+    /// An image, drawn centered on the particle, at its
+    /// size in pixels times the particle's size.
+    Image(Image),
+    /// A mesh, drawn with the particle's position as its origin
+    /// and scaled by the particle's size.
+    Mesh(Mesh),
+}
+
+impl From<Image> for ParticleGraphic {
+    fn from(image: Image) -> Self {
+        ParticleGraphic::Image(image)
+    }
+}
+
+impl From<Mesh> for ParticleGraphic {
+    fn from(mesh: Mesh) -> Self {
+        ParticleGraphic::Mesh(mesh)
+    }
+}
+
+/// A single live particle.  Its acceleration and spin are picked
+/// when it is emitted and stay the same for its whole life.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Particle {
+    position: Point,
+    velocity: Point,
+    /// Where the emitter was when the particle was emitted;
+    /// radial and tangential acceleration are relative to this.
+    origin: Point,
+    linear_acceleration: Point,
+    radial_acceleration: f32,
+    tangential_acceleration: f32,
+    rotation: f32,
+    spin: f32,
+    age: f32,
+    lifetime: f32,
+}
+
+impl Particle {
+    /// Moves the particle along by `dt` seconds.
+    fn step(&mut self, dt: f32) {
+        let dx = self.position.x - self.origin.x;
+        let dy = self.position.y - self.origin.y;
+        let distance = (dx * dx + dy * dy).sqrt();
+        let (rx, ry) = if distance > 0.0 {
+            (dx / distance, dy / distance)
+        } else {
+            (0.0, 0.0)
+        };
+        // The tangent is the radial direction turned a quarter turn.
+        let ax = self.linear_acceleration.x + rx * self.radial_acceleration -
+            ry * self.tangential_acceleration;
+        let ay = self.linear_acceleration.y + ry * self.radial_acceleration +
+            rx * self.tangential_acceleration;
+        self.velocity.x += ax * dt;
+        self.velocity.y += ay * dt;
+        self.position.x += self.velocity.x * dt;
+        self.position.y += self.velocity.y * dt;
+        self.rotation += self.spin * dt;
+        self.age += dt;
+    }
+
+    /// How far through its life the particle is, from 0 to 1.
+    fn progress(&self) -> f32 {
+        if self.lifetime > 0.0 {
+            (self.age / self.lifetime).min(1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// A particle emitter's settings and the particles it has emitted.
+/// Kept apart from what the particles are drawn as, so it can be
+/// stepped on its own.
+struct Emitter {
+    particles: Vec<Particle>,
+    max_particles: usize,
+
+    position: Point,
+    shape: EmitterShape,
+    emission_rate: f32,
+    lifetime: Option<f32>,
+    particle_lifetime: (f32, f32),
+    direction: f32,
+    spread: f32,
+    speed: (f32, f32),
+    linear_acceleration: (Point, Point),
+    radial_acceleration: (f32, f32),
+    tangential_acceleration: (f32, f32),
+    rotation: (f32, f32),
+    spin: (f32, f32),
+    sizes: Vec<f32>,
+    colors: Vec<Color>,
+
+    active: bool,
+    age: f32,
+    /// Fractions of a particle left over from previous updates.
+    emit_carry: f32,
+    rng: XorShiftRng,
+}
+
+impl Emitter {
+    fn new(max_particles: usize) -> Emitter {
+        Emitter {
+            particles: Vec::with_capacity(max_particles),
+            max_particles: max_particles,
+            position: Point::zero(),
+            shape: EmitterShape::Point,
+            emission_rate: 10.0,
+            lifetime: None,
+            particle_lifetime: (1.0, 1.0),
+            direction: 0.0,
+            spread: 0.0,
+            speed: (0.0, 0.0),
+            linear_acceleration: (Point::zero(), Point::zero()),
+            radial_acceleration: (0.0, 0.0),
+            tangential_acceleration: (0.0, 0.0),
+            rotation: (0.0, 0.0),
+            spin: (0.0, 0.0),
+            sizes: vec![1.0],
+            colors: vec![WHITE],
+            active: true,
+            age: 0.0,
+            emit_carry: 0.0,
+            rng: rand::weak_rng(),
+        }
+    }
+
+    /// Moves the particles along and emits new ones, as if
+    /// `dt` seconds have passed.
+    fn update(&mut self, dt: f32) {
+        for particle in &mut self.particles {
+            particle.step(dt);
+        }
+        self.particles.retain(|p| p.age < p.lifetime);
+
+        if self.active {
+            let mut emit_time = dt;
+            if let Some(lifetime) = self.lifetime {
+                let left = lifetime - self.age;
+                if left <= dt {
+                    emit_time = left.max(0.0);
+                    self.active = false;
+                }
+            }
+            self.age += dt;
+            self.emit_carry += self.emission_rate * emit_time;
+            let count = self.emit_carry.floor();
+            self.emit_carry -= count;
+            self.emit(count as usize);
+            if !self.active {
+                // Same as `stop()`: a restarted emitter shouldn't
+                // get a head start from the last run.
+                self.emit_carry = 0.0;
+            }
+        }
+    }
+
+    /// Emits up to `count` particles, as many as there is
+    /// room for under the maximum.
+    fn emit(&mut self, count: usize) {
+        let room = self.max_particles.saturating_sub(self.particles.len());
+        for _ in 0..cmp::min(count, room) {
+            let particle = self.spawn();
+            self.particles.push(particle);
+        }
+    }
+
+    /// Picks the starting state of a new particle.
+    fn spawn(&mut self) -> Particle {
+        let rng = &mut self.rng;
+        let offset = random_point_in(self.shape, rng);
+        let angle = self.direction + (rng.next_f32() - 0.5) * self.spread;
+        let speed = random_between(rng, self.speed);
+        let (min_accel, max_accel) = self.linear_acceleration;
+        Particle {
+            position: Point::new(self.position.x + offset.x, self.position.y + offset.y),
+            velocity: Point::new(angle.cos() * speed, angle.sin() * speed),
+            origin: self.position,
+            linear_acceleration: Point::new(
+                random_between(rng, (min_accel.x, max_accel.x)),
+                random_between(rng, (min_accel.y, max_accel.y)),
+            ),
+            radial_acceleration: random_between(rng, self.radial_acceleration),
+            tangential_acceleration: random_between(rng, self.tangential_acceleration),
+            rotation: random_between(rng, self.rotation),
+            spin: random_between(rng, self.spin),
+            age: 0.0,
+            lifetime: random_between(rng, self.particle_lifetime),
+        }
+    }
+
+    fn start(&mut self) {
+        self.active = true;
+        self.age = 0.0;
+    }
+
+    fn stop(&mut self) {
+        self.active = false;
+        self.emit_carry = 0.0;
+    }
+
+    fn reset(&mut self) {
+        self.particles.clear();
+        self.age = 0.0;
+        self.emit_carry = 0.0;
+    }
+
+    /// Changes the maximum, removing the oldest particles
+    /// if there are more than that already.
+    fn set_max_particles(&mut self, max_particles: usize) {
+        if self.particles.len() > max_particles {
+            let excess = self.particles.len() - max_particles;
+            self.particles.drain(..excess);
+        }
+        self.max_particles = max_particles;
+    }
+
+    /// Returns how the given particle is drawn, before any
+    /// adjustment for the graphic it is drawn with.
+    fn particle_draw_param(&self, particle: &Particle) -> DrawParam {
+        let t = particle.progress();
+        let (i, j, f) = blend_indices(self.sizes.len(), t);
+        let size = self.sizes[i] + (self.sizes[j] - self.sizes[i]) * f;
+        let (i, j, f) = blend_indices(self.colors.len(), t);
+        let (a, b) = (self.colors[i], self.colors[j]);
+        let color = Color::new(
+            a.r + (b.r - a.r) * f,
+            a.g + (b.g - a.g) * f,
+            a.b + (b.b - a.b) * f,
+            a.a + (b.a - a.a) * f,
+        );
+        DrawParam {
+            dest: particle.position,
+            rotation: particle.rotation,
+            scale: Point::new(size, size),
+            color: Some(color),
+            ..Default::default()
+        }
+    }
+}
+
+/// A particle emitter and the particles it has emitted.
+///
+/// Particles are emitted continuously at the emission rate while the
+/// system is active, and in bursts with `emit()`.  Each one gets its
+/// lifetime, speed, accelerations, rotation and spin picked at random
+/// from the ranges set on the system; ranges are given as `(min, max)`
+/// and times are in seconds.  Sizes and colors are given as lists that
+/// each particle goes through evenly over its life, blending between
+/// neighbouring entries.
+///
+/// Particles move in the coordinates the system is drawn in, so moving
+/// the emitter with `set_position()` leaves particles already emitted
+/// where they are.  The `DrawParam` the system is drawn with applies
+/// on top of that to the whole system, as for a `SpriteBatch`.
+///
+/// ```rust,ignore
+/// let mut sparks = ParticleSystem::new(image, 500);
+/// sparks.set_emission_rate(0.0);
+/// sparks.set_particle_lifetime(0.3, 0.8);
+/// sparks.set_speed(100.0, 300.0);
+/// sparks.set_spread(2.0 * PI);
+/// sparks.set_colors(&[Color::new(1.0, 0.8, 0.2, 1.0), Color::new(1.0, 0.2, 0.0, 0.0)]);
+/// sparks.set_position(Point::new(x, y));
+/// sparks.emit(50);
+///
+/// // Then, every frame:
+/// sparks.update(timer::get_delta(ctx));
+/// graphics::draw(ctx, &sparks, Point::zero(), 0.0)?;
+/// ```
+pub struct ParticleSystem {
+    graphic: ParticleGraphic,
+    emitter: Emitter,
+    instances: RefCell<InstanceBuffer>,
+}
+
+impl ParticleSystem {
+    /// Creates a new particle system drawing the given `Image` or
+    /// `Mesh`, with room for at most `max_particles` at a time.
+    ///
+    /// It starts out active, emitting 10 particles a second from
+    /// a point, each living for one second and not moving.
+    pub fn new<G>(graphic: G, max_particles: usize) -> Self
+    where
+        G: Into<ParticleGraphic>,
+    {
+        ParticleSystem {
+            graphic: graphic.into(),
+            emitter: Emitter::new(max_particles),
+            instances: RefCell::new(InstanceBuffer::new()),
+        }
+    }
+
+    /// Moves the particles along and emits new ones, as if the
+    /// given amount of time has passed.  Usually called once per
+    /// frame from `EventHandler::update()` with `timer::get_delta()`.
+    pub fn update(&mut self, dt: Duration) {
+        self.emitter.update(timer::duration_to_f64(dt) as f32);
+        self.mark_dirty();
+    }
+
+    /// Emits a burst of particles at once, whether or not the
+    /// system is active.  Only as many as there is room for
+    /// under the maximum are emitted.
+    pub fn emit(&mut self, count: usize) {
+        self.emitter.emit(count);
+        self.mark_dirty();
+    }
+
+    /// Starts emitting particles at the emission rate, restarting
+    /// the emitter's lifetime if it has one.
+    pub fn start(&mut self) {
+        self.emitter.start();
+    }
+
+    /// Stops emitting particles.  The ones already emitted
+    /// carry on until they die.
+    pub fn stop(&mut self) {
+        self.emitter.stop();
+    }
+
+    /// Removes all particles and restarts the emitter's lifetime.
+    /// Whether it is active stays the same.
+    pub fn reset(&mut self) {
+        self.emitter.reset();
+        self.mark_dirty();
+    }
+
+    /// Returns whether the system is emitting particles at the
+    /// emission rate.
+    pub fn is_active(&self) -> bool {
+        self.emitter.active
+    }
+
+    /// Returns the number of live particles.
+    pub fn len(&self) -> usize {
+        self.emitter.particles.len()
+    }
+
+    /// Returns whether there are no live particles.
+    pub fn is_empty(&self) -> bool {
+        self.emitter.particles.is_empty()
+    }
+
+    /// Sets the most particles that can be alive at once.
+    /// If there are more than that already, the oldest are removed.
+    pub fn set_max_particles(&mut self, max_particles: usize) {
+        self.emitter.set_max_particles(max_particles);
+        self.mark_dirty();
+    }
+
+    /// Returns the most particles that can be alive at once.
+    pub fn get_max_particles(&self) -> usize {
+        self.emitter.max_particles
+    }
+
+    /// Sets what the particles are drawn as.
+    pub fn set_graphic<G>(&mut self, graphic: G)
+    where
+        G: Into<ParticleGraphic>,
+    {
+        self.graphic = graphic.into();
+        self.mark_dirty();
+    }
+
+    /// Returns what the particles are drawn as.
+    pub fn get_graphic(&self) -> &ParticleGraphic {
+        &self.graphic
+    }
+
+    /// Moves the emitter.  Particles already emitted stay where they are.
+    pub fn set_position(&mut self, position: Point) {
+        self.emitter.position = position;
+    }
+
+    /// Returns where the emitter is.
+    pub fn get_position(&self) -> Point {
+        self.emitter.position
+    }
+
+    /// Sets the area new particles appear in.  Default: a point.
+    pub fn set_emitter_shape(&mut self, shape: EmitterShape) {
+        self.emitter.shape = shape;
+    }
+
+    /// Returns the area new particles appear in.
+    pub fn get_emitter_shape(&self) -> EmitterShape {
+        self.emitter.shape
+    }
+
+    /// Sets how many particles are emitted per second while the
+    /// system is active.  Zero means particles only come from `emit()`.
+    pub fn set_emission_rate(&mut self, rate: f32) {
+        self.emitter.emission_rate = rate.max(0.0);
+    }
+
+    /// Returns how many particles are emitted per second.
+    pub fn get_emission_rate(&self) -> f32 {
+        self.emitter.emission_rate
+    }
+
+    /// Sets how long the system emits particles for once it is
+    /// started, in seconds, after which it stops by itself.
+    /// `None`, the default, means it keeps going until stopped.
+    pub fn set_emitter_lifetime(&mut self, lifetime: Option<f32>) {
+        self.emitter.lifetime = lifetime;
+    }
+
+    /// Returns how long the system emits particles for once started.
+    pub fn get_emitter_lifetime(&self) -> Option<f32> {
+        self.emitter.lifetime
+    }
+
+    /// Sets how long each particle lives, in seconds.
+    pub fn set_particle_lifetime(&mut self, min: f32, max: f32) {
+        self.emitter.particle_lifetime = (min, max);
+    }
+
+    /// Returns the range of how long each particle lives.
+    pub fn get_particle_lifetime(&self) -> (f32, f32) {
+        self.emitter.particle_lifetime
+    }
+
+    /// Sets the direction particles are emitted in, as an angle
+    /// in radians from the X axis.
+    pub fn set_direction(&mut self, direction: f32) {
+        self.emitter.direction = direction;
+    }
+
+    /// Returns the direction particles are emitted in.
+    pub fn get_direction(&self) -> f32 {
+        self.emitter.direction
+    }
+
+    /// Sets how widely the directions of particles are spread
+    /// around the emitter's direction, in radians.  A spread of
+    /// 2π emits particles in every direction.
+    pub fn set_spread(&mut self, spread: f32) {
+        self.emitter.spread = spread;
+    }
+
+    /// Returns how widely the directions of particles are spread.
+    pub fn get_spread(&self) -> f32 {
+        self.emitter.spread
+    }
+
+    /// Sets the speed particles start out with, in pixels per second.
+    pub fn set_speed(&mut self, min: f32, max: f32) {
+        self.emitter.speed = (min, max);
+    }
+
+    /// Returns the range of speeds particles start out with.
+    pub fn get_speed(&self) -> (f32, f32) {
+        self.emitter.speed
+    }
+
+    /// Sets the acceleration along the X and Y axes, such
+    /// as gravity or wind, in pixels per second squared.
+    pub fn set_linear_acceleration(&mut self, min: Point, max: Point) {
+        self.emitter.linear_acceleration = (min, max);
+    }
+
+    /// Returns the range of accelerations along the X and Y axes.
+    pub fn get_linear_acceleration(&self) -> (Point, Point) {
+        self.emitter.linear_acceleration
+    }
+
+    /// Sets the acceleration away from where the emitter was when
+    /// each particle was emitted.  Negative values pull particles
+    /// back in.
+    pub fn set_radial_acceleration(&mut self, min: f32, max: f32) {
+        self.emitter.radial_acceleration = (min, max);
+    }
+
+    /// Returns the range of accelerations away from the emitter.
+    pub fn get_radial_acceleration(&self) -> (f32, f32) {
+        self.emitter.radial_acceleration
+    }
+
+    /// Sets the acceleration at right angles to the direction
+    /// away from the emitter, which makes particles swirl around it.
+    pub fn set_tangential_acceleration(&mut self, min: f32, max: f32) {
+        self.emitter.tangential_acceleration = (min, max);
+    }
+
+    /// Returns the range of accelerations around the emitter.
+    pub fn get_tangential_acceleration(&self) -> (f32, f32) {
+        self.emitter.tangential_acceleration
+    }
+
+    /// Sets the rotation particles start out with, in radians.
+    pub fn set_rotation(&mut self, min: f32, max: f32) {
+        self.emitter.rotation = (min, max);
+    }
+
+    /// Returns the range of rotations particles start out with.
+    pub fn get_rotation(&self) -> (f32, f32) {
+        self.emitter.rotation
+    }
+
+    /// Sets how fast particles spin, in radians per second.
+    pub fn set_spin(&mut self, min: f32, max: f32) {
+        self.emitter.spin = (min, max);
+    }
+
+    /// Returns the range of how fast particles spin.
+    pub fn get_spin(&self) -> (f32, f32) {
+        self.emitter.spin
+    }
+
+    /// Sets the sizes particles go through over their life, as
+    /// scales of the image or mesh.  An empty list means 1.0.
+    pub fn set_sizes(&mut self, sizes: &[f32]) {
+        self.emitter.sizes = if sizes.is_empty() {
+            vec![1.0]
+        } else {
+            sizes.to_vec()
+        };
+    }
+
+    /// Returns the sizes particles go through over their life.
+    pub fn get_sizes(&self) -> &[f32] {
+        &self.emitter.sizes
+    }
+
+    /// Sets the colors particles go through over their life.
+    /// An empty list means white.
+    pub fn set_colors(&mut self, colors: &[Color]) {
+        self.emitter.colors = if colors.is_empty() {
+            vec![WHITE]
+        } else {
+            colors.to_vec()
+        };
+    }
+
+    /// Returns the colors particles go through over their life.
+    pub fn get_colors(&self) -> &[Color] {
+        &self.emitter.colors
+    }
+
+    fn mark_dirty(&mut self) {
+        self.instances.borrow_mut().mark_dirty();
+    }
+
+    /// Sends the particles to the graphics card, if they have
+    /// changed since the last time.
+    fn flush(&self, ctx: &mut Context) -> GameResult<()> {
+        let gfx = &mut ctx.gfx_context;
+        let instances = &mut *self.instances.borrow_mut();
+        if !instances.needs_upload(gfx.screen_rect) {
+            return Ok(());
+        }
+
+        let screen_rect = gfx.screen_rect;
+        let properties: Vec<RectProperties> = self.emitter
+            .particles
+            .iter()
+            .map(|p| {
+                let param = self.emitter.particle_draw_param(p);
+                match self.graphic {
+                    ParticleGraphic::Image(ref image) => {
+                        image.quad_draw_param(param, screen_rect).into()
+                    }
+                    ParticleGraphic::Mesh(ref mesh) => mesh.texture_draw_param(param).into(),
+                }
+            })
+            .collect();
+        instances.upload(gfx, &properties, self.emitter.max_particles)
+    }
+}
+
+impl fmt::Debug for ParticleSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<ParticleSystem: {} of {} particles, {:p}>",
+            self.emitter.particles.len(),
+            self.emitter.max_particles,
+            self
+        )
+    }
+}
+
+impl Drawable for ParticleSystem {
+    /// Draws every live particle.  The given `DrawParam` is applied
+    /// on top of each particle's own, as for a `SpriteBatch`; its
+    /// color, if any, tints the whole system.
+    fn draw_ex(&self, ctx: &mut Context, param: DrawParam) -> GameResult<()> {
+        self.flush(ctx)?;
+        let gfx = &mut ctx.gfx_context;
+        let slice = match self.graphic {
+            ParticleGraphic::Image(ref image) => image.bind(gfx),
+            ParticleGraphic::Mesh(ref mesh) => {
+                mesh.bind(gfx);
+                mesh.slice.clone()
+            }
+        };
+        self.instances.borrow().draw(gfx, slice, param)
+    }
+}
+
+/// Picks a number at random from a `(min, max)` range.
+fn random_between(rng: &mut XorShiftRng, (min, max): (f32, f32)) -> f32 {
+    min + (max - min) * rng.next_f32()
+}
+
+/// Picks a point at random in the given emitter shape,
+/// relative to its center.
+fn random_point_in(shape: EmitterShape, rng: &mut XorShiftRng) -> Point {
+    match shape {
+        EmitterShape::Point => Point::zero(),
+        EmitterShape::Rect { width, height } => Point::new(
+            (rng.next_f32() - 0.5) * width,
+            (rng.next_f32() - 0.5) * height,
+        ),
+        EmitterShape::Ellipse { width, height } => {
+            // The square root spreads points evenly over
+            // the area instead of bunching them in the middle.
+            let r = rng.next_f32().sqrt();
+            let angle = rng.next_f32() * 2.0 * PI;
+            Point::new(
+                angle.cos() * r * width / 2.0,
+                angle.sin() * r * height / 2.0,
+            )
+        }
+        EmitterShape::EllipseBorder { width, height } => {
+            let angle = rng.next_f32() * 2.0 * PI;
+            Point::new(angle.cos() * width / 2.0, angle.sin() * height / 2.0)
+        }
+    }
+}
+
+/// Finds the two entries of a list of `len` values, spread evenly
+/// over a particle's life, to blend between at progress `t`, and how
+/// far to blend from the first to the second.
+fn blend_indices(len: usize, t: f32) -> (usize, usize, f32) {
+    if len < 2 {
+        return (0, 0, 0.0);
+    }
+    let position = t.max(0.0).min(1.0) * (len - 1) as f32;
+    let i = cmp::min(position.floor() as usize, len - 2);
+    (i, i + 1, position - i as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn particle() -> Particle {
+        Particle {
+            position: Point::new(10.0, 0.0),
+            velocity: Point::zero(),
+            origin: Point::zero(),
+            linear_acceleration: Point::zero(),
+            radial_acceleration: 0.0,
+            tangential_acceleration: 0.0,
+            rotation: 0.0,
+            spin: 0.0,
+            age: 0.0,
+            lifetime: 2.0,
+        }
+    }
+
+    #[test]
+    fn test_particle_step() {
+        let mut p = particle();
+        p.velocity = Point::new(0.0, 5.0);
+        p.linear_acceleration = Point::new(0.0, 10.0);
+        p.spin = 1.0;
+        p.step(0.5);
+        assert_eq!(p.velocity, Point::new(0.0, 10.0));
+        assert_eq!(p.position, Point::new(10.0, 5.0));
+        assert_eq!(p.rotation, 0.5);
+        assert_eq!(p.progress(), 0.25);
+
+        // Radial acceleration pushes away from the origin,
+        // tangential acceleration a quarter turn from that.
+        let mut p = particle();
+        p.radial_acceleration = 4.0;
+        p.step(1.0);
+        assert_eq!(p.velocity, Point::new(4.0, 0.0));
+        let mut p = particle();
+        p.tangential_acceleration = 4.0;
+        p.step(1.0);
+        assert_eq!(p.velocity, Point::new(0.0, 4.0));
+    }
+
+    #[test]
+    fn test_emitter_shapes() {
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        for _ in 0..100 {
+            let p = random_point_in(EmitterShape::Rect { width: 20.0, height: 10.0 }, &mut rng);
+            assert!(p.x.abs() <= 10.0 && p.y.abs() <= 5.0);
+            let p = random_point_in(EmitterShape::Ellipse { width: 20.0, height: 10.0 }, &mut rng);
+            assert!((p.x / 10.0).powi(2) + (p.y / 5.0).powi(2) <= 1.0001);
+            let p = random_point_in(EmitterShape::EllipseBorder { width: 20.0, height: 20.0 }, &mut rng);
+            assert!(((p.x * p.x + p.y * p.y).sqrt() - 10.0).abs() < 0.001);
+            let x = random_between(&mut rng, (3.0, 4.0));
+            assert!(x >= 3.0 && x <= 4.0);
+        }
+        assert_eq!(random_point_in(EmitterShape::Point, &mut rng), Point::zero());
+    }
+
+    #[test]
+    fn test_emission_carry() {
+        let mut emitter = Emitter::new(100);
+        emitter.emission_rate = 2.0;
+        emitter.particle_lifetime = (100.0, 100.0);
+        // Each update is worth a quarter of a particle, so one
+        // comes out every fourth update and none are lost.
+        for _ in 0..3 {
+            emitter.update(0.125);
+            assert_eq!(emitter.particles.len(), 0);
+        }
+        emitter.update(0.125);
+        assert_eq!(emitter.particles.len(), 1);
+        emitter.update(1.25);
+        assert_eq!(emitter.particles.len(), 3);
+
+        // Stopping throws away the half particle left over.
+        emitter.stop();
+        emitter.start();
+        emitter.update(0.25);
+        assert_eq!(emitter.particles.len(), 3);
+        emitter.update(0.25);
+        assert_eq!(emitter.particles.len(), 4);
+    }
+
+    #[test]
+    fn test_emitter_lifetime() {
+        let mut emitter = Emitter::new(100);
+        emitter.emission_rate = 2.0;
+        emitter.particle_lifetime = (100.0, 100.0);
+        emitter.lifetime = Some(1.75);
+        emitter.update(1.5);
+        assert_eq!(emitter.particles.len(), 3);
+        // Only the quarter second the emitter had left counts.
+        emitter.update(1.5);
+        assert_eq!(emitter.particles.len(), 3);
+        assert!(!emitter.active);
+        emitter.update(1.0);
+        assert_eq!(emitter.particles.len(), 3);
+
+        // The half particle left over when it ran out
+        // doesn't carry over into the next run.
+        emitter.start();
+        emitter.update(0.25);
+        assert_eq!(emitter.particles.len(), 3);
+        assert!(emitter.active);
+        emitter.update(0.25);
+        assert_eq!(emitter.particles.len(), 4);
+    }
+
+    #[test]
+    fn test_set_max_particles() {
+        let mut emitter = Emitter::new(10);
+        emitter.emission_rate = 0.0;
+        emitter.particle_lifetime = (100.0, 100.0);
+        emitter.emit(6);
+        emitter.update(0.125);
+        emitter.emit(20);
+        assert_eq!(emitter.particles.len(), 10);
+
+        // The oldest particles go first.
+        emitter.set_max_particles(4);
+        assert_eq!(emitter.particles.len(), 4);
+        assert!(emitter.particles.iter().all(|p| p.age == 0.0));
+        emitter.set_max_particles(8);
+        assert_eq!(emitter.particles.len(), 4);
+        emitter.emit(10);
+        assert_eq!(emitter.particles.len(), 8);
+    }
+
+    #[test]
+    fn test_blend_indices() {
+        assert_eq!(blend_indices(1, 0.7), (0, 0, 0.0));
+        assert_eq!(blend_indices(2, 0.25), (0, 1, 0.25));
+        assert_eq!(blend_indices(3, 0.75), (1, 2, 0.5));
+        assert_eq!(blend_indices(3, 1.0), (1, 2, 1.0));
+    }
+}
@@ -0,0 +1,249 @@
+//! Reading pixels back from the graphics card, for screenshots
+//! and for checking rendering results in tests.
+
+use std::path;
+
+use gfx::format::Formatted;
+use gfx::memory::Typed;
+use gfx_device_gl;
+use gfx_device_gl::gl;
+use image::png;
+
+use super::*;
+
+/// Image file formats that `ImageData` can be encoded to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// A PNG file.
+    Png,
+}
+
+/// Pixel data that has been read back from the graphics card.
+///
+/// The pixels are stored as RGBA8, row by row, starting with
+/// the top row; the same layout `Image::from_rgba8()` takes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageData {
+    /// The width of the image in pixels.
+    pub width: u32,
+    /// The height of the image in pixels.
+    pub height: u32,
+    /// The RGBA8 pixel data.
+    pub rgba: Vec<u8>,
+}
+
+impl ImageData {
+    /// Returns the color of the pixel at the given position,
+    /// or `None` if it is out of bounds.
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<Color> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let i = ((y * self.width + x) * 4) as usize;
+        let p = &self.rgba[i..i + 4];
+        Some(Color::from((p[0], p[1], p[2], p[3])))
+    }
+
+    /// Uploads the data to the graphics card again as a new `Image`.
+    pub fn to_image(&self, ctx: &mut Context) -> GameResult<Image> {
+        Image::from_rgba8(ctx, self.width as u16, self.height as u16, &self.rgba)
+    }
+
+    /// Encodes the data in the given format and writes it to the
+    /// given path through the `Filesystem`, which puts it in the
+    /// user config directory.
+    pub fn encode<P: AsRef<path::Path>>(
+        &self,
+        ctx: &mut Context,
+        format: ImageFormat,
+        path: P,
+    ) -> GameResult<()> {
+        let mut file = ctx.filesystem.create(path)?;
+        match format {
+            ImageFormat::Png => {
+                png::PNGEncoder::new(&mut file).encode(
+                    &self.rgba,
+                    self.width,
+                    self.height,
+                    image::ColorType::RGBA(8),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Image {
+    /// Reads the image's pixels back from the graphics card.
+    pub fn to_rgba8(&self, ctx: &mut Context) -> GameResult<Vec<u8>> {
+        let gfx = &mut ctx.gfx_context;
+        read_texture(gfx, &self.raw_texture, self.width, self.height)
+    }
+
+    /// Reads the image back from the graphics card and writes it
+    /// to the given path in the user config directory, as
+    /// `ImageData::encode()` does.
+    pub fn encode<P: AsRef<path::Path>>(
+        &self,
+        ctx: &mut Context,
+        format: ImageFormat,
+        path: P,
+    ) -> GameResult<()> {
+        let data = ImageData {
+            width: self.width,
+            height: self.height,
+            rgba: self.to_rgba8(ctx)?,
+        };
+        data.encode(ctx, format, path)
+    }
+}
+
+/// Reads back what has been drawn to the current render target,
+/// which is either the window or the `Canvas` selected with
+/// `set_canvas()`.
+///
+/// Anything drawn but not yet presented is included; since the
+/// window's contents are undefined once `present()` has shown them,
+/// call this before `present()` when taking a screenshot.
+pub fn screenshot(ctx: &mut Context) -> GameResult<ImageData> {
+    let gfx = &mut ctx.gfx_context;
+    let (width, height, _, _) = gfx.data.out.get_dimensions();
+    let (width, height) = (width as u32, height as u32);
+    let rgba = match gfx.target_texture.clone() {
+        Some(texture) => read_texture(gfx, &texture, width, height)?,
+        None => read_window(gfx, width, height),
+    };
+    // Render targets have their origin in the bottom-left corner.
+    Ok(ImageData {
+        width: width,
+        height: height,
+        rgba: flip_rows(width, &rgba),
+    })
+}
+
+/// Copies the whole of the given texture into a buffer the CPU can read.
+fn read_texture(
+    gfx: &mut GraphicsContext,
+    texture: &gfx::handle::RawTexture<gfx_device_gl::Resources>,
+    width: u32,
+    height: u32,
+) -> GameResult<Vec<u8>> {
+    let count = (width * height) as usize;
+    let download = gfx.factory.create_download_buffer::<[u8; 4]>(count)?;
+    let info = texture::ImageInfoCommon {
+        xoffset: 0,
+        yoffset: 0,
+        zoffset: 0,
+        width: width as u16,
+        height: height as u16,
+        depth: 0,
+        format: ColorFormat::get_format(),
+        mipmap: 0,
+    };
+    gfx.encoder
+        .copy_texture_to_buffer_raw(texture, None, info, download.raw(), 0)?;
+    gfx.encoder.flush(&mut *gfx.device);
+    let reader = gfx.factory.read_mapping(&download)?;
+    let mut rgba = Vec::with_capacity(count * 4);
+    for pixel in reader.iter() {
+        rgba.extend_from_slice(pixel);
+    }
+    Ok(rgba)
+}
+
+/// The window's framebuffer has no texture behind it to copy from,
+/// so this goes to OpenGL directly.  gfx caches the state this
+/// changes, so it's all put back the way it was afterwards.
+fn read_window(gfx: &mut GraphicsContext, width: u32, height: u32) -> Vec<u8> {
+    gfx.encoder.flush(&mut *gfx.device);
+    let mut rgba = vec![0u8; (width * height * 4) as usize];
+    unsafe {
+        gfx.device.with_gl(|gl_ctx| {
+            let mut old_framebuffer = 0;
+            let mut old_read_buffer = 0;
+            let mut old_alignment = 0;
+            gl_ctx.GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut old_framebuffer);
+            gl_ctx.GetIntegerv(gl::PACK_ALIGNMENT, &mut old_alignment);
+            gl_ctx.BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+            gl_ctx.GetIntegerv(gl::READ_BUFFER, &mut old_read_buffer);
+            gl_ctx.ReadBuffer(gl::BACK);
+            gl_ctx.PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl_ctx.ReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                rgba.as_mut_ptr() as *mut _,
+            );
+            gl_ctx.ReadBuffer(old_read_buffer as gl::types::GLenum);
+            gl_ctx.PixelStorei(gl::PACK_ALIGNMENT, old_alignment);
+            gl_ctx.BindFramebuffer(gl::READ_FRAMEBUFFER, old_framebuffer as gl::types::GLuint);
+        });
+    }
+    rgba
+}
+
+/// Reverses the order of the rows of an RGBA8 image.
+fn flip_rows(width: u32, rgba: &[u8]) -> Vec<u8> {
+    let row_len = (width * 4) as usize;
+    if row_len == 0 {
+        return rgba.to_vec();
+    }
+    rgba.chunks(row_len)
+        .rev()
+        .flat_map(|row| row.iter().cloned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flip_rows() {
+        let rgba = [1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4];
+        let flipped = flip_rows(2, &rgba);
+        assert_eq!(flipped, vec![3, 3, 3, 3, 4, 4, 4, 4, 1, 1, 1, 1, 2, 2, 2, 2]);
+
+        let data = ImageData {
+            width: 2,
+            height: 2,
+            rgba: flipped,
+        };
+        assert_eq!(data.get_pixel(1, 0), Some(Color::from((4, 4, 4, 4))));
+        assert_eq!(data.get_pixel(2, 0), None);
+    }
+
+    // This needs a GL driver that can render off-screen, which not
+    // every build server has.  Run it with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_headless_golden_image() {
+        use conf;
+        let mut c = conf::Conf::new();
+        c.headless = true;
+        c.window_width = 32;
+        c.window_height = 24;
+        let ctx = &mut Context::load_from_conf("test_headless_golden_image", "ggez", c).unwrap();
+        set_background_color(ctx, Color::new(0.0, 0.0, 1.0, 1.0));
+        clear(ctx);
+        set_color(ctx, Color::new(1.0, 0.0, 0.0, 1.0)).unwrap();
+        rectangle(ctx, DrawMode::Fill, Rect::new(16.0, 8.0, 16.0, 8.0)).unwrap();
+        let shot = screenshot(ctx).unwrap();
+
+        let golden = include_bytes!("../../resources/golden_rectangle.png");
+        let golden = image::load_from_memory(golden).unwrap().to_rgba();
+        assert_eq!((shot.width, shot.height), golden.dimensions());
+        for (i, (&a, &b)) in shot.rgba.iter().zip(golden.into_raw().iter()).enumerate() {
+            let (x, y) = ((i / 4) as u32 % shot.width, (i / 4) as u32 / shot.width);
+            assert!(
+                (a as i32 - b as i32).abs() <= 2,
+                "pixel ({}, {}) differs from the golden image",
+                x,
+                y
+            );
+        }
+    }
+}
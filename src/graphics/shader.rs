@@ -0,0 +1,225 @@
+//! The `shader` module allows user-defined shaders to be used
+//! with ggez for cool and spooky effects.  See the `shader`
+//! example for a taste...
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::Read;
+use std::path;
+use std::rc::Rc;
+
+use gfx::handle::Buffer;
+use gfx::memory::Typed;
+use gfx::traits::{FactoryExt, Pod};
+use gfx_device_gl;
+
+use super::*;
+
+/// An ID used by the `GraphicsContext` to uniquely identify a shader.
+/// IDs are never reused, even by other contexts.
+pub type ShaderId = usize;
+
+/// A type for empty shader data, for shaders that do not require any
+/// additional data to be sent to the GPU.
+#[derive(Clone, Copy, Debug)]
+pub struct EmptyConst;
+
+unsafe impl Pod for EmptyConst {}
+
+/// A handle referring to a shader program that has been compiled
+/// and stored in the `GraphicsContext`, along with the buffer of
+/// user-defined uniforms of type `C` that it reads from.
+///
+/// The GLSL sources are compiled against the same layout as ggez's
+/// built-in shader (see `src/graphics/shader/basic_150.glslv`), so
+/// they must use the `a_Pos`, `a_Uv` and `a_VertColor` vertex inputs,
+/// the per-instance `RectProperties` inputs (`a_Src`, `a_Dest`,
+/// `a_Color`, etc.), the `Globals` uniform block, the `t_Texture`
+/// sampler and the `Target0` output as needed.  The
+/// user-defined uniforms are read from a uniform block with the
+/// name given when the shader is created, and are set with `send()`.
+///
+/// `C` is usually a type created with gfx's `gfx_defines!` macro's
+/// `constant` definition; use `EmptyConst` if the shader needs no
+/// extra uniforms.
+///
+/// Dropping a `Shader` frees its program.  Drawing while a dropped
+/// shader, or one created on another `Context`, is still selected
+/// returns an error.
+pub struct Shader<C>
+where
+    C: Pod,
+{
+    id: ShaderId,
+    buffer: Buffer<gfx_device_gl::Resources, C>,
+    name: String,
+    dropped: Rc<RefCell<Vec<ShaderId>>>,
+}
+
+impl<C> Shader<C>
+where
+    C: Pod,
+{
+    /// Create a new `Shader` given source files, constants and a name
+    /// for the uniform block the constants are bound to.
+    ///
+    /// The files are loaded through the `Filesystem`.  The vertex shader
+    /// may be `None`, in which case ggez's built-in vertex shader is used.
+    pub fn new<P: AsRef<path::Path>>(
+        ctx: &mut Context,
+        vertex_path: Option<P>,
+        pixel_path: P,
+        consts: C,
+        name: &str,
+    ) -> GameResult<Shader<C>> {
+        let vertex_source = match vertex_path {
+            Some(p) => {
+                let mut buf = Vec::new();
+                let mut reader = ctx.filesystem.open(p)?;
+                reader.read_to_end(&mut buf)?;
+                Some(buf)
+            }
+            None => None,
+        };
+        let pixel_source = {
+            let mut buf = Vec::new();
+            let mut reader = ctx.filesystem.open(pixel_path)?;
+            reader.read_to_end(&mut buf)?;
+            buf
+        };
+        Shader::from_u8(
+            ctx,
+            vertex_source.as_ref().map(|v| &v[..]),
+            &pixel_source,
+            consts,
+            name,
+        )
+    }
+
+    /// Create a new `Shader` directly from GLSL source code.
+    pub fn from_u8(
+        ctx: &mut Context,
+        vertex_source: Option<&[u8]>,
+        pixel_source: &[u8],
+        consts: C,
+        name: &str,
+    ) -> GameResult<Shader<C>> {
+        let vertex_source = vertex_source.unwrap_or(DEFAULT_VERTEX_SHADER);
+        let gfx = &mut ctx.gfx_context;
+        let buffer = gfx.factory.create_constant_buffer(1);
+        gfx.encoder.update_buffer(&buffer, &[consts], 0)?;
+        let program = ShaderProgram::new(
+            &mut *gfx.factory,
+            vertex_source,
+            pixel_source,
+            name,
+            buffer.raw().clone(),
+        )?;
+        let id = gfx.add_shader(program);
+        Ok(Shader {
+            id: id,
+            buffer: buffer,
+            name: name.to_owned(),
+            dropped: gfx.dropped_shaders.clone(),
+        })
+    }
+
+    /// Send data to the GPU for use with the `Shader`.
+    pub fn send(&self, ctx: &mut Context, consts: C) -> GameResult<()> {
+        ctx.gfx_context
+            .encoder
+            .update_buffer(&self.buffer, &[consts], 0)?;
+        Ok(())
+    }
+
+    /// Gets the shader ID for the `Shader` which is used by the
+    /// `GraphicsContext` for identifying the shader.
+    pub fn shader_id(&self) -> ShaderId {
+        self.id
+    }
+
+    /// Returns the name of the uniform block the shader's constants
+    /// are bound to.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<C> fmt::Debug for Shader<C>
+where
+    C: Pod,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<Shader[{}]: {}, {:p}>",
+            self.id,
+            self.name,
+            self
+        )
+    }
+}
+
+impl<C> Drop for Shader<C>
+where
+    C: Pod,
+{
+    fn drop(&mut self) {
+        // The context frees the program the next time it
+        // looks up a shader, since we can't reach it from here.
+        self.dropped.borrow_mut().push(self.id);
+    }
+}
+
+/// A lock for RAII shader regions.  The shader automatically
+/// gets cleared once the lock goes out of scope, restoring the
+/// previous shader (if any).
+///
+/// Essentially, binding a `Shader` will return one of these, and the
+/// `Shader` will remain active as long as the `ShaderLock` is alive.
+pub struct ShaderLock {
+    cell: Rc<RefCell<Option<ShaderId>>>,
+    previous_shader: Option<ShaderId>,
+}
+
+impl fmt::Debug for ShaderLock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<ShaderLock: restores {:?}>", self.previous_shader)
+    }
+}
+
+impl Drop for ShaderLock {
+    fn drop(&mut self) {
+        *self.cell.borrow_mut() = self.previous_shader;
+    }
+}
+
+/// Use a shader until the returned lock goes out of scope.
+pub fn use_shader<C>(ctx: &mut Context, shader: &Shader<C>) -> ShaderLock
+where
+    C: Pod,
+{
+    let cell = ctx.gfx_context.current_shader.clone();
+    let previous_shader = *cell.borrow();
+    set_shader(ctx, Some(shader));
+    ShaderLock {
+        cell: cell,
+        previous_shader: previous_shader,
+    }
+}
+
+/// Set the current shader for the `Context` to render with.
+/// `None` goes back to ggez's built-in shader; since that
+/// needs a type annotation, `clear_shader()` may be more convenient.
+pub fn set_shader<C>(ctx: &mut Context, shader: Option<&Shader<C>>)
+where
+    C: Pod,
+{
+    *ctx.gfx_context.current_shader.borrow_mut() = shader.map(|s| s.id);
+}
+
+/// Clears the the current shader for the `Context`, making ggez
+/// use its built-in shader.
+pub fn clear_shader(ctx: &mut Context) {
+    *ctx.gfx_context.current_shader.borrow_mut() = None;
+}
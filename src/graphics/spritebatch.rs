@@ -1,79 +1,167 @@
-/*
-use sdl2;
-use image;
-use gfx;
-use gfx::texture;
-use gfx::traits::Device;
-use gfx::traits::FactoryExt;
-use gfx_device_gl;
-use gfx::Factory;
-*/
+//! A `SpriteBatch` is a way to efficiently draw a large
+//! number of copies of the same image, or part of the same image.
+//!
+//! The sprites' `DrawParam`s are uploaded to the graphics card as
+//! instance data and the whole batch is drawn with a single draw call.
+//! The data is only re-uploaded when the batch actually changes, so
+//! drawing a batch that hasn't been touched since the last frame is
+//! very cheap.
+
+use std::cell::RefCell;
+use std::mem;
 
 use context::Context;
-use graphics;
+use graphics::{self, Color, DrawParam, InstanceBuffer, RectProperties};
 use GameResult;
 
-// Owning the given Image is inconvenient because we might want, say,
-// the same Rc<Image> shared among many SpriteBatch'es.
-//
-// But draw() doesn't handle that particularly well...
-//
-// The right way to fix this might be to have another object that implements
-// Drawable that is created from a SpriteBatch and an image reference.
-// BoundSpriteBatch or something.  That *feels* like the right way to go...
-//
-// Or just not implement Drawable and provide your own drawing functions, though
-// that's squirrelly.
-//
-// Oh, or maybe make it take a Cow<Image> ?  that might work.
-//
-// For now though, let's mess around with the gfx bits rather than the ggez bits.
-// We need to be able to, essentially, have an array of RectProperties.
-
-/// A SpriteBatch draws a number of copies of the same image, using a single draw call.
+/// A `SpriteBatch` draws a number of copies of the same image, using a single draw call.
 #[derive(Debug)]
 pub struct SpriteBatch {
     image: graphics::Image,
-    sprites: Vec<graphics::DrawParam>,
+    sprites: Vec<Option<DrawParam>>,
+    free_slots: Vec<SpriteIdx>,
+    instances: RefCell<InstanceBuffer>,
 }
 
+/// An index of a particular sprite in a `SpriteBatch`.
 pub type SpriteIdx = usize;
 
 impl SpriteBatch {
     /// Creates a new `SpriteBatch`, drawing with the given image.
     pub fn new(image: graphics::Image) -> Self {
+        Self::with_capacity(image, 0)
+    }
+
+    /// Creates a new `SpriteBatch` with room for the given number
+    /// of sprites before it has to grow its buffers.
+    pub fn with_capacity(image: graphics::Image, capacity: usize) -> Self {
         Self {
             image: image,
-            sprites: vec![],
+            sprites: Vec::with_capacity(capacity),
+            free_slots: vec![],
+            instances: RefCell::new(InstanceBuffer::new()),
         }
     }
 
     /// Adds a new sprite to the sprite batch.
     ///
+    /// The `color` of the `DrawParam` can be used to tint
+    /// each sprite individually.
+    ///
     /// Returns a handle with which to modify the sprite using `set()`
     pub fn add(&mut self, param: graphics::DrawParam) -> SpriteIdx {
-        self.sprites.push(param);
-        self.sprites.len() - 1
+        self.mark_dirty();
+        match self.free_slots.pop() {
+            Some(handle) => {
+                self.sprites[handle] = Some(param);
+                handle
+            }
+            None => {
+                self.sprites.push(Some(param));
+                self.sprites.len() - 1
+            }
+        }
+    }
+
+    /// Adds a new sprite to the sprite batch, tinted with the given color.
+    /// This is the same as `add()` with the `DrawParam`'s `color` set.
+    ///
+    /// Returns a handle with which to modify the sprite using `set()`
+    pub fn add_colored(&mut self, param: graphics::DrawParam, color: Color) -> SpriteIdx {
+        self.add(graphics::DrawParam {
+            color: Some(color),
+            ..param
+        })
     }
 
     /// Alters a sprite in the batch to use the given draw params.
+    ///
+    /// Does nothing if the sprite has been removed.
     pub fn set(&mut self, handle: SpriteIdx, param: graphics::DrawParam) {
-        self.sprites[handle] = param;
+        if let Some(ref mut sprite) = self.sprites[handle] {
+            *sprite = param;
+        }
+        self.mark_dirty();
     }
 
-    /// Immediately sends all data in the batch to the graphics card.
+    /// Alters the color of a sprite in the batch.
+    ///
+    /// Does nothing if the sprite has been removed.
+    pub fn set_color(&mut self, handle: SpriteIdx, color: Color) {
+        if let Some(ref mut sprite) = self.sprites[handle] {
+            sprite.color = Some(color);
+        }
+        self.mark_dirty();
+    }
+
+    /// Gets the draw params of a sprite in the batch, or `None` if
+    /// it has been removed.
+    pub fn get(&self, handle: SpriteIdx) -> Option<graphics::DrawParam> {
+        self.sprites.get(handle).and_then(|s| *s)
+    }
+
+    /// Removes a sprite from the batch.  Its handle may be reused
+    /// by later calls to `add()`; the handles of other sprites
+    /// stay valid.
+    pub fn remove(&mut self, handle: SpriteIdx) {
+        if self.sprites[handle].take().is_some() {
+            self.free_slots.push(handle);
+            self.mark_dirty();
+        }
+    }
+
+    /// Returns the number of sprites in the batch.
+    pub fn len(&self) -> usize {
+        self.sprites.len() - self.free_slots.len()
+    }
+
+    /// Returns whether the batch has no sprites in it.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns how many sprites fit into the batch's buffer
+    /// on the graphics card before it has to be recreated.
+    pub fn capacity(&self) -> usize {
+        self.instances.borrow().capacity()
+    }
+
+    /// Returns whether the batch has changed since its data was
+    /// last sent to the graphics card.
+    pub fn is_dirty(&self) -> bool {
+        self.instances.borrow().is_dirty()
+    }
+
+    fn mark_dirty(&mut self) {
+        self.instances.borrow_mut().mark_dirty();
+    }
+
+    /// Immediately sends all data in the batch to the graphics card,
+    /// if it has changed since the last time.
     ///
     /// Generally just calling `graphics::draw()` on the `SpriteBatch`
     /// will do this automatically.
-    pub fn flush(&self, ctx: &mut Context) {
-        for s in &self.sprites {
-            graphics::draw_ex(ctx, &self.image, *s);
+    pub fn flush(&self, ctx: &mut Context) -> GameResult<()> {
+        let gfx = &mut ctx.gfx_context;
+        let instances = &mut *self.instances.borrow_mut();
+        if !instances.needs_upload(gfx.screen_rect) {
+            return Ok(());
         }
+
+        let screen_rect = gfx.screen_rect;
+        let properties: Vec<RectProperties> = self.sprites
+            .iter()
+            .filter_map(|s| s.as_ref())
+            .map(|s| self.image.quad_draw_param(*s, screen_rect).into())
+            .collect();
+        instances.upload(gfx, &properties, self.sprites.capacity())
     }
 
     /// Removes all data from the sprite batch.
     pub fn clear(&mut self) {
-        self.sprites.clear()
+        self.sprites.clear();
+        self.free_slots.clear();
+        self.mark_dirty();
     }
 
     /// Unwraps the contained `Image`
@@ -83,16 +171,19 @@ impl SpriteBatch {
 
     /// Replaces the contained `Image`, returning the old one.
     pub fn set_image(&mut self, image: graphics::Image) -> graphics::Image {
-        use std::mem;
+        self.mark_dirty();
         mem::replace(&mut self.image, image)
     }
 }
 
 impl graphics::Drawable for SpriteBatch {
-    /// Does not properly work yet, ideally the position, scale, etc. of the given
-    /// DrawParam would be added to the DrawParam for each sprite.
-    fn draw_ex(&self, ctx: &mut Context, _param: graphics::DrawParam) -> GameResult<()> {
-        self.flush(ctx);
-        Ok(())
+    /// Draws every sprite in the batch.  The given `DrawParam` is
+    /// applied on top of each sprite's own, as if the whole batch
+    /// was one big image; its color, if any, tints the whole batch.
+    fn draw_ex(&self, ctx: &mut Context, param: graphics::DrawParam) -> GameResult<()> {
+        self.flush(ctx)?;
+        let gfx = &mut ctx.gfx_context;
+        let slice = self.image.bind(gfx);
+        self.instances.borrow().draw(gfx, slice, param)
     }
 }
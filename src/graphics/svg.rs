@@ -0,0 +1,867 @@
+//! Loading simple SVG images as meshes.
+//!
+//! `<path>`, `<rect>`, `<circle>`, `<ellipse>`, `<line>`, `<polyline>`
+//! and `<polygon>` elements are read, in any number of groups, with
+//! their `fill`, `stroke`, `stroke-width`, `fill-rule`, opacity and
+//! `transform` attributes, which may also be given as `style`
+//! properties.  Gradients, patterns, text, images, `<use>`, clipping,
+//! masks and stylesheets are left out.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path;
+use std::str;
+
+use xml::reader::{EventReader, XmlEvent};
+
+use super::*;
+
+/// How close curves are flattened to, in pixels.
+const SVG_TOLERANCE: f32 = 0.1;
+
+/// Elements whose contents aren't drawn where they are.
+const UNDRAWN_ELEMENTS: [&str; 12] = [
+    "clipPath",
+    "defs",
+    "desc",
+    "linearGradient",
+    "marker",
+    "mask",
+    "metadata",
+    "pattern",
+    "radialGradient",
+    "style",
+    "symbol",
+    "title",
+];
+
+/// A shape from an SVG image, with its own transform and those of the
+/// groups it is in already applied to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgShape {
+    /// The outline of the shape, with its fill rule.
+    pub path: Path,
+    /// The color it is filled with, if it is filled.
+    pub fill: Option<Color>,
+    /// The color it is outlined in, if it is outlined.
+    pub stroke: Option<Color>,
+    /// How its outline is drawn.
+    pub line_style: LineStyle,
+}
+
+/// An SVG image, turned into a single `Mesh` of filled and outlined
+/// shapes.
+///
+/// It is drawn with its top left corner at `DrawParam::dest`, at the
+/// size given by the `width` and `height` of its `<svg>` element, in
+/// pixels.  If there is a `viewBox`, it is scaled to fit.
+///
+/// ```rust,ignore
+/// let logo = Svg::new(ctx, "/logo.svg")?;
+/// graphics::draw(ctx, &logo, Point::new(10.0, 10.0), 0.0)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Svg {
+    width: f32,
+    height: f32,
+    shapes: Vec<SvgShape>,
+    mesh: Mesh,
+}
+
+impl Svg {
+    /// Loads an SVG image from a file in the `Filesystem`.
+    pub fn new<P: AsRef<path::Path>>(ctx: &mut Context, path: P) -> GameResult<Svg> {
+        let mut source = String::new();
+        let mut reader = ctx.filesystem.open(path)?;
+        reader.read_to_string(&mut source)?;
+        Svg::from_source(ctx, &source)
+    }
+
+    /// Creates an SVG image from the text of an SVG file.
+    pub fn from_source(ctx: &mut Context, source: &str) -> GameResult<Svg> {
+        let document = parse_svg(source)?;
+        let mut builder = MeshBuilder::new();
+        for shape in &document.shapes {
+            if let Some(fill) = shape.fill {
+                builder.path(DrawMode::Fill, &shape.path, SVG_TOLERANCE, fill);
+            }
+            if let Some(stroke) = shape.stroke {
+                builder
+                    .set_line_style(&shape.line_style)
+                    .path(DrawMode::Line, &shape.path, SVG_TOLERANCE, stroke);
+            }
+        }
+        Ok(Svg {
+            width: document.width,
+            height: document.height,
+            shapes: document.shapes,
+            mesh: builder.build(ctx)?,
+        })
+    }
+
+    /// Returns the width of the image, in pixels.
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// Returns the height of the image, in pixels.
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    /// Returns the shapes in the image, in the order they are drawn.
+    pub fn get_shapes(&self) -> &[SvgShape] {
+        &self.shapes
+    }
+
+    /// Returns the mesh the image is drawn with.
+    pub fn get_mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+}
+
+impl Drawable for Svg {
+    fn draw_ex(&self, ctx: &mut Context, param: DrawParam) -> GameResult<()> {
+        self.mesh.draw_ex(ctx, param)
+    }
+}
+
+fn svg_error<S: AsRef<str>>(msg: S) -> GameError {
+    GameError::ResourceLoadError(format!("Invalid SVG image: {}", msg.as_ref()))
+}
+
+/// Everything in an SVG file that goes into an `Svg`.
+#[derive(Debug)]
+struct SvgDocument {
+    width: f32,
+    height: f32,
+    shapes: Vec<SvgShape>,
+}
+
+fn parse_svg(source: &str) -> GameResult<SvgDocument> {
+    let mut size = None;
+    let mut shapes = Vec::new();
+    // The style of each element we're inside, outermost first.
+    let mut styles = vec![Style::default()];
+    // How deep we are inside an element that isn't drawn.
+    let mut skipping = 0;
+    for event in EventReader::from_str(source) {
+        match event? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let attributes: HashMap<String, String> = attributes
+                    .into_iter()
+                    .map(|a| (a.name.local_name, a.value))
+                    .collect();
+                let name = name.local_name;
+                let undrawn = UNDRAWN_ELEMENTS.contains(&&name[..]) || is_hidden(&attributes);
+                if skipping > 0 || undrawn {
+                    skipping += 1;
+                    continue;
+                }
+                let mut style = styles[styles.len() - 1].apply(&attributes);
+                if name == "svg" && size.is_none() {
+                    let (width, height, view_box) = viewport(&attributes);
+                    size = Some((width, height));
+                    style.transform = style.transform * view_box;
+                }
+                if let Some(path) = shape_path(&name, &attributes) {
+                    shapes.push(style.shape(path));
+                }
+                styles.push(style);
+            }
+            XmlEvent::EndElement { .. } => {
+                if skipping > 0 {
+                    skipping -= 1;
+                } else {
+                    styles.pop();
+                }
+            }
+            _ => (),
+        }
+    }
+    let (width, height) = size.ok_or_else(|| svg_error("No <svg> element"))?;
+    Ok(SvgDocument {
+        width: width,
+        height: height,
+        shapes: shapes,
+    })
+}
+
+/// An element's attributes followed by the properties in its `style`
+/// attribute, which take precedence over them.
+fn properties(attributes: &HashMap<String, String>) -> Vec<(&str, &str)> {
+    let mut properties: Vec<(&str, &str)> = attributes
+        .iter()
+        .map(|(name, value)| (&name[..], value.trim()))
+        .collect();
+    if let Some(style) = attributes.get("style") {
+        for declaration in style.split(';') {
+            let mut parts = declaration.splitn(2, ':');
+            if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                properties.push((name.trim(), value.trim()));
+            }
+        }
+    }
+    properties
+}
+
+fn is_hidden(attributes: &HashMap<String, String>) -> bool {
+    properties(attributes).iter().any(|&property| {
+        property == ("display", "none") || property == ("visibility", "hidden")
+    })
+}
+
+/// The presentation attributes an element passes on to those inside it.
+#[derive(Debug, Clone)]
+struct Style {
+    fill: Option<Color>,
+    stroke: Option<Color>,
+    line_style: LineStyle,
+    fill_rule: FillRule,
+    opacity: f32,
+    fill_opacity: f32,
+    stroke_opacity: f32,
+    transform: Matrix,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            fill: Some(BLACK),
+            stroke: None,
+            line_style: LineStyle::default(),
+            fill_rule: FillRule::NonZero,
+            opacity: 1.0,
+            fill_opacity: 1.0,
+            stroke_opacity: 1.0,
+            transform: Matrix::identity(),
+        }
+    }
+}
+
+impl Style {
+    /// The style of an element with the given attributes, inside an
+    /// element with this style.  Values that can't be read are ignored.
+    fn apply(&self, attributes: &HashMap<String, String>) -> Style {
+        let mut style = self.clone();
+        style.opacity = 1.0;
+        for (name, value) in properties(attributes) {
+            match name {
+                "fill" => if let Some(paint) = parse_paint(value) {
+                    style.fill = paint;
+                },
+                "stroke" => if let Some(paint) = parse_paint(value) {
+                    style.stroke = paint;
+                },
+                "stroke-width" => if let Some(width) = parse_length(value) {
+                    style.line_style.width = width;
+                },
+                "stroke-linecap" => match value {
+                    "butt" => style.line_style.cap = LineCap::Butt,
+                    "round" => style.line_style.cap = LineCap::Round,
+                    "square" => style.line_style.cap = LineCap::Square,
+                    _ => (),
+                },
+                "stroke-linejoin" => match value {
+                    "miter" | "miter-clip" | "arcs" => style.line_style.join = LineJoin::Miter,
+                    "round" => style.line_style.join = LineJoin::Round,
+                    "bevel" => style.line_style.join = LineJoin::Bevel,
+                    _ => (),
+                },
+                "stroke-miterlimit" => if let Ok(limit) = value.parse() {
+                    style.line_style.miter_limit = limit;
+                },
+                "stroke-dasharray" => style.line_style.dashes = parse_numbers(value),
+                "stroke-dashoffset" => if let Some(offset) = parse_length(value) {
+                    style.line_style.dash_offset = offset;
+                },
+                "fill-rule" => match value {
+                    "nonzero" => style.fill_rule = FillRule::NonZero,
+                    "evenodd" => style.fill_rule = FillRule::EvenOdd,
+                    _ => (),
+                },
+                "opacity" => if let Ok(opacity) = value.parse() {
+                    style.opacity = opacity;
+                },
+                "fill-opacity" => if let Ok(opacity) = value.parse() {
+                    style.fill_opacity = opacity;
+                },
+                "stroke-opacity" => if let Ok(opacity) = value.parse() {
+                    style.stroke_opacity = opacity;
+                },
+                "transform" => style.transform = self.transform * parse_transform(value),
+                _ => (),
+            }
+        }
+        // Group opacity really applies to the group as a whole, but
+        // applying it to each shape in it is close enough.
+        style.opacity *= self.opacity;
+        style
+    }
+
+    /// A shape with this style.
+    fn shape(&self, mut path: Path) -> SvgShape {
+        path.transform(&self.transform).set_fill_rule(self.fill_rule);
+        let fade = |color: Color, opacity: f32| {
+            let alpha = color.a * (opacity * self.opacity).max(0.0).min(1.0);
+            Color::new(color.r, color.g, color.b, alpha)
+        };
+        // Outlines and their dashes are as much wider or narrower
+        // as the transform makes shapes bigger or smaller.
+        let m = &self.transform.m;
+        let scale = (m[0][0] * m[1][1] - m[0][1] * m[1][0]).abs().sqrt();
+        let line_style = LineStyle {
+            width: self.line_style.width * scale,
+            dashes: self.line_style.dashes.iter().map(|d| d * scale).collect(),
+            dash_offset: self.line_style.dash_offset * scale,
+            ..self.line_style.clone()
+        };
+        SvgShape {
+            path: path,
+            fill: self.fill.map(|c| fade(c, self.fill_opacity)),
+            stroke: self.stroke.map(|c| fade(c, self.stroke_opacity)),
+            line_style: line_style,
+        }
+    }
+}
+
+/// The size of the root `<svg>` element, and the transform that
+/// scales its `viewBox` to fit.
+fn viewport(attributes: &HashMap<String, String>) -> (f32, f32, Matrix) {
+    let width = attributes.get("width").and_then(|w| parse_length(w));
+    let height = attributes.get("height").and_then(|h| parse_length(h));
+    let view_box = attributes
+        .get("viewBox")
+        .map(|v| parse_numbers(v))
+        .unwrap_or_default();
+    if view_box.len() != 4 || view_box[2] <= 0.0 || view_box[3] <= 0.0 {
+        return (width.unwrap_or(0.0), height.unwrap_or(0.0), Matrix::identity());
+    }
+    let (x, y, w, h) = (view_box[0], view_box[1], view_box[2], view_box[3]);
+    let (width, height) = (width.unwrap_or(w), height.unwrap_or(h));
+    let (mut scale_x, mut scale_y) = (width / w, height / h);
+    let (mut offset_x, mut offset_y) = (0.0, 0.0);
+    let stretch = attributes
+        .get("preserveAspectRatio")
+        .map_or(false, |p| p.trim() == "none");
+    if !stretch {
+        // The default is to keep the aspect ratio and center it.
+        let scale = scale_x.min(scale_y);
+        scale_x = scale;
+        scale_y = scale;
+        offset_x = (width - w * scale) / 2.0;
+        offset_y = (height - h * scale) / 2.0;
+    }
+    let transform = Matrix::translation(offset_x, offset_y) * Matrix::scale(scale_x, scale_y)
+        * Matrix::translation(-x, -y);
+    (width, height, transform)
+}
+
+/// The outline of a basic shape or `<path>` element.
+fn shape_path(name: &str, attributes: &HashMap<String, String>) -> Option<Path> {
+    let length = |name: &str| attributes.get(name).and_then(|v| parse_length(v));
+    let value = |name: &str| length(name).unwrap_or(0.0);
+    let mut path = Path::new();
+    match name {
+        "path" => return attributes.get("d").map(|d| parse_path_data(d)),
+        "rect" => {
+            let (x, y, w, h) = (value("x"), value("y"), value("width"), value("height"));
+            if w <= 0.0 || h <= 0.0 {
+                return None;
+            }
+            let (rx, ry) = match (length("rx"), length("ry")) {
+                (Some(rx), Some(ry)) => (rx, ry),
+                (Some(r), None) | (None, Some(r)) => (r, r),
+                (None, None) => (0.0, 0.0),
+            };
+            let (rx, ry) = (rx.max(0.0).min(w / 2.0), ry.max(0.0).min(h / 2.0));
+            let radii = Point::new(rx, ry);
+            path.move_to(Point::new(x + rx, y))
+                .line_to(Point::new(x + w - rx, y))
+                .arc_to(radii, 0.0, false, true, Point::new(x + w, y + ry))
+                .line_to(Point::new(x + w, y + h - ry))
+                .arc_to(radii, 0.0, false, true, Point::new(x + w - rx, y + h))
+                .line_to(Point::new(x + rx, y + h))
+                .arc_to(radii, 0.0, false, true, Point::new(x, y + h - ry))
+                .line_to(Point::new(x, y + ry))
+                .arc_to(radii, 0.0, false, true, Point::new(x + rx, y))
+                .close();
+        }
+        "circle" | "ellipse" => {
+            let center = Point::new(value("cx"), value("cy"));
+            let radii = if name == "circle" {
+                Point::new(value("r"), value("r"))
+            } else {
+                Point::new(value("rx"), value("ry"))
+            };
+            if radii.x <= 0.0 || radii.y <= 0.0 {
+                return None;
+            }
+            path.move_to(Point::new(center.x + radii.x, center.y))
+                .arc_to(radii, 0.0, false, true, Point::new(center.x - radii.x, center.y))
+                .arc_to(radii, 0.0, false, true, Point::new(center.x + radii.x, center.y))
+                .close();
+        }
+        "line" => {
+            path.move_to(Point::new(value("x1"), value("y1")))
+                .line_to(Point::new(value("x2"), value("y2")));
+        }
+        "polyline" | "polygon" => {
+            let numbers = parse_numbers(attributes.get("points").map_or("", |p| &p[..]));
+            let mut points = numbers.chunks(2).filter(|c| c.len() == 2);
+            let first = points.next()?;
+            path.move_to(Point::new(first[0], first[1]));
+            for point in points {
+                path.line_to(Point::new(point[0], point[1]));
+            }
+            if name == "polygon" {
+                path.close();
+            }
+        }
+        _ => return None,
+    }
+    Some(path)
+}
+
+/// Reads the numbers, flags and commands of SVG path data and
+/// other lists of numbers.
+#[derive(Debug)]
+struct Tokens<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(source: &'a str) -> Tokens<'a> {
+        Tokens {
+            bytes: source.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self.pos < self.bytes.len()
+            && (self.bytes[self.pos].is_ascii_whitespace() || self.bytes[self.pos] == b',')
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_separators();
+        self.pos >= self.bytes.len()
+    }
+
+    /// The rest of the source, with nothing skipped.
+    fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.pos..]
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        match self.rest().first() {
+            Some(&c) if c.is_ascii_alphabetic() => {
+                self.pos += 1;
+                Some(c)
+            }
+            _ => None,
+        }
+    }
+
+    fn number(&mut self) -> Option<f32> {
+        self.skip_separators();
+        let bytes = self.rest();
+        let digits = |from: usize| {
+            bytes[from..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .count()
+        };
+        let mut end = 0;
+        if end < bytes.len() && (bytes[end] == b'+' || bytes[end] == b'-') {
+            end += 1;
+        }
+        let whole = digits(end);
+        end += whole;
+        let mut fraction = 0;
+        if end < bytes.len() && bytes[end] == b'.' {
+            fraction = digits(end + 1);
+            end += 1 + fraction;
+        }
+        if whole + fraction == 0 {
+            return None;
+        }
+        if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+            let mut exponent = end + 1;
+            if exponent < bytes.len() && (bytes[exponent] == b'+' || bytes[exponent] == b'-') {
+                exponent += 1;
+            }
+            let exponent_digits = digits(exponent);
+            if exponent_digits > 0 {
+                end = exponent + exponent_digits;
+            }
+        }
+        let number = str::from_utf8(&bytes[..end]).ok()?.parse().ok()?;
+        self.pos += end;
+        Some(number)
+    }
+
+    /// An arc flag, which needn't be followed by a separator.
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        let flag = match self.rest().first() {
+            Some(&b'0') => false,
+            Some(&b'1') => true,
+            _ => return None,
+        };
+        self.pos += 1;
+        Some(flag)
+    }
+
+    fn point(&mut self) -> Option<Point> {
+        let x = self.number()?;
+        let y = self.number()?;
+        Some(Point::new(x, y))
+    }
+}
+
+fn parse_numbers(source: &str) -> Vec<f32> {
+    let mut tokens = Tokens::new(source);
+    let mut numbers = Vec::new();
+    while let Some(number) = tokens.number() {
+        numbers.push(number);
+    }
+    numbers
+}
+
+/// A length in pixels.  Percentages and font-relative units
+/// aren't supported.
+fn parse_length(source: &str) -> Option<f32> {
+    let mut tokens = Tokens::new(source.trim());
+    let number = tokens.number()?;
+    let scale = match str::from_utf8(tokens.rest()).ok()?.trim() {
+        "" | "px" => 1.0,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        "mm" => 96.0 / 25.4,
+        "cm" => 96.0 / 2.54,
+        "in" => 96.0,
+        _ => return None,
+    };
+    Some(number * scale)
+}
+
+/// Reads path data, the `d` attribute of a `<path>`.  As the SVG
+/// specification says, everything up to the first error is kept.
+fn parse_path_data(source: &str) -> Path {
+    let mut path = Path::new();
+    let mut tokens = Tokens::new(source);
+    let mut command = None;
+    // The kind of the last curve and its last control point,
+    // which `S` and `T` curves reflect.
+    let mut last_control = None;
+    while !tokens.at_end() {
+        if let Some(c) = tokens.command() {
+            command = Some(c);
+        }
+        let c = match command {
+            Some(c) => c,
+            None => break,
+        };
+        match path_segment(&mut path, &mut tokens, c, last_control) {
+            Some(control) => last_control = control,
+            None => break,
+        }
+        // Coordinates after a move without a command are lines.
+        command = match c {
+            b'M' => Some(b'L'),
+            b'm' => Some(b'l'),
+            b'Z' | b'z' => None,
+            c => Some(c),
+        };
+    }
+    path
+}
+
+/// Adds one segment of path data to the path, returning the
+/// control point for a following `S` or `T` curve, if any.
+fn path_segment(
+    path: &mut Path,
+    tokens: &mut Tokens,
+    command: u8,
+    last_control: Option<(u8, Point)>,
+) -> Option<Option<(u8, Point)>> {
+    let current = path.get_current_point();
+    let relative = command.is_ascii_lowercase();
+    let offset = |p: Point| if relative {
+        Point::new(current.x + p.x, current.y + p.y)
+    } else {
+        p
+    };
+    let reflect = |kind: u8| match last_control {
+        Some((k, p)) if k == kind => Point::new(2.0 * current.x - p.x, 2.0 * current.y - p.y),
+        _ => current,
+    };
+    let mut control = None;
+    match command.to_ascii_uppercase() {
+        b'M' => {
+            path.move_to(offset(tokens.point()?));
+        }
+        b'L' => {
+            path.line_to(offset(tokens.point()?));
+        }
+        b'H' => {
+            let x = tokens.number()?;
+            let x = if relative { current.x + x } else { x };
+            path.line_to(Point::new(x, current.y));
+        }
+        b'V' => {
+            let y = tokens.number()?;
+            let y = if relative { current.y + y } else { y };
+            path.line_to(Point::new(current.x, y));
+        }
+        b'C' | b'S' => {
+            let control1 = if command.to_ascii_uppercase() == b'C' {
+                offset(tokens.point()?)
+            } else {
+                reflect(b'C')
+            };
+            let control2 = offset(tokens.point()?);
+            let to = offset(tokens.point()?);
+            path.cubic_to(control1, control2, to);
+            control = Some((b'C', control2));
+        }
+        b'Q' | b'T' => {
+            let control1 = if command.to_ascii_uppercase() == b'Q' {
+                offset(tokens.point()?)
+            } else {
+                reflect(b'Q')
+            };
+            let to = offset(tokens.point()?);
+            path.quad_to(control1, to);
+            control = Some((b'Q', control1));
+        }
+        b'A' => {
+            let radii = Point::new(tokens.number()?, tokens.number()?);
+            let x_rotation = tokens.number()?.to_radians();
+            let large_arc = tokens.flag()?;
+            let sweep = tokens.flag()?;
+            let to = offset(tokens.point()?);
+            path.arc_to(radii, x_rotation, large_arc, sweep, to);
+        }
+        b'Z' => {
+            path.close();
+        }
+        _ => return None,
+    }
+    Some(control)
+}
+
+/// Reads a `transform` attribute.  A transform that can't be read
+/// is ignored altogether.
+fn parse_transform(source: &str) -> Matrix {
+    let mut matrix = Matrix::identity();
+    for part in source.split(')') {
+        let mut halves = part.splitn(2, '(');
+        let name = halves
+            .next()
+            .unwrap_or("")
+            .trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let args = match halves.next() {
+            Some(args) => parse_numbers(args),
+            None if name.is_empty() => continue,
+            None => return Matrix::identity(),
+        };
+        let a = |i: usize| args[i];
+        let next = match (name, args.len()) {
+            ("matrix", 6) => Matrix::affine(a(0), a(2), a(1), a(3), a(4), a(5)),
+            ("translate", 1) => Matrix::translation(a(0), 0.0),
+            ("translate", 2) => Matrix::translation(a(0), a(1)),
+            ("scale", 1) => Matrix::scale(a(0), a(0)),
+            ("scale", 2) => Matrix::scale(a(0), a(1)),
+            ("rotate", 1) => Matrix::rotation(a(0).to_radians()),
+            ("rotate", 3) => {
+                Matrix::translation(a(1), a(2)) * Matrix::rotation(a(0).to_radians())
+                    * Matrix::translation(-a(1), -a(2))
+            }
+            ("skewX", 1) => Matrix::affine(1.0, a(0).to_radians().tan(), 0.0, 1.0, 0.0, 0.0),
+            ("skewY", 1) => Matrix::affine(1.0, 0.0, a(0).to_radians().tan(), 1.0, 0.0, 0.0),
+            _ => return Matrix::identity(),
+        };
+        matrix = matrix * next;
+    }
+    matrix
+}
+
+/// Reads a `fill` or `stroke`: `Some(None)` for `none`, `None`
+/// if it can't be read.  Gradients and patterns are drawn as their
+/// fallback color, if they have one, or not at all.
+fn parse_paint(source: &str) -> Option<Option<Color>> {
+    let source = source.trim();
+    if source == "none" {
+        return Some(None);
+    }
+    if source.starts_with("url(") {
+        let fallback = source.splitn(2, ')').nth(1).unwrap_or("").trim();
+        return if fallback.is_empty() {
+            Some(None)
+        } else {
+            parse_paint(fallback)
+        };
+    }
+    parse_color(source).map(Some)
+}
+
+/// Reads a `#rgb`, `#rrggbb`, `rgb()` or named color.  Only the
+/// basic color keywords are known.
+fn parse_color(source: &str) -> Option<Color> {
+    let source = source.trim();
+    if source.starts_with('#') {
+        let digits: Vec<u8> = source[1..]
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()?;
+        return match digits.len() {
+            3 => Some(Color::from((digits[0] * 17, digits[1] * 17, digits[2] * 17))),
+            6 => Some(Color::from((
+                digits[0] * 16 + digits[1],
+                digits[2] * 16 + digits[3],
+                digits[4] * 16 + digits[5],
+            ))),
+            _ => None,
+        };
+    }
+    if source.starts_with("rgb(") && source.ends_with(')') {
+        let args: Vec<&str> = source[4..source.len() - 1].split(',').map(str::trim).collect();
+        if args.len() != 3 {
+            return None;
+        }
+        let mut rgb = [0.0; 3];
+        for (channel, arg) in rgb.iter_mut().zip(args) {
+            let value = if arg.ends_with('%') {
+                arg[..arg.len() - 1].parse::<f32>().ok()? / 100.0
+            } else {
+                arg.parse::<f32>().ok()? / 255.0
+            };
+            *channel = value.max(0.0).min(1.0);
+        }
+        return Some(Color::new(rgb[0], rgb[1], rgb[2], 1.0));
+    }
+    let rgb: (u8, u8, u8) = match &source.to_lowercase()[..] {
+        "black" => (0, 0, 0),
+        "silver" => (192, 192, 192),
+        "gray" | "grey" => (128, 128, 128),
+        "white" => (255, 255, 255),
+        "maroon" => (128, 0, 0),
+        "red" => (255, 0, 0),
+        "purple" => (128, 0, 128),
+        "fuchsia" | "magenta" => (255, 0, 255),
+        "green" => (0, 128, 0),
+        "lime" => (0, 255, 0),
+        "olive" => (128, 128, 0),
+        "yellow" => (255, 255, 0),
+        "navy" => (0, 0, 128),
+        "blue" => (0, 0, 255),
+        "teal" => (0, 128, 128),
+        "aqua" | "cyan" => (0, 255, 255),
+        "orange" => (255, 165, 0),
+        "transparent" => return Some(Color::new(0.0, 0.0, 0.0, 0.0)),
+        _ => return None,
+    };
+    Some(Color::from(rgb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_path_data() {
+        // Implicit lines after a move, relative and horizontal and
+        // vertical lines, a reflected curve and numbers run together.
+        let path = parse_path_data("M10,10 20 10 v10 h-10z m5-5 c0 5 5 5 5 0s5-5 5 0l.5.5 X 1 1");
+        let mut expected = Path::new();
+        expected
+            .move_to(Point::new(10.0, 10.0))
+            .line_to(Point::new(20.0, 10.0))
+            .line_to(Point::new(20.0, 20.0))
+            .line_to(Point::new(10.0, 20.0))
+            .close()
+            .move_to(Point::new(15.0, 5.0))
+            .cubic_to(Point::new(15.0, 10.0), Point::new(20.0, 10.0), Point::new(20.0, 5.0))
+            .cubic_to(Point::new(20.0, 0.0), Point::new(25.0, 0.0), Point::new(25.0, 5.0))
+            .line_to(Point::new(25.5, 5.5));
+        assert_eq!(path, expected);
+
+        // Arc flags don't need separators.
+        let arc = parse_path_data("M0 0a10 10 0 0110 10");
+        let mut expected = Path::new();
+        expected
+            .move_to(Point::new(0.0, 0.0))
+            .arc_to(Point::new(10.0, 10.0), 0.0, false, true, Point::new(10.0, 10.0));
+        assert_eq!(arc, expected);
+
+        assert_eq!(parse_numbers("1e2,-3.5-.5 2"), vec![100.0, -3.5, -0.5, 2.0]);
+        assert_eq!(parse_length(" 2in "), Some(192.0));
+        assert_eq!(parse_length("50%"), None);
+    }
+
+    #[test]
+    fn test_parse_style() {
+        assert_eq!(parse_color("#f80"), Some(Color::from((255, 136, 0))));
+        assert_eq!(parse_color("#00ff7F"), Some(Color::from((0, 255, 127))));
+        assert_eq!(parse_color("rgb(255, 0, 50%)"), Some(Color::new(1.0, 0.0, 0.5, 1.0)));
+        assert_eq!(parse_color("Navy"), Some(Color::from((0, 0, 128))));
+        assert_eq!(parse_color("#12"), None);
+        assert_eq!(parse_paint("none"), Some(None));
+        assert_eq!(parse_paint("url(#gradient) red"), Some(Some(Color::from((255, 0, 0)))));
+        assert_eq!(parse_paint("bogus"), None);
+
+        let transform = parse_transform("translate(10, 20) scale(2)");
+        assert_eq!(transform.transform_point(Point::new(1.0, 1.0)), Point::new(12.0, 22.0));
+        let rotate = parse_transform("rotate(90 10 10)");
+        let p = rotate.transform_point(Point::new(20.0, 10.0));
+        assert!((p.x - 10.0).abs() < 0.001 && (p.y - 20.0).abs() < 0.001);
+        assert_eq!(parse_transform("scale(2) nonsense(1)"), Matrix::identity());
+    }
+
+    #[test]
+    fn test_parse_svg() {
+        let source = r##"<?xml version="1.0"?>
+            <svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" viewBox="0 0 100 50">
+              <defs><rect width="5" height="5"/></defs>
+              <g fill="red" stroke="#00f" style="stroke-width: 2; stroke-linecap: round"
+                 stroke-dasharray="1 3" transform="translate(10 0)">
+                <rect x="0" y="0" width="10" height="10" fill-opacity="0.5"
+                      stroke-linejoin="bevel" stroke-miterlimit="2"/>
+                <circle cx="20" cy="20" r="5" fill="none" fill-rule="evenodd"/>
+                <path d="M0 0 L 10 10" style="display:none"/>
+              </g>
+              <polygon points="0,0 10,0 10,10"/>
+            </svg>"##;
+        let document = parse_svg(source).unwrap();
+        assert_eq!((document.width, document.height), (200.0, 100.0));
+        assert_eq!(document.shapes.len(), 3);
+
+        let rect = &document.shapes[0];
+        assert_eq!(rect.fill, Some(Color::new(1.0, 0.0, 0.0, 0.5)));
+        assert_eq!(rect.stroke, Some(Color::from((0, 0, 255))));
+        // The viewBox doubles everything, including outlines.
+        assert_eq!(rect.line_style.width, 4.0);
+        assert_eq!(rect.line_style.dashes, vec![2.0, 6.0]);
+        assert_eq!(rect.line_style.cap, LineCap::Round);
+        assert_eq!(rect.line_style.join, LineJoin::Bevel);
+        assert_eq!(rect.line_style.miter_limit, 2.0);
+        assert_eq!(rect.path.get_current_point(), Point::new(20.0, 0.0));
+
+        let circle = &document.shapes[1];
+        assert_eq!(circle.fill, None);
+        assert_eq!(circle.path.get_fill_rule(), FillRule::EvenOdd);
+
+        let polygon = &document.shapes[2];
+        assert_eq!(polygon.fill, Some(BLACK));
+        assert_eq!(polygon.stroke, None);
+    }
+}
@@ -1,21 +1,34 @@
+use std::cell::{Cell, RefCell};
 use std::fmt;
 use std::path;
 use std::collections::BTreeMap;
 use std::io::Read;
+use std::iter;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
 
 use rusttype;
 use image;
 
 use super::*;
 
+/// A unique identifier for a loaded TTF font, used by the glyph cache
+/// to tell fonts apart.  Clones of a `Font` share the same ID.
+pub type FontId = usize;
+
+static NEXT_FONT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
 /// A font that defines the shape of characters drawn on the screen.
 /// Can be created from a .ttf file or from an image (bitmap fonts).
 #[derive(Clone)]
 pub enum Font {
     TTFFont {
+        id: FontId,
         font: rusttype::Font<'static>,
         points: u32,
         scale: rusttype::Scale,
+        // Fonts to look in for characters this one doesn't have,
+        // in order.  See `Font::add_fallback()`.
+        fallbacks: Vec<(FontId, rusttype::Font<'static>)>,
     },
     BitmapFont {
         // Width, height and data for the original glyph image.
@@ -27,6 +40,43 @@ pub enum Font {
         glyphs: BTreeMap<char, usize>,
         glyph_width: usize,
     },
+    /// A bitmap font with glyphs of varying sizes, which may be
+    /// spread over several images, such as an AngelCode BMFont.
+    BMFont { data: BMFontData },
+}
+
+/// The vertical metrics of a `Font`, in pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FontMetrics {
+    /// The distance from the baseline to the top of the highest glyph.
+    pub ascent: f32,
+    /// The distance from the baseline to the bottom of the lowest
+    /// glyph.  This is usually negative.
+    pub descent: f32,
+    /// The extra space the font wants between the descent of one
+    /// line and the ascent of the next.
+    pub line_gap: f32,
+}
+
+impl FontMetrics {
+    /// Returns the distance from one baseline to the next.
+    pub fn line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+}
+
+/// The metrics of a single character of a `Font`, in pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GlyphMetrics {
+    /// How far to move along the line after drawing the character.
+    pub advance: f32,
+    /// The distance from the current position to the left edge
+    /// of the glyph.
+    pub left_side_bearing: f32,
+    /// The bounding box of the glyph, relative to the current position
+    /// on the baseline, with y pointing down.  `None` for glyphs that
+    /// draw nothing, such as spaces.
+    pub bounds: Option<Rect>,
 }
 
 impl Font {
@@ -62,9 +112,11 @@ impl Font {
         let scale = display_independent_scale(points, x_dpi, y_dpi);
 
         Ok(Font::TTFFont {
+            id: NEXT_FONT_ID.fetch_add(1, Ordering::Relaxed),
             font: font,
             points: points,
             scale: scale,
+            fallbacks: Vec::new(),
         })
     }
 
@@ -85,7 +137,7 @@ impl Font {
         };
         let (image_width, image_height) = img.dimensions();
 
-        let glyph_width = (image_width as usize) / glyphs.len();
+        let glyph_width = (image_width as usize) / glyphs.chars().count();
         // println!("Number of glyphs: {}, Glyph width: {}, image width: {}",
         // glyphs.len(), glyph_width, image_width);
         let mut glyphs_map: BTreeMap<char, usize> = BTreeMap::new();
@@ -101,6 +153,29 @@ impl Font {
         })
     }
 
+    /// Loads an AngelCode BMFont `.fnt` file, in either the text or the
+    /// binary format, along with the images it refers to, which are
+    /// looked for relative to the `.fnt` file.
+    pub fn new_bmfont<P: AsRef<path::Path>>(context: &mut Context, path: P) -> GameResult<Font> {
+        let data = BMFontData::load(context, path)?;
+        Ok(Font::BMFont { data: data })
+    }
+
+    /// Loads an `Image` of glyphs laid out in a grid of cells of the
+    /// given size, and uses it to create a new bitmap font.
+    /// The `glyphs` string is the characters in the image from left
+    /// to right, then top to bottom.
+    pub fn new_bitmap_grid<P: AsRef<path::Path>>(
+        context: &mut Context,
+        path: P,
+        glyphs: &str,
+        glyph_width: u32,
+        glyph_height: u32,
+    ) -> GameResult<Font> {
+        let data = BMFontData::load_grid(context, path, glyphs, glyph_width, glyph_height)?;
+        Ok(Font::BMFont { data: data })
+    }
+
     /// Returns a baked-in default font: currently DejaVuSerif.ttf
     /// Note it does create a new `Font` object with every call.
     pub fn default_font() -> GameResult<Self> {
@@ -121,6 +196,7 @@ impl Font {
     pub fn get_height(&self) -> usize {
         match *self {
             Font::BitmapFont { height, .. } => height,
+            Font::BMFont { ref data } => data.line_height as usize,
             Font::TTFFont { scale, .. } => {
                 // let v_metrics = font.v_metrics(scale);
                 // v_metrics.
@@ -137,19 +213,169 @@ impl Font {
     /// Does not handle line-breaks.
     pub fn get_width(&self, text: &str) -> usize {
         match *self {
-            Font::BitmapFont { width, .. } => width * text.len(),
+            Font::BitmapFont { glyph_width, .. } => glyph_width * text.chars().count(),
+            Font::BMFont { ref data } => data.get_width(text),
             Font::TTFFont {
-                ref font, scale, ..
+                id,
+                ref font,
+                ref fallbacks,
+                scale,
+                ..
             } => {
                 let v_metrics = font.v_metrics(scale);
                 let offset = rusttype::point(0.0, v_metrics.ascent);
                 let glyphs: Vec<rusttype::PositionedGlyph> =
-                    font.layout(text, scale, offset).collect();
+                    layout_ttf(id, font, fallbacks, text, scale, offset)
+                        .into_iter()
+                        .map(|(_, g)| g)
+                        .collect();
                 text_width(&glyphs) as usize
             }
         }
     }
 
+    /// Returns the vertical metrics of the font, in pixels.
+    pub fn get_metrics(&self) -> FontMetrics {
+        match *self {
+            Font::TTFFont {
+                ref font, scale, ..
+            } => {
+                let v_metrics = font.v_metrics(scale);
+                FontMetrics {
+                    ascent: v_metrics.ascent,
+                    descent: v_metrics.descent,
+                    line_gap: v_metrics.line_gap,
+                }
+            }
+            Font::BitmapFont { height, .. } => FontMetrics {
+                ascent: height as f32,
+                descent: 0.0,
+                line_gap: 0.0,
+            },
+            Font::BMFont { ref data } => FontMetrics {
+                ascent: data.base as f32,
+                descent: data.base as f32 - data.line_height as f32,
+                line_gap: 0.0,
+            },
+        }
+    }
+
+    /// Returns the metrics of a single character, in pixels, or `None`
+    /// if neither the font nor any of its fallbacks have it.
+    pub fn get_glyph_metrics(&self, c: char) -> Option<GlyphMetrics> {
+        match *self {
+            Font::TTFFont {
+                id,
+                ref font,
+                ref fallbacks,
+                scale,
+                ..
+            } => {
+                let (_, _, glyph) = find_glyph(id, font, fallbacks, c)?;
+                if glyph.id().0 == 0 {
+                    return None;
+                }
+                let glyph = glyph.scaled(scale);
+                let h_metrics = glyph.h_metrics();
+                let bounds = glyph.exact_bounding_box().map(|bb| {
+                    rect_from_corner(bb.min.x, bb.min.y, bb.max.x - bb.min.x, bb.max.y - bb.min.y)
+                });
+                Some(GlyphMetrics {
+                    advance: h_metrics.advance_width,
+                    left_side_bearing: h_metrics.left_side_bearing,
+                    bounds: bounds,
+                })
+            }
+            Font::BitmapFont {
+                height,
+                ref glyphs,
+                glyph_width,
+                ..
+            } => glyphs.get(&c).map(|_| GlyphMetrics {
+                advance: glyph_width as f32,
+                left_side_bearing: 0.0,
+                bounds: Some(rect_from_corner(
+                    0.0,
+                    -(height as f32),
+                    glyph_width as f32,
+                    height as f32,
+                )),
+            }),
+            Font::BMFont { ref data } => data.chars.get(&c).map(|ch| GlyphMetrics {
+                advance: ch.xadvance as f32,
+                left_side_bearing: ch.xoffset as f32,
+                bounds: Some(rect_from_corner(
+                    ch.xoffset as f32,
+                    (ch.yoffset - data.base as i32) as f32,
+                    ch.width as f32,
+                    ch.height as f32,
+                )),
+            }),
+        }
+    }
+
+    /// Returns the kerning between two characters, in pixels: how much
+    /// to move the second one along the line on top of the first one's
+    /// advance.  This is usually zero or negative.
+    pub fn get_kerning(&self, first: char, second: char) -> f32 {
+        match *self {
+            Font::TTFFont {
+                id,
+                ref font,
+                ref fallbacks,
+                scale,
+                ..
+            } => {
+                let first = find_glyph(id, font, fallbacks, first);
+                let second = find_glyph(id, font, fallbacks, second);
+                match (first, second) {
+                    // Kerning only applies between glyphs of the same font.
+                    (Some((a_id, f, a)), Some((b_id, _, b))) if a_id == b_id => {
+                        f.pair_kerning(scale, a.id(), b.id())
+                    }
+                    _ => 0.0,
+                }
+            }
+            Font::BitmapFont { .. } => 0.0,
+            Font::BMFont { ref data } => data.get_kerning(first, second) as f32,
+        }
+    }
+
+    /// Adds a font to look in for characters this one doesn't have,
+    /// such as CJK characters or emoji missing from the default font.
+    /// Fallbacks are tried in the order they were added, and are drawn
+    /// at this font's size.  Any fallbacks of `fallback` itself are
+    /// added after it.
+    ///
+    /// Only TTF fonts can have or be fallbacks.
+    pub fn add_fallback(&mut self, fallback: &Font) -> GameResult<()> {
+        let (fallback_id, fallback_font, fallback_fallbacks) = match *fallback {
+            Font::TTFFont {
+                id,
+                ref font,
+                ref fallbacks,
+                ..
+            } => (id, font, fallbacks),
+            _ => {
+                return Err(GameError::FontError(
+                    "Only TTF fonts can be used as fallbacks".to_owned(),
+                ))
+            }
+        };
+        match *self {
+            Font::TTFFont {
+                ref mut fallbacks, ..
+            } => {
+                fallbacks.push((fallback_id, fallback_font.clone()));
+                fallbacks.extend(fallback_fallbacks.iter().cloned());
+                Ok(())
+            }
+            _ => Err(GameError::FontError(
+                "Only TTF fonts can have fallbacks".to_owned(),
+            )),
+        }
+    }
+
     /// Breaks the given text into lines that will not exceed `wrap_limit` pixels
     /// in length.  It accounts for newlines correctly but does not
     /// try to break words or handle hypenated words; it just breaks
@@ -204,20 +430,61 @@ impl Font {
     }
 }
 
+impl Font {
+    /// Returns a copy of the font that rasterizes `factor` times
+    /// bigger.  Bitmap fonts stay the same size.
+    fn scaled_by(&self, factor: f32) -> Font {
+        let mut font = self.clone();
+        if let Font::TTFFont { ref mut scale, .. } = font {
+            scale.x *= factor;
+            scale.y *= factor;
+        }
+        font
+    }
+}
+
 impl fmt::Debug for Font {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Font::TTFFont { .. } => write!(f, "<TTFFont: {:p}>", &self),
             Font::BitmapFont { .. } => write!(f, "<BitmapFont: {:p}>", &self),
+            Font::BMFont { .. } => write!(f, "<BMFont: {:p}>", &self),
         }
     }
 }
 
-/// Drawable text created from a `Font`.
+/// Drawable text created from a `Font`, or from several
+/// `TextFragment`s with a `TextLayout`.
+///
+/// Text in TTF fonts is rasterized at the DPI scale (see
+/// `graphics::get_dpi_scale()`), and again the next time it is
+/// drawn after that changes.
 #[derive(Clone)]
 pub struct Text {
-    texture: Image,
+    texture: RefCell<Image>,
+    /// The DPI scale `texture` was rasterized at.
+    dpi_scale: Cell<f32>,
+    /// What to rasterize again when the DPI scale changes.  Bitmap
+    /// fonts look the same at any scale, so they have none.
+    source: Option<TextSource>,
+    width: u32,
+    height: u32,
     contents: String,
+    lines: Vec<Rect>,
+    glyphs: Vec<Rect>,
+}
+
+/// What a `Text` was created from.
+#[derive(Debug, Clone)]
+enum TextSource {
+    Font(Font),
+    Layout(TextLayout),
+}
+
+/// Returns a `Rect` given its top-left corner and size, since
+/// ggez `Rect`s are positioned by their center.
+fn rect_from_corner(left: f32, top: f32, w: f32, h: f32) -> Rect {
+    Rect::new(left + w / 2.0, top + h / 2.0, w, h)
 }
 
 /// Compute a scale for a font of a given size.
@@ -237,6 +504,62 @@ fn display_independent_scale(points: u32, dpi_w: f32, dpi_h: f32) -> rusttype::S
     }
 }
 
+/// Finds the glyph for a character in a TTF font, or if it doesn't have
+/// one, in the first of its fallbacks that does.  The ID of the font the
+/// glyph came from is returned with it, since kerning only applies
+/// between glyphs of the same font.  Characters that none of the fonts
+/// have come out as the first font's missing-character glyph.
+fn find_glyph<'a>(
+    id: FontId,
+    font: &'a rusttype::Font<'static>,
+    fallbacks: &'a [(FontId, rusttype::Font<'static>)],
+    c: char,
+) -> Option<(FontId, &'a rusttype::Font<'static>, rusttype::Glyph<'a>)> {
+    let fonts = iter::once((id, font)).chain(fallbacks.iter().map(|&(id, ref f)| (id, f)));
+    for (font_id, f) in fonts {
+        if let Some(glyph) = f.glyph(c) {
+            // Glyph 0 is the font's missing-character glyph.
+            if glyph.id().0 != 0 {
+                return Some((font_id, f, glyph));
+            }
+        }
+    }
+    font.glyph(c).map(|glyph| (id, font, glyph))
+}
+
+/// Lays out a line of text in a TTF font and its fallbacks, starting at
+/// the given point on the baseline and kerning between glyphs.  Each
+/// glyph comes with the ID of the font it's from.
+fn layout_ttf<'a>(
+    id: FontId,
+    font: &'a rusttype::Font<'static>,
+    fallbacks: &'a [(FontId, rusttype::Font<'static>)],
+    text: &str,
+    scale: rusttype::Scale,
+    start: rusttype::Point<f32>,
+) -> Vec<(FontId, rusttype::PositionedGlyph<'a>)> {
+    let mut glyphs = Vec::with_capacity(text.len());
+    let mut caret = start;
+    let mut previous = None;
+    for c in text.chars() {
+        let (font_id, f, glyph) = match find_glyph(id, font, fallbacks, c) {
+            Some(found) => found,
+            None => continue,
+        };
+        let glyph = glyph.scaled(scale);
+        if let Some((previous_id, previous_glyph)) = previous {
+            if previous_id == font_id {
+                caret.x += f.pair_kerning(scale, previous_glyph, glyph.id());
+            }
+        }
+        previous = Some((font_id, glyph.id()));
+        let advance = glyph.h_metrics().advance_width;
+        glyphs.push((font_id, glyph.positioned(caret)));
+        caret.x += advance;
+    }
+    glyphs
+}
+
 fn text_width(glyphs: &[rusttype::PositionedGlyph]) -> f32 {
     glyphs
         .iter()
@@ -250,10 +573,61 @@ fn text_width(glyphs: &[rusttype::PositionedGlyph]) -> f32 {
         .unwrap_or(0.0)
 }
 
+/// Lays out the given text and queues it to be drawn by the next call
+/// to `draw_queued_text()`.  `dest` is the top-left corner of the
+/// text, which is drawn in the given color or white.
+///
+/// Only TTF fonts can be queued.
+pub fn queue_text(
+    ctx: &mut Context,
+    text: &str,
+    font: &Font,
+    dest: Point,
+    color: Option<Color>,
+) -> GameResult<()> {
+    let (font_id, font, fallbacks, scale) = match *font {
+        Font::TTFFont {
+            id,
+            ref font,
+            ref fallbacks,
+            scale,
+            ..
+        } => (id, font, fallbacks, scale),
+        _ => {
+            return Err(GameError::FontError(
+                "Only TTF fonts can be queued with queue_text()".to_owned(),
+            ))
+        }
+    };
+    let color = color.unwrap_or(WHITE);
+    let v_metrics = font.v_metrics(scale);
+    let line_height = scale.y.ceil();
+    // Lay the text out at the DPI scale, so it's rasterized
+    // with as many pixels as it covers on screen.
+    let dpi_scale = ctx.gfx_context.dpi_scale;
+    let raster_scale = rusttype::Scale {
+        x: scale.x * dpi_scale,
+        y: scale.y * dpi_scale,
+    };
+    let cache = &mut ctx.gfx_context.glyph_cache;
+    for (i, line) in text.lines().enumerate() {
+        let baseline = dest.y + v_metrics.ascent + line_height * i as f32;
+        let offset = rusttype::point(dest.x * dpi_scale, baseline * dpi_scale);
+        for (glyph_font_id, glyph) in
+            layout_ttf(font_id, font, fallbacks, line, raster_scale, offset)
+        {
+            cache.queue_glyph(glyph_font_id, glyph.standalone(), dpi_scale, color);
+        }
+    }
+    Ok(())
+}
+
 fn render_ttf(
     context: &mut Context,
     text: &str,
+    id: FontId,
     font: &rusttype::Font<'static>,
+    fallbacks: &[(FontId, rusttype::Font<'static>)],
     scale: rusttype::Scale,
 ) -> GameResult<Text> {
     // Ripped almost wholesale from
@@ -265,8 +639,18 @@ fn render_ttf(
     // Then turn them into an array of positioned glyphs...
     // `layout()` turns an abstract glyph, which contains no concrete
     // size or position information, into a PositionedGlyph, which does.
-    let glyphs: Vec<rusttype::PositionedGlyph> = font.layout(text, scale, offset).collect();
+    let glyphs: Vec<rusttype::PositionedGlyph> = layout_ttf(id, font, fallbacks, text, scale, offset)
+        .into_iter()
+        .map(|(_, g)| g)
+        .collect();
     let text_width_pixels = text_width(&glyphs).ceil() as usize;
+    let glyph_bounds = glyphs
+        .iter()
+        .map(|g| {
+            let advance = g.unpositioned().h_metrics().advance_width;
+            rect_from_corner(g.position().x, 0.0, advance, text_height_pixels as f32)
+        })
+        .collect();
     // let text_width_pixels = glyphs
     //     .iter()
     //     .rev()
@@ -321,12 +705,9 @@ fn render_ttf(
         &pixel_data,
     )?;
 
-    let text_string = text.to_string();
-    Ok(Text {
-        texture: image,
-        contents: text_string,
-    })
-
+    let bounds = image.get_dimensions();
+    let lines = vec![rect_from_corner(0.0, 0.0, bounds.w, bounds.h)];
+    Ok(Text::from_image(image, text.to_string(), lines, glyph_bounds))
 }
 
 /// Treats src and dst as row-major 2D arrays, and blits the given rect from src to dst.
@@ -374,7 +755,7 @@ fn render_bitmap(
     glyphs_map: &BTreeMap<char, usize>,
     glyph_width: usize,
 ) -> GameResult<Text> {
-    let text_length = text.len();
+    let text_length = text.chars().count();
     let glyph_height = height;
     let buf_len = text_length * glyph_width * glyph_height * 4;
     let mut dest_buf = Vec::with_capacity(buf_len);
@@ -404,21 +785,601 @@ fn render_bitmap(
         &dest_buf,
     )?;
     let text_string = text.to_string();
+    let glyph_bounds = (0..text_length)
+        .map(|i| {
+            rect_from_corner(
+                (i * glyph_width) as f32,
+                0.0,
+                glyph_width as f32,
+                glyph_height as f32,
+            )
+        })
+        .collect();
 
-    Ok(Text {
-        texture: image,
-        contents: text_string,
-    })
+    let lines = vec![
+        rect_from_corner(
+            0.0,
+            0.0,
+            (text_length * glyph_width) as f32,
+            glyph_height as f32,
+        ),
+    ];
+    Ok(Text::from_image(image, text_string, lines, glyph_bounds))
 }
 
+/// Renders text with a `BMFontData`, which unlike `render_bitmap()`
+/// handles glyphs of different sizes and offsets, kerning and
+/// line breaks.
+fn render_bmfont(context: &mut Context, text: &str, data: &BMFontData) -> GameResult<Text> {
+    let line_height = data.line_height as i32;
+    // First work out where everything goes...
+    let mut placed = Vec::new();
+    let mut glyph_bounds = Vec::new();
+    let mut line_bounds = Vec::new();
+    let mut width = 0;
+    for (line_idx, line) in text.split('\n').enumerate() {
+        let top = line_idx as i32 * line_height;
+        let mut x = 0;
+        let mut previous = None;
+        for c in line.chars() {
+            let error = GameError::FontError(format!("Character '{}' not in bitmap font!", c));
+            let ch = data.chars.get(&c).ok_or(error)?;
+            if let Some(p) = previous {
+                x += data.get_kerning(p, c);
+            }
+            placed.push((ch, x + ch.xoffset, top + ch.yoffset));
+            glyph_bounds.push(rect_from_corner(
+                x as f32,
+                top as f32,
+                ch.xadvance as f32,
+                line_height as f32,
+            ));
+            width = width.max(x + ch.xoffset + ch.width as i32);
+            x += ch.xadvance;
+            previous = Some(c);
+        }
+        width = width.max(x);
+        line_bounds.push(rect_from_corner(0.0, top as f32, x as f32, line_height as f32));
+        // The newline itself.
+        glyph_bounds.push(rect_from_corner(x as f32, top as f32, 0.0, line_height as f32));
+    }
+    // There's no newline after the last line.
+    glyph_bounds.pop();
+
+    // ...then copy the glyphs over.  Textures can't be empty.
+    let width = width.max(1) as usize;
+    let height = (line_bounds.len() as i32 * line_height).max(1) as usize;
+    if width >= u16::MAX as usize || height >= u16::MAX as usize {
+        let msg = format!("Text is too large to render: {}x{}", width, height);
+        return Err(GameError::RenderError(msg));
+    }
+    let mut dest_buf = vec![0u8; width * height * 4];
+    for (ch, dest_x, dest_y) in placed {
+        let page = &data.pages[ch.page];
+        for row in 0..ch.height as i32 {
+            for col in 0..ch.width as i32 {
+                let (x, y) = (dest_x + col, dest_y + row);
+                // Offsets can put parts of glyphs outside the text.
+                if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                    continue;
+                }
+                let src = ((ch.y as i32 + row) as usize * page.width as usize +
+                               (ch.x as i32 + col) as usize) * 4;
+                let dest = (y as usize * width + x as usize) * 4;
+                // Where glyphs overlap, the more opaque one wins.
+                if page.rgba[src + 3] >= dest_buf[dest + 3] {
+                    dest_buf[dest..dest + 4].copy_from_slice(&page.rgba[src..src + 4]);
+                }
+            }
+        }
+    }
+
+    let image = Image::from_rgba8(context, width as u16, height as u16, &dest_buf)?;
+    Ok(Text::from_image(image, text.to_string(), line_bounds, glyph_bounds))
+}
+
+/// Specifies how the lines of a `TextLayout` are aligned
+/// within its bounds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+    /// Stretches the spaces between words so lines fill the whole
+    /// width.  The last line of each paragraph is left-aligned.
+    Justify,
+}
+
+impl Default for Align {
+    fn default() -> Self {
+        Align::Left
+    }
+}
+
+/// A piece of text with its own style, to be laid out
+/// with a `TextLayout`.
+#[derive(Debug, Clone)]
+pub struct TextFragment {
+    /// The text itself.
+    pub text: String,
+    /// The font to draw it in.  Only TTF fonts are supported.
+    pub font: Font,
+    /// The color to draw it in, or `None` for white.
+    pub color: Option<Color>,
+    /// The size to draw it at, in points, or `None`
+    /// to use the size the font was loaded at.
+    pub size: Option<u32>,
+}
+
+impl TextFragment {
+    /// Creates a new fragment of text in the given font.
+    pub fn new<S: Into<String>>(text: S, font: &Font) -> Self {
+        TextFragment {
+            text: text.into(),
+            font: font.clone(),
+            color: None,
+            size: None,
+        }
+    }
+
+    /// Sets the color of the fragment.
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    /// Sets the size of the fragment, in points.
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = Some(size);
+        self
+    }
+}
+
+/// Lays out a series of `TextFragment`s into lines, wrapping them
+/// to fit a width and aligning them.  Create a `Text` out of it with
+/// `Text::from_layout()`.
+///
+/// ```rust,ignore
+/// let layout = TextLayout::new()
+///     .fragment(TextFragment::new("Hello ", &font))
+///     .fragment(TextFragment::new("world!", &font).color(Color::new(1.0, 0.0, 0.0, 1.0)))
+///     .width(Some(200.0))
+///     .align(Align::Center);
+/// let text = Text::from_layout(ctx, &layout)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct TextLayout {
+    fragments: Vec<TextFragment>,
+    width: Option<f32>,
+    align: Align,
+    line_spacing: f32,
+}
+
+impl Default for TextLayout {
+    fn default() -> Self {
+        TextLayout {
+            fragments: Vec::new(),
+            width: None,
+            align: Align::default(),
+            line_spacing: 1.0,
+        }
+    }
+}
+
+/// A character of a `TextLayout` with everything needed to
+/// position it.
+struct LayoutItem<'a> {
+    c: char,
+    glyph: rusttype::ScaledGlyph<'a>,
+    advance: f32,
+    /// Kerning between the previous character and this one.
+    kerning: f32,
+    v_metrics: rusttype::VMetrics,
+    color: Color,
+}
+
+/// A line of a `TextLayout`, as indices into its items.
+struct LayoutLine {
+    start: usize,
+    end: usize,
+    /// Whether the line was ended by a newline or the end of
+    /// the text, rather than wrapped.
+    ends_paragraph: bool,
+}
+
+/// The result of laying out a `TextLayout`.
+struct LaidOutText {
+    glyphs: Vec<(rusttype::PositionedGlyph<'static>, Color)>,
+    glyph_bounds: Vec<Rect>,
+    line_bounds: Vec<Rect>,
+    width: f32,
+    height: f32,
+}
+
+impl TextLayout {
+    /// Creates a new, empty `TextLayout`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a fragment to the end of the text.
+    pub fn fragment(mut self, fragment: TextFragment) -> Self {
+        self.fragments.push(fragment);
+        self
+    }
+
+    /// Sets the width to wrap the text to, in pixels.  With `None`,
+    /// lines are only broken at newlines, and the width of the text
+    /// is that of its longest line.
+    pub fn width(mut self, width: Option<f32>) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Sets how the lines are aligned.
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Sets the distance between lines, as a multiple of the
+    /// font's usual line height.
+    pub fn line_spacing(mut self, line_spacing: f32) -> Self {
+        self.line_spacing = line_spacing;
+        self
+    }
+
+    /// Returns the fragments making up the text.
+    pub fn fragments(&self) -> &[TextFragment] {
+        &self.fragments
+    }
+
+    /// Returns the whole text, without styling.
+    pub fn contents(&self) -> String {
+        self.fragments.iter().map(|f| &f.text[..]).collect()
+    }
+
+    /// Returns the size the text takes up once laid out, in pixels.
+    pub fn dimensions(&self) -> GameResult<(f32, f32)> {
+        let laid_out = self.lay_out()?;
+        Ok((laid_out.width, laid_out.height))
+    }
+
+    /// Returns the bounds of each line, relative to the top-left
+    /// corner of the text.
+    pub fn line_bounds(&self) -> GameResult<Vec<Rect>> {
+        Ok(self.lay_out()?.line_bounds)
+    }
+
+    /// Returns the bounds of each character, relative to the top-left
+    /// corner of the text.  See `Text::glyph_bounds()`.
+    pub fn glyph_bounds(&self) -> GameResult<Vec<Rect>> {
+        Ok(self.lay_out()?.glyph_bounds)
+    }
+
+    /// Returns a copy of the layout with everything `factor` times
+    /// bigger, for rasterizing at the DPI scale.
+    fn scaled_by(&self, factor: f32) -> TextLayout {
+        TextLayout {
+            fragments: self.fragments
+                .iter()
+                .map(|f| TextFragment {
+                    font: f.font.scaled_by(factor),
+                    ..f.clone()
+                })
+                .collect(),
+            width: self.width.map(|w| w * factor),
+            ..self.clone()
+        }
+    }
+
+    /// Turns the fragments into a flat list of styled characters.
+    fn items(&self) -> GameResult<Vec<LayoutItem>> {
+        let mut items = Vec::new();
+        for fragment in &self.fragments {
+            let (id, font, fallbacks, scale) = match fragment.font {
+                Font::TTFFont {
+                    id,
+                    ref font,
+                    ref fallbacks,
+                    points,
+                    scale,
+                } => {
+                    let factor = fragment.size.map(|s| s as f32 / points as f32).unwrap_or(1.0);
+                    (id, font, fallbacks, rusttype::Scale {
+                        x: scale.x * factor,
+                        y: scale.y * factor,
+                    })
+                }
+                _ => {
+                    return Err(GameError::FontError(
+                        "Only TTF fonts can be used in a TextLayout".to_owned(),
+                    ))
+                }
+            };
+            let color = fragment.color.unwrap_or(WHITE);
+            let mut previous = None;
+            for c in fragment.text.chars() {
+                let (font_id, f, glyph) = match find_glyph(id, font, fallbacks, c) {
+                    Some(found) => found,
+                    None => continue,
+                };
+                let glyph = glyph.scaled(scale);
+                let kerning = match previous {
+                    Some((previous_id, p)) if previous_id == font_id => {
+                        f.pair_kerning(scale, p, glyph.id())
+                    }
+                    _ => 0.0,
+                };
+                previous = Some((font_id, glyph.id()));
+                items.push(LayoutItem {
+                    c: c,
+                    advance: glyph.h_metrics().advance_width,
+                    glyph: glyph,
+                    kerning: kerning,
+                    v_metrics: f.v_metrics(scale),
+                    color: color,
+                });
+            }
+        }
+        Ok(items)
+    }
+
+    /// Breaks the items up into lines no wider than the layout's width,
+    /// if it has one.  Breaks happen at whitespace, or mid-word if a
+    /// single word doesn't fit.
+    fn break_lines(&self, items: &[LayoutItem]) -> Vec<LayoutLine> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut line_width = 0.0;
+        let mut i = 0;
+        while i < items.len() {
+            if items[i].c == '\n' {
+                lines.push(LayoutLine {
+                    start: start,
+                    end: i + 1,
+                    ends_paragraph: true,
+                });
+                start = i + 1;
+                line_width = 0.0;
+                i += 1;
+                continue;
+            }
+            // Find the end of the word, or of this run of whitespace.
+            let is_space = items[i].c.is_whitespace();
+            let mut end = i + 1;
+            while end < items.len() && items[end].c != '\n' &&
+                items[end].c.is_whitespace() == is_space
+            {
+                end += 1;
+            }
+            let advance_in_line = |from: usize, to: usize, line_start: usize| -> f32 {
+                items[from..to]
+                    .iter()
+                    .enumerate()
+                    .map(|(j, item)| {
+                        let kerning = if from + j == line_start { 0.0 } else { item.kerning };
+                        kerning + item.advance
+                    })
+                    .sum()
+            };
+            let word_width = advance_in_line(i, end, start);
+            let too_wide = match self.width {
+                Some(width) => !is_space && line_width + word_width > width,
+                None => false,
+            };
+            if too_wide && i > start {
+                // Wrap before the word.
+                lines.push(LayoutLine {
+                    start: start,
+                    end: i,
+                    ends_paragraph: false,
+                });
+                start = i;
+                line_width = 0.0;
+                continue;
+            }
+            if too_wide {
+                // The word alone doesn't fit on a line; take as
+                // much of it as fits, but at least one character.
+                let width = self.width.unwrap_or(0.0);
+                let mut split = i + 1;
+                while split < end && advance_in_line(i, split + 1, start) <= width {
+                    split += 1;
+                }
+                lines.push(LayoutLine {
+                    start: start,
+                    end: split,
+                    ends_paragraph: false,
+                });
+                start = split;
+                line_width = 0.0;
+                i = split;
+                continue;
+            }
+            line_width += word_width;
+            i = end;
+        }
+        if start < items.len() || lines.is_empty() || lines[lines.len() - 1].ends_paragraph {
+            lines.push(LayoutLine {
+                start: start,
+                end: items.len(),
+                ends_paragraph: true,
+            });
+        }
+        lines
+    }
+
+    /// Lays out the text: breaks it into lines, aligns them and
+    /// positions every glyph.
+    fn lay_out(&self) -> GameResult<LaidOutText> {
+        let items = self.items()?;
+        let lines = self.break_lines(&items);
+        let default_metrics = items.first().map(|i| i.v_metrics).unwrap_or(rusttype::VMetrics {
+            ascent: 0.0,
+            descent: 0.0,
+            line_gap: 0.0,
+        });
+
+        // Trailing whitespace and newlines don't count towards a
+        // line's width, so wrapped lines still look aligned.
+        let content_end = |line: &LayoutLine| {
+            let mut end = line.end;
+            while end > line.start && items[end - 1].c.is_whitespace() {
+                end -= 1;
+            }
+            end
+        };
+        let content_width = |line: &LayoutLine| -> f32 {
+            (line.start..content_end(line))
+                .map(|i| {
+                    let kerning = if i == line.start { 0.0 } else { items[i].kerning };
+                    kerning + items[i].advance
+                })
+                .sum()
+        };
+        let widths: Vec<f32> = lines.iter().map(|l| content_width(l)).collect();
+        let width = self.width
+            .unwrap_or_else(|| widths.iter().cloned().fold(0.0, f32::max));
+
+        let mut laid_out = LaidOutText {
+            glyphs: Vec::with_capacity(items.len()),
+            glyph_bounds: Vec::with_capacity(items.len()),
+            line_bounds: Vec::with_capacity(lines.len()),
+            width: width,
+            height: 0.0,
+        };
+        let mut top = 0.0;
+        for (line, &line_width) in lines.iter().zip(&widths) {
+            let line_items = &items[line.start..line.end];
+            let metrics = if line_items.is_empty() {
+                // Empty lines still take up space.
+                items
+                    .get(line.start.saturating_sub(1))
+                    .map(|i| i.v_metrics)
+                    .unwrap_or(default_metrics)
+            } else {
+                line_items.iter().fold(
+                    rusttype::VMetrics {
+                        ascent: 0.0,
+                        descent: 0.0,
+                        line_gap: 0.0,
+                    },
+                    |m, i| rusttype::VMetrics {
+                        ascent: m.ascent.max(i.v_metrics.ascent),
+                        descent: m.descent.min(i.v_metrics.descent),
+                        line_gap: m.line_gap.max(i.v_metrics.line_gap),
+                    },
+                )
+            };
+            let line_height =
+                (metrics.ascent - metrics.descent + metrics.line_gap) * self.line_spacing;
+            let baseline = top + metrics.ascent;
+
+            let end = content_end(line);
+            let spaces = (line.start..end)
+                .filter(|&i| items[i].c.is_whitespace())
+                .count();
+            let (mut x, extra_space) = match self.align {
+                Align::Left => (0.0, 0.0),
+                Align::Center => ((width - line_width) / 2.0, 0.0),
+                Align::Right => (width - line_width, 0.0),
+                Align::Justify if !line.ends_paragraph && spaces > 0 => {
+                    (0.0, (width - line_width) / spaces as f32)
+                }
+                Align::Justify => (0.0, 0.0),
+            };
+            let line_left = x;
+            for (i, item) in line_items.iter().enumerate() {
+                let index = line.start + i;
+                if i > 0 {
+                    x += item.kerning;
+                }
+                let mut advance = item.advance;
+                if index >= end || item.c == '\n' {
+                    // Trailing whitespace takes no room.
+                    advance = 0.0;
+                } else if item.c.is_whitespace() {
+                    advance += extra_space;
+                }
+                let glyph = item.glyph
+                    .clone()
+                    .positioned(rusttype::point(x, baseline))
+                    .standalone();
+                laid_out.glyphs.push((glyph, item.color));
+                laid_out
+                    .glyph_bounds
+                    .push(rect_from_corner(x, top, advance, line_height));
+                x += advance;
+            }
+            laid_out.line_bounds.push(rect_from_corner(
+                line_left,
+                top,
+                x - line_left,
+                line_height,
+            ));
+            top += line_height;
+        }
+        laid_out.height = top;
+        Ok(laid_out)
+    }
+}
+
+/// Renders laid-out glyphs into an RGBA buffer, each in its own color.
+fn render_layout(laid_out: &LaidOutText, width: usize, height: usize) -> Vec<u8> {
+    let mut pixel_data = vec![0u8; width * height * 4];
+    for &(ref glyph, color) in &laid_out.glyphs {
+        if let Some(bb) = glyph.pixel_bounding_box() {
+            let (r, g, b, a): (u8, u8, u8, u8) = color.into();
+            glyph.draw(|x, y, v| {
+                let x = x as i32 + bb.min.x;
+                let y = y as i32 + bb.min.y;
+                if x >= 0 && x < width as i32 && y >= 0 && y < height as i32 {
+                    let i = (x as usize + y as usize * width) * 4;
+                    let alpha = (v * a as f32) as u8;
+                    // Where glyphs overlap, the more opaque one wins.
+                    if alpha >= pixel_data[i + 3] {
+                        pixel_data[i] = r;
+                        pixel_data[i + 1] = g;
+                        pixel_data[i + 2] = b;
+                        pixel_data[i + 3] = alpha;
+                    }
+                }
+            })
+        }
+    }
+    pixel_data
+}
 
 impl Text {
     /// Renders a new `Text` from the given `Font`
     pub fn new(context: &mut Context, text: &str, font: &Font) -> GameResult<Text> {
+        let mut rendered = Text::render(context, text, font)?;
+        if let Font::TTFFont { .. } = *font {
+            rendered.source = Some(TextSource::Font(font.clone()));
+            rendered.update_dpi_scale(context)?;
+        }
+        Ok(rendered)
+    }
+
+    /// Renders a new `Text` from the given `TextLayout`.
+    pub fn from_layout(context: &mut Context, layout: &TextLayout) -> GameResult<Text> {
+        let mut rendered = Text::render_layout(context, layout)?;
+        rendered.source = Some(TextSource::Layout(layout.clone()));
+        rendered.update_dpi_scale(context)?;
+        Ok(rendered)
+    }
+
+    /// Renders text at the font's own size, ignoring the DPI scale.
+    fn render(context: &mut Context, text: &str, font: &Font) -> GameResult<Text> {
         match *font {
             Font::TTFFont {
-                font: ref f, scale, ..
-            } => render_ttf(context, text, f, scale),
+                id,
+                font: ref f,
+                ref fallbacks,
+                scale,
+                ..
+            } => render_ttf(context, text, id, f, fallbacks, scale),
             Font::BitmapFont {
                 ref bytes,
                 width,
@@ -426,17 +1387,76 @@ impl Text {
                 glyph_width,
                 ref glyphs,
             } => render_bitmap(context, text, bytes, width, height, glyphs, glyph_width),
+            Font::BMFont { ref data } => render_bmfont(context, text, data),
         }
     }
 
+    /// Renders a `TextLayout` at its fonts' own sizes, ignoring the
+    /// DPI scale.
+    fn render_layout(context: &mut Context, layout: &TextLayout) -> GameResult<Text> {
+        let laid_out = layout.lay_out()?;
+        // Textures can't be empty.
+        let width = (laid_out.width.ceil() as usize).max(1);
+        let height = (laid_out.height.ceil() as usize).max(1);
+        if width >= u16::MAX as usize || height >= u16::MAX as usize {
+            let msg = format!("Text is too large to render: {}x{}", width, height);
+            return Err(GameError::RenderError(msg));
+        }
+        let pixel_data = render_layout(&laid_out, width, height);
+        let image = Image::from_rgba8(context, width as u16, height as u16, &pixel_data)?;
+        Ok(Text::from_image(
+            image,
+            layout.contents(),
+            laid_out.line_bounds,
+            laid_out.glyph_bounds,
+        ))
+    }
+
+    fn from_image(image: Image, contents: String, lines: Vec<Rect>, glyphs: Vec<Rect>) -> Text {
+        Text {
+            width: image.width(),
+            height: image.height(),
+            texture: RefCell::new(image),
+            dpi_scale: Cell::new(1.0),
+            source: None,
+            contents: contents,
+            lines: lines,
+            glyphs: glyphs,
+        }
+    }
+
+    /// Rasterizes the text again if the DPI scale has changed since
+    /// it last was.  It keeps its size on screen either way.
+    fn update_dpi_scale(&self, context: &mut Context) -> GameResult<()> {
+        let dpi_scale = context.gfx_context.dpi_scale;
+        if (dpi_scale - self.dpi_scale.get()).abs() < 0.001 {
+            return Ok(());
+        }
+        let rendered = match self.source {
+            Some(TextSource::Font(ref font)) => {
+                Text::render(context, &self.contents, &font.scaled_by(dpi_scale))?
+            }
+            Some(TextSource::Layout(ref layout)) => {
+                Text::render_layout(context, &layout.scaled_by(dpi_scale))?
+            }
+            None => return Ok(()),
+        };
+        let mut texture = self.texture.borrow_mut();
+        let mut image = rendered.texture.into_inner();
+        image.set_filter(texture.get_filter());
+        *texture = image;
+        self.dpi_scale.set(dpi_scale);
+        Ok(())
+    }
+
     /// Returns the width of the rendered text, in pixels.
     pub fn width(&self) -> u32 {
-        self.texture.width()
+        self.width
     }
 
     /// Returns the height of the rendered text, in pixels.
     pub fn height(&self) -> u32 {
-        self.texture.height()
+        self.height
     }
 
     /// Returns the string that the text represents.
@@ -444,27 +1464,50 @@ impl Text {
         &self.contents
     }
 
+    /// Returns the bounds of each line of the text, relative to the
+    /// top-left corner of the text.
+    pub fn line_bounds(&self) -> &[Rect] {
+        &self.lines
+    }
+
+    /// Returns the bounds of each character of the text, relative to
+    /// the top-left corner of the text, for things like placing a cursor.
+    /// Each covers the character's advance and the height of its line.
+    pub fn glyph_bounds(&self) -> &[Rect] {
+        &self.glyphs
+    }
+
     /// Returns the dimensions of the rendered text.
     pub fn get_dimensions(&self) -> Rect {
-        self.texture.get_dimensions()
+        Rect::new(0.0, 0.0, self.width as f32, self.height as f32)
     }
 
 
     /// Get the filter mode for the the rendered text.
     pub fn get_filter(&self) -> FilterMode {
-        self.texture.get_filter()
+        self.texture.borrow().get_filter()
     }
 
     /// Set the filter mode for the the rendered text.
     pub fn set_filter(&mut self, mode: FilterMode) {
-        self.texture.set_filter(mode);
+        self.texture.get_mut().set_filter(mode);
     }
 }
 
 
 impl Drawable for Text {
     fn draw_ex(&self, ctx: &mut Context, param: DrawParam) -> GameResult<()> {
-        draw_ex(ctx, &self.texture, param)
+        self.update_dpi_scale(ctx)?;
+        let texture = self.texture.borrow();
+        // The texture may have more pixels than the text covers
+        // on screen, so shrink it to fit.
+        let scale_x = self.width as f32 / texture.width() as f32;
+        let scale_y = self.height as f32 / texture.height() as f32;
+        let param = DrawParam {
+            scale: Point::new(param.scale.x * scale_x, param.scale.y * scale_y),
+            ..param
+        };
+        draw_ex(ctx, &*texture, param)
     }
 }
 
@@ -473,8 +1516,8 @@ impl fmt::Debug for Text {
         write!(
             f,
             "<Text: {}x{}, {:p}>",
-            self.texture.width,
-            self.texture.height,
+            self.width,
+            self.height,
             &self
         )
 
@@ -484,6 +1527,69 @@ impl fmt::Debug for Text {
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    /// Builds a TrueType font with a single square glyph, for the
+    /// given character, to test fallbacks with.
+    fn single_glyph_font(c: char) -> Vec<u8> {
+        fn push16(v: &mut Vec<u8>, x: u16) {
+            v.extend_from_slice(&[(x >> 8) as u8, x as u8]);
+        }
+        fn push32(v: &mut Vec<u8>, x: u32) {
+            push16(v, (x >> 16) as u16);
+            push16(v, x as u16);
+        }
+        let words = |words: &[u16]| {
+            let mut v = Vec::new();
+            for &w in words {
+                push16(&mut v, w);
+            }
+            v
+        };
+        // A format 12 character map with one group, for `c`.
+        let mut cmap = words(&[0, 1, 3, 10, 0, 12, 12, 0, 0, 28, 0, 0, 0, 1]);
+        for &x in &[c as u32, c as u32, 1] {
+            push32(&mut cmap, x);
+        }
+        // A square from (100, 0) to (900, 800), as deltas between points.
+        let glyf = words(&[
+            1, 100, 0, 900, 800, 3, 0, 0x0101, 0x0101, 100, 0, 800, 0, 0, 800, 0, 0xfce0,
+        ]);
+        let head = words(&[
+            1, 0, 1, 0, 0, 0, 0x5f0f, 0x3cf5, 0, 1000, 0, 0, 0, 0, 0, 0, 0, 0, 100, 0, 900, 800,
+            0, 8, 2, 0, 0,
+        ]);
+        let hhea = words(&[1, 0, 800, 0xff38, 0, 1000, 0, 0, 900, 1, 0, 0, 0, 0, 0, 0, 0, 2]);
+        let hmtx = words(&[500, 0, 1000, 100]);
+        let loca = words(&[0, 0, glyf.len() as u16 / 2]);
+        let maxp = words(&[1, 0, 2, 4, 1, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let tables = [
+            (b"cmap", cmap),
+            (b"glyf", glyf),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"loca", loca),
+            (b"maxp", maxp),
+        ];
+
+        let mut font = words(&[1, 0, tables.len() as u16, 64, 2, 48]);
+        let mut offset = 12 + 16 * tables.len();
+        for &(tag, ref data) in &tables {
+            font.extend_from_slice(tag);
+            push32(&mut font, 0);
+            push32(&mut font, offset as u32);
+            push32(&mut font, data.len() as u32);
+            offset += (data.len() + 3) / 4 * 4;
+        }
+        for &(_, ref data) in &tables {
+            font.extend_from_slice(data);
+            while font.len() % 4 != 0 {
+                font.push(0);
+            }
+        }
+        font
+    }
+
     #[test]
     fn test_blit() {
         let dst = &mut [0; 125][..];
@@ -510,11 +1616,55 @@ mod tests {
         assert_eq!(dst, src);
     }
 
+    #[test]
+    fn test_layout() {
+        let f = Font::default_font().unwrap();
+        let layout = TextLayout::new()
+            .fragment(TextFragment::new("Foo bar ", &f))
+            .fragment(TextFragment::new("baz\nquux", &f).color(BLACK));
+        let glyphs = layout.glyph_bounds().unwrap();
+        assert_eq!(glyphs.len(), layout.contents().chars().count());
+        assert_eq!(layout.line_bounds().unwrap().len(), 2);
+
+        // Wrapping to the width of "Foo bar" puts "baz" on its own line.
+        let wrap_width = f.get_width("Foo bar") as f32 + 1.0;
+        let wrapped = layout.clone().width(Some(wrap_width));
+        let lines = wrapped.line_bounds().unwrap();
+        assert_eq!(lines.len(), 3);
+        for line in &lines {
+            assert!(line.w <= wrap_width);
+        }
+        assert!(lines[1].y > lines[0].y);
+
+        // Centered and right-aligned lines end up further right.
+        let left = wrapped.line_bounds().unwrap();
+        let center = wrapped.clone().align(Align::Center).line_bounds().unwrap();
+        let right = wrapped.clone().align(Align::Right).line_bounds().unwrap();
+        assert!(left[1].left() < center[1].left());
+        assert!(center[1].left() < right[1].left());
+        assert!((right[1].right() - wrap_width).abs() < 0.01);
+
+        // Double line spacing doubles the height.
+        let (_, h1) = layout.dimensions().unwrap();
+        let (_, h2) = layout.clone().line_spacing(2.0).dimensions().unwrap();
+        assert!((h2 - h1 * 2.0).abs() < 0.01);
+
+        // Rasterizing at a DPI scale of 2 lays the text out twice as big,
+        // wrapping at the same places.
+        let (w1, h1) = wrapped.dimensions().unwrap();
+        let doubled = wrapped.scaled_by(2.0);
+        let (w2, h2) = doubled.dimensions().unwrap();
+        assert!((w2 - w1 * 2.0).abs() < 0.01 && (h2 - h1 * 2.0).abs() < 0.01);
+        assert_eq!(doubled.line_bounds().unwrap().len(), 3);
+    }
+
     #[test]
     fn test_metrics() {
         let f = Font::default_font().unwrap();
         assert_eq!(f.get_height(), 17);
         assert_eq!(f.get_width("Foo!"), 33);
+        // Kerning pulls the V in between the As; 30 without it.
+        assert_eq!(f.get_width("AVA"), 28);
 
         // http://www.catipsum.com/index.php
         let text_to_wrap = "Walk on car leaving trail of paw prints on hood and windshield sniff \
@@ -546,13 +1696,74 @@ mod tests {
         assert_eq!(&v, &wrapped_text);
     }
 
-    // We sadly can't have this test in the general case because it needs to create a Context,
-    // which creates a window, which fails on a headless server like our CI systems.  :/
-    //#[test]
-    #[allow(dead_code)]
+    #[test]
+    fn test_glyph_metrics() {
+        let mut f = Font::default_font().unwrap();
+        let metrics = f.get_metrics();
+        assert!(metrics.ascent > 0.0);
+        assert!(metrics.descent < 0.0);
+        assert!(metrics.line_height() > metrics.ascent);
+
+        let a = f.get_glyph_metrics('A').unwrap();
+        assert!(a.advance > 0.0);
+        // The glyph sits above the baseline.
+        assert!(a.bounds.unwrap().y < 0.0);
+        assert_eq!(f.get_glyph_metrics(' ').unwrap().bounds, None);
+        assert!(f.get_kerning('A', 'V') <= 0.0);
+
+        // DejaVu has no CJK, and neither does its fallback.
+        assert_eq!(f.get_glyph_metrics('漢'), None);
+        let fallback = Font::default_font().unwrap();
+        f.add_fallback(&fallback).unwrap();
+        assert_eq!(f.get_glyph_metrics('漢'), None);
+        assert_eq!(f.get_glyph_metrics('A'), Some(a));
+
+        // A fallback that has the character fills the gap.
+        let square = Font::from_bytes("square", &single_glyph_font('漢'), 16, (75.0, 75.0)).unwrap();
+        f.add_fallback(&square).unwrap();
+        let han = f.get_glyph_metrics('漢').unwrap();
+        assert!(han.advance > a.advance);
+        assert_eq!(f.get_glyph_metrics('A'), Some(a));
+        let square_id = match square {
+            Font::TTFFont { id, .. } => id,
+            _ => unreachable!(),
+        };
+        if let Font::TTFFont {
+            id,
+            ref font,
+            ref fallbacks,
+            scale,
+            ..
+        } = f
+        {
+            let glyphs = layout_ttf(id, font, fallbacks, "A漢A", scale, rusttype::point(0.0, 0.0));
+            let ids: Vec<FontId> = glyphs.iter().map(|&(id, _)| id).collect();
+            assert_eq!(ids, vec![id, square_id, id]);
+            assert!((glyphs[1].1.position().x - a.advance).abs() < 0.001);
+            assert!((glyphs[2].1.position().x - (a.advance + han.advance)).abs() < 0.001);
+        }
+
+        let mut bitmap = Font::BitmapFont {
+            bytes: vec![0; 8 * 8 * 4],
+            width: 8,
+            height: 8,
+            glyphs: vec![('a', 0)].into_iter().collect(),
+            glyph_width: 8,
+        };
+        assert!(bitmap.add_fallback(&f).is_err());
+        assert!(f.add_fallback(&bitmap).is_err());
+        assert_eq!(bitmap.get_glyph_metrics('a').unwrap().advance, 8.0);
+    }
+
+    // This needs to create a headless Context, which needs a GL driver
+    // that can render off-screen, which not every build server has.
+    // Run it with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
     fn test_wrapping() {
         use conf;
-        let c = conf::Conf::new();
+        let mut c = conf::Conf::new();
+        c.headless = true;
         let ctx = &mut Context::load_from_conf("test_wrapping", "ggez", c).unwrap();
         let font = Font::default_font().unwrap();
         let text_to_wrap = "Walk on car leaving trail of paw prints on hood and windshield sniff \
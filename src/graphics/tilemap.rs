@@ -0,0 +1,1648 @@
+//! Tile maps made with the Tiled map editor, <http://www.mapeditor.org/>.
+//!
+//! A `TileMap` loads a map saved as `.tmx` or `.json`, along with its
+//! tilesets and their images, and draws its tile layers.  Each layer is
+//! cut into chunks of tiles, each drawn with a `SpriteBatch` per
+//! tileset, and only the chunks that are on the screen get drawn.
+//! Object layers aren't drawn; they are there as data, for things like
+//! spawn points and collision shapes.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::io::Read;
+use std::path;
+use std::str;
+
+use base64;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde_json;
+use xml::reader::{EventReader, XmlEvent};
+
+use super::*;
+use super::spritebatch::SpriteBatch;
+
+/// The number of tiles along each side of a chunk.
+const CHUNK_SIZE: u32 = 16;
+
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+/// Every flag Tiled may set in the top bits of a tile's global ID,
+/// including the one for hexagonal maps, which we ignore.
+const FLAG_BITS: u32 = 0xF000_0000;
+
+/// How the tiles of a `TileMap` are laid out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MapOrientation {
+    /// In a grid of rectangles.
+    Orthogonal,
+    /// In a grid of diamonds, with the first tile at the top.
+    Isometric,
+}
+
+/// A tile in a layer: which tile from which tileset it is,
+/// and how it is flipped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Tile {
+    /// The global ID of the tile, without the flip flags.
+    pub gid: u32,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    /// Flipped along the diagonal from the top left to the bottom
+    /// right, before flipping horizontally or vertically.  Together
+    /// with those this rotates the tile by multiples of 90 degrees.
+    pub flip_diagonal: bool,
+}
+
+impl Tile {
+    /// Decodes a tile from a global ID as Tiled stores it, with the
+    /// flip flags in the top bits.  ID 0 is an empty space.
+    pub fn from_gid(raw: u32) -> Option<Tile> {
+        let gid = raw & !FLAG_BITS;
+        if gid == 0 {
+            return None;
+        }
+        Some(Tile {
+            gid: gid,
+            flip_horizontal: raw & FLIPPED_HORIZONTALLY != 0,
+            flip_vertical: raw & FLIPPED_VERTICALLY != 0,
+            flip_diagonal: raw & FLIPPED_DIAGONALLY != 0,
+        })
+    }
+}
+
+/// A tileset: an image cut into a grid of same-sized tiles.
+#[derive(Debug, Clone)]
+pub struct Tileset {
+    pub name: String,
+    /// The global ID of the first tile in the tileset.
+    pub first_gid: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    /// Pixels around the edge of the image before the first tiles.
+    pub margin: u32,
+    /// Pixels between neighbouring tiles.
+    pub spacing: u32,
+    pub columns: u32,
+    pub tile_count: u32,
+    image: Image,
+}
+
+impl Tileset {
+    /// Returns the tileset's image.
+    pub fn get_image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Returns whether the tile with the given global ID is in this tileset.
+    pub fn contains(&self, gid: u32) -> bool {
+        gid >= self.first_gid && gid - self.first_gid < self.tile_count
+    }
+
+    /// Returns the part of the image the tile with the given global
+    /// ID is in, as a fraction of the image, ready to use as
+    /// `DrawParam::src`.
+    pub fn get_tile_src(&self, gid: u32) -> Rect {
+        let origin = gid.checked_sub(self.first_gid).and_then(|id| {
+            tile_origin(id, self.columns, self.tile_width, self.tile_height, self.margin, self.spacing)
+        });
+        // Loading a tileset checks that all its tiles are in reach.
+        let (x, y) = origin.unwrap_or((0, 0));
+        Rect::fraction(
+            x as f32,
+            y as f32,
+            self.tile_width as f32,
+            self.tile_height as f32,
+            &self.image.get_dimensions(),
+        )
+    }
+}
+
+/// A layer of tiles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileLayer {
+    pub name: String,
+    /// The width of the layer, in tiles.
+    pub width: u32,
+    /// The height of the layer, in tiles.
+    pub height: u32,
+    /// The tiles, row by row from the top, with `None` for empty spaces.
+    pub tiles: Vec<Option<Tile>>,
+    pub visible: bool,
+    pub opacity: f32,
+    /// How far the layer is drawn from where it would be, in pixels.
+    pub offset: Point,
+    pub properties: HashMap<String, String>,
+}
+
+impl TileLayer {
+    /// Returns the tile at the given position, if there is one.
+    pub fn get_tile(&self, x: u32, y: u32) -> Option<Tile> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.tiles[(y * self.width + x) as usize]
+    }
+}
+
+/// The shape of a `MapObject`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectShape {
+    /// The rectangle given by the object's position and size.
+    Rectangle,
+    /// The ellipse filling that rectangle.
+    Ellipse,
+    /// Just the object's position.
+    Point,
+    /// A closed shape, with points relative to the object's position.
+    Polygon(Vec<Point>),
+    /// An open line, with points relative to the object's position.
+    Polyline(Vec<Point>),
+    /// A tile, placed with its bottom-left corner at the
+    /// object's position.
+    Tile(Tile),
+}
+
+/// An object from an object layer.
+///
+/// Positions and sizes are in pixels, as Tiled stores them.  On
+/// isometric maps Tiled measures them along the axes of the grid in
+/// units of the tile height, so use `TileMap::tile_to_world()` with the
+/// position divided by the tile height to find where they are drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    /// The object's type, a free-form string set in Tiled.
+    pub kind: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// The rotation around the object's position, in radians clockwise.
+    pub rotation: f32,
+    pub visible: bool,
+    pub shape: ObjectShape,
+    pub properties: HashMap<String, String>,
+}
+
+/// A layer of objects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectLayer {
+    pub name: String,
+    pub objects: Vec<MapObject>,
+    pub visible: bool,
+    pub opacity: f32,
+    /// How far the layer's objects are from where they say they are, in pixels.
+    pub offset: Point,
+    pub properties: HashMap<String, String>,
+}
+
+/// A layer of a `TileMap`.
+///
+/// Layers inside groups are flattened out into the map's list of
+/// layers, with the group's offset, opacity and visibility applied.
+/// Image layers are left out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapLayer {
+    Tiles(TileLayer),
+    Objects(ObjectLayer),
+}
+
+impl MapLayer {
+    /// Returns the layer's name.
+    pub fn name(&self) -> &str {
+        match *self {
+            MapLayer::Tiles(ref layer) => &layer.name,
+            MapLayer::Objects(ref layer) => &layer.name,
+        }
+    }
+
+    /// Returns whether the layer is visible.
+    pub fn is_visible(&self) -> bool {
+        match *self {
+            MapLayer::Tiles(ref layer) => layer.visible,
+            MapLayer::Objects(ref layer) => layer.visible,
+        }
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        match *self {
+            MapLayer::Tiles(ref mut layer) => layer.visible = visible,
+            MapLayer::Objects(ref mut layer) => layer.visible = visible,
+        }
+    }
+}
+
+/// The size and shape of a map's grid, and the
+/// conversions between tiles and pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Grid {
+    orientation: MapOrientation,
+    width: u32,
+    height: u32,
+    tile_width: f32,
+    tile_height: f32,
+}
+
+impl Grid {
+    fn tile_to_world(&self, x: f32, y: f32) -> Point {
+        match self.orientation {
+            MapOrientation::Orthogonal => Point::new(x * self.tile_width, y * self.tile_height),
+            MapOrientation::Isometric => {
+                let origin_x = self.height as f32 * self.tile_width / 2.0;
+                Point::new(
+                    origin_x + (x - y) * self.tile_width / 2.0,
+                    (x + y) * self.tile_height / 2.0,
+                )
+            }
+        }
+    }
+
+    fn world_to_tile(&self, point: Point) -> (f32, f32) {
+        match self.orientation {
+            MapOrientation::Orthogonal => (point.x / self.tile_width, point.y / self.tile_height),
+            MapOrientation::Isometric => {
+                let origin_x = self.height as f32 * self.tile_width / 2.0;
+                let a = (point.x - origin_x) / (self.tile_width / 2.0);
+                let b = point.y / (self.tile_height / 2.0);
+                ((b + a) / 2.0, (b - a) / 2.0)
+            }
+        }
+    }
+
+    /// Returns the top-left corner of the box around a cell.
+    fn cell_origin(&self, x: u32, y: u32) -> Point {
+        let p = self.tile_to_world(x as f32, y as f32);
+        match self.orientation {
+            MapOrientation::Orthogonal => p,
+            MapOrientation::Isometric => Point::new(p.x - self.tile_width / 2.0, p.y),
+        }
+    }
+
+    /// Works out how to draw a tile from a tileset in the given cell.
+    /// Tiles are drawn with the bottom of the image on the bottom of
+    /// the cell, on the left on orthogonal maps and in the middle on
+    /// isometric ones, the same as Tiled does, so tiles taller than
+    /// the grid stick up out of their cell.
+    fn tile_draw_param(
+        &self,
+        tileset: &Tileset,
+        tile: Tile,
+        x: u32,
+        y: u32,
+        layer: &TileLayer,
+    ) -> (DrawParam, Bounds) {
+        let (w, h) = (tileset.tile_width as f32, tileset.tile_height as f32);
+        let (w, h) = if tile.flip_diagonal { (h, w) } else { (w, h) };
+        let cell = self.cell_origin(x, y);
+        let left = match self.orientation {
+            MapOrientation::Orthogonal => cell.x,
+            MapOrientation::Isometric => cell.x + (self.tile_width - w) / 2.0,
+        };
+        let bottom = cell.y + self.tile_height;
+        let center = Point::new(
+            left + w / 2.0 + layer.offset.x,
+            bottom - h / 2.0 + layer.offset.y,
+        );
+
+        // A diagonal flip is a quarter turn plus a vertical flip; any
+        // flips after it turn into flips of the other axis before it.
+        let h_sign = if tile.flip_horizontal { -1.0 } else { 1.0 };
+        let v_sign = if tile.flip_vertical { -1.0 } else { 1.0 };
+        let (scale, rotation) = if tile.flip_diagonal {
+            (Point::new(v_sign, -h_sign), PI / 2.0)
+        } else {
+            (Point::new(h_sign, v_sign), 0.0)
+        };
+        let color = if layer.opacity < 1.0 {
+            Some(Color::new(1.0, 1.0, 1.0, layer.opacity))
+        } else {
+            None
+        };
+        let param = DrawParam {
+            src: tileset.get_tile_src(tile.gid),
+            dest: center,
+            rotation: rotation,
+            scale: scale,
+            color: color,
+            ..Default::default()
+        };
+        let bounds = Bounds {
+            left: center.x - w / 2.0,
+            top: center.y - h / 2.0,
+            right: center.x + w / 2.0,
+            bottom: center.y + h / 2.0,
+        };
+        (param, bounds)
+    }
+}
+
+/// An axis-aligned box, in map pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Bounds {
+    left: f32,
+    top: f32,
+    right: f32,
+    bottom: f32,
+}
+
+impl Bounds {
+    fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    fn overlaps(&self, other: &Bounds) -> bool {
+        self.left < other.right && other.left < self.right && self.top < other.bottom &&
+            other.top < self.bottom
+    }
+}
+
+/// A block of tiles from one layer, with a batch for each
+/// tileset they use.
+#[derive(Debug)]
+struct Chunk {
+    layer: usize,
+    bounds: Bounds,
+    batches: Vec<(usize, SpriteBatch)>,
+}
+
+/// A map made with Tiled.
+///
+/// The map is laid out in pixels with Y increasing downwards, the same
+/// as in Tiled, so it's meant to be drawn with the default screen
+/// coordinates.  Drawing it draws all its visible tile layers, with the
+/// map's top-left corner at `DrawParam::dest`; only the parts on the
+/// screen are drawn, even when the view is moved around with
+/// `set_screen_coordinates()` or the transform stack.
+///
+/// ```rust,ignore
+/// let map = TileMap::new(ctx, "/maps/level1.tmx")?;
+/// let spawn = map.get_layer("spawns")
+///     .and_then(|layer| match *layer {
+///         MapLayer::Objects(ref objects) => objects.objects.first().cloned(),
+///         _ => None,
+///     });
+/// graphics::draw(ctx, &map, Point::zero(), 0.0)?;
+/// ```
+///
+/// Orthogonal and isometric maps are supported, with tiles stored in
+/// any of Tiled's layer formats, and tilesets either embedded in the
+/// map or in their own `.tsx` or `.json` files.  Infinite maps,
+/// tilesets made of separate images, and tile animations aren't.
+#[derive(Debug)]
+pub struct TileMap {
+    grid: Grid,
+    tilesets: Vec<Tileset>,
+    layers: Vec<MapLayer>,
+    properties: HashMap<String, String>,
+    chunks: Vec<Chunk>,
+}
+
+impl TileMap {
+    /// Loads a map from a `.tmx` or `.json` file, along with its
+    /// tilesets and their images.  Paths in the map are relative
+    /// to the directory the map is in.
+    pub fn new<P: AsRef<path::Path>>(ctx: &mut Context, path: P) -> GameResult<TileMap> {
+        let path = path.as_ref();
+        let source = read_file(ctx, path)?;
+        let data = if is_json(path) {
+            parse_json_map(&source)?
+        } else {
+            parse_tmx(&source)?
+        };
+
+        let mut tilesets = Vec::with_capacity(data.tilesets.len());
+        for info in data.tilesets {
+            let (info, base) = match info.source.clone() {
+                Some(source) => {
+                    let tileset_path = resolve_path(path, &source);
+                    let tileset_source = read_file(ctx, &tileset_path)?;
+                    let mut external = if is_json(&tileset_path) {
+                        parse_json_tileset(&tileset_source)?
+                    } else {
+                        parse_tsx(&tileset_source)?
+                    };
+                    external.first_gid = info.first_gid;
+                    (external, tileset_path)
+                }
+                None => (info, path.to_path_buf()),
+            };
+            let image_file = match info.image {
+                Some(ref image) => image.clone(),
+                None => {
+                    return Err(tiled_error(format!(
+                        "Tileset {:?} has no image; tilesets made of separate \
+                         images aren't supported",
+                        info.name
+                    )))
+                }
+            };
+            let image = Image::new(ctx, resolve_path(&base, &image_file))?;
+            tilesets.push(info.into_tileset(image)?);
+        }
+
+        let grid = Grid {
+            orientation: data.orientation,
+            width: data.width,
+            height: data.height,
+            tile_width: data.tile_width as f32,
+            tile_height: data.tile_height as f32,
+        };
+        let chunks = build_chunks(&grid, &tilesets, &data.layers);
+        Ok(TileMap {
+            grid: grid,
+            tilesets: tilesets,
+            layers: data.layers,
+            properties: data.properties,
+            chunks: chunks,
+        })
+    }
+
+    /// Returns how the map's tiles are laid out.
+    pub fn get_orientation(&self) -> MapOrientation {
+        self.grid.orientation
+    }
+
+    /// Returns the width of the map, in tiles.
+    pub fn get_width(&self) -> u32 {
+        self.grid.width
+    }
+
+    /// Returns the height of the map, in tiles.
+    pub fn get_height(&self) -> u32 {
+        self.grid.height
+    }
+
+    /// Returns the width of the map's grid cells, in pixels.
+    pub fn get_tile_width(&self) -> u32 {
+        self.grid.tile_width as u32
+    }
+
+    /// Returns the height of the map's grid cells, in pixels.
+    pub fn get_tile_height(&self) -> u32 {
+        self.grid.tile_height as u32
+    }
+
+    /// Returns the map's tilesets.
+    pub fn tilesets(&self) -> &[Tileset] {
+        &self.tilesets
+    }
+
+    /// Returns the map's layers, from the bottom up.
+    pub fn layers(&self) -> &[MapLayer] {
+        &self.layers
+    }
+
+    /// Returns the first layer with the given name.
+    pub fn get_layer(&self, name: &str) -> Option<&MapLayer> {
+        self.layers.iter().find(|layer| layer.name() == name)
+    }
+
+    /// Shows or hides a layer when the map is drawn.
+    ///
+    /// Does nothing if there is no layer with that index.
+    pub fn set_layer_visible(&mut self, index: usize, visible: bool) {
+        if let Some(layer) = self.layers.get_mut(index) {
+            layer.set_visible(visible);
+        }
+    }
+
+    /// Returns the map's custom properties.
+    pub fn get_properties(&self) -> &HashMap<String, String> {
+        &self.properties
+    }
+
+    /// Converts a position on the map's grid, in tiles, to pixels.
+    /// On isometric maps (0, 0) is the top corner of the first tile.
+    pub fn tile_to_world(&self, x: f32, y: f32) -> Point {
+        self.grid.tile_to_world(x, y)
+    }
+
+    /// Converts a position in pixels to a position on the map's grid,
+    /// in tiles.  The tile it is in is the integer part of each.
+    pub fn world_to_tile(&self, point: Point) -> (f32, f32) {
+        self.grid.world_to_tile(point)
+    }
+
+    /// Draws a single layer, whether or not it is visible.
+    /// Does nothing for object layers.
+    pub fn draw_layer(&self, ctx: &mut Context, index: usize, param: DrawParam) -> GameResult<()> {
+        self.draw_chunks(ctx, param, |layer| layer == index)
+    }
+
+    fn draw_chunks<F>(&self, ctx: &mut Context, param: DrawParam, include: F) -> GameResult<()>
+    where
+        F: Fn(usize) -> bool,
+    {
+        let view = match visible_bounds(ctx, param) {
+            Some(view) => view,
+            None => return Ok(()),
+        };
+        for chunk in &self.chunks {
+            if include(chunk.layer) && chunk.bounds.overlaps(&view) {
+                for &(_, ref batch) in &chunk.batches {
+                    batch.draw_ex(ctx, param)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drawable for TileMap {
+    fn draw_ex(&self, ctx: &mut Context, param: DrawParam) -> GameResult<()> {
+        let layers = &self.layers;
+        self.draw_chunks(ctx, param, |layer| layers[layer].is_visible())
+    }
+}
+
+/// Works out the part of the map that's on the screen when it is
+/// drawn with the given `DrawParam`, or `None` if it isn't drawn at
+/// all because the transform squashes it flat.
+fn visible_bounds(ctx: &Context, param: DrawParam) -> Option<Bounds> {
+    let screen = get_screen_coordinates(ctx);
+    let to_map = (ctx.gfx_context.get_transform() * Matrix::from(param)).inverse()?;
+    let corners = [
+        to_map.transform_point(Point::new(screen.left(), screen.top())),
+        to_map.transform_point(Point::new(screen.right(), screen.top())),
+        to_map.transform_point(Point::new(screen.left(), screen.bottom())),
+        to_map.transform_point(Point::new(screen.right(), screen.bottom())),
+    ];
+    let first = Bounds {
+        left: corners[0].x,
+        top: corners[0].y,
+        right: corners[0].x,
+        bottom: corners[0].y,
+    };
+    Some(corners[1..].iter().fold(first, |bounds, p| {
+        bounds.union(&Bounds {
+            left: p.x,
+            top: p.y,
+            right: p.x,
+            bottom: p.y,
+        })
+    }))
+}
+
+/// Cuts the map's tile layers into chunks, filling
+/// a batch for each tileset used in each chunk.
+fn build_chunks(grid: &Grid, tilesets: &[Tileset], layers: &[MapLayer]) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    for (index, layer) in layers.iter().enumerate() {
+        let layer = match *layer {
+            MapLayer::Tiles(ref layer) => layer,
+            MapLayer::Objects(_) => continue,
+        };
+        for chunk_y in 0..chunk_count(layer.height) {
+            for chunk_x in 0..chunk_count(layer.width) {
+                let mut batches: Vec<(usize, SpriteBatch)> = Vec::new();
+                let mut bounds: Option<Bounds> = None;
+                let (x0, y0) = (chunk_x * CHUNK_SIZE, chunk_y * CHUNK_SIZE);
+                for y in y0..cmp::min(y0.saturating_add(CHUNK_SIZE), layer.height) {
+                    for x in x0..cmp::min(x0.saturating_add(CHUNK_SIZE), layer.width) {
+                        let tile = match layer.get_tile(x, y) {
+                            Some(tile) => tile,
+                            None => continue,
+                        };
+                        let tileset_index = match tilesets.iter().position(|t| t.contains(tile.gid)) {
+                            Some(i) => i,
+                            None => continue,
+                        };
+                        let tileset = &tilesets[tileset_index];
+                        let (param, tile_bounds) = grid.tile_draw_param(tileset, tile, x, y, layer);
+                        let batch = match batches.iter().position(|&(i, _)| i == tileset_index) {
+                            Some(i) => i,
+                            None => {
+                                batches.push((tileset_index, SpriteBatch::new(tileset.image.clone())));
+                                batches.len() - 1
+                            }
+                        };
+                        batches[batch].1.add(param);
+                        bounds = Some(match bounds {
+                            Some(b) => b.union(&tile_bounds),
+                            None => tile_bounds,
+                        });
+                    }
+                }
+                if let Some(bounds) = bounds {
+                    chunks.push(Chunk {
+                        layer: index,
+                        bounds: bounds,
+                        batches: batches,
+                    });
+                }
+            }
+        }
+    }
+    chunks
+}
+
+/// Finds where a tile is in its tileset's image, in pixels, or
+/// `None` if that's too far in to count to.
+fn tile_origin(
+    id: u32,
+    columns: u32,
+    tile_width: u32,
+    tile_height: u32,
+    margin: u32,
+    spacing: u32,
+) -> Option<(u32, u32)> {
+    let offset = |index: u32, tile_len: u32| {
+        tile_len
+            .checked_add(spacing)
+            .and_then(|step| index.checked_mul(step))
+            .and_then(|distance| distance.checked_add(margin))
+    };
+    if columns == 0 {
+        return None;
+    }
+    let (column, row) = (id % columns, id / columns);
+    match (offset(column, tile_width), offset(row, tile_height)) {
+        (Some(x), Some(y)) => Some((x, y)),
+        _ => None,
+    }
+}
+
+/// How many chunks it takes to cover `len` tiles.
+fn chunk_count(len: u32) -> u32 {
+    // Rounded up, without overflowing for the largest layers.
+    len / CHUNK_SIZE + if len % CHUNK_SIZE == 0 { 0 } else { 1 }
+}
+
+fn tiled_error<S: AsRef<str>>(msg: S) -> GameError {
+    GameError::ResourceLoadError(format!("Invalid Tiled map: {}", msg.as_ref()))
+}
+
+fn read_file(ctx: &mut Context, path: &path::Path) -> GameResult<String> {
+    let mut source = String::new();
+    let mut reader = ctx.filesystem.open(path)?;
+    reader.read_to_string(&mut source)?;
+    Ok(source)
+}
+
+fn is_json(path: &path::Path) -> bool {
+    path.extension().map_or(false, |ext| ext == "json")
+}
+
+/// Finds a file referred to by another file, relative to the
+/// directory the other file is in.
+fn resolve_path(base: &path::Path, relative: &str) -> path::PathBuf {
+    let mut result = base.parent()
+        .unwrap_or_else(|| path::Path::new("/"))
+        .to_path_buf();
+    for component in path::Path::new(relative).components() {
+        match component {
+            path::Component::ParentDir => {
+                result.pop();
+            }
+            path::Component::CurDir => (),
+            other => result.push(other.as_os_str()),
+        }
+    }
+    result
+}
+
+/// Everything in a map file apart from the tileset images.
+#[derive(Debug)]
+struct MapData {
+    orientation: MapOrientation,
+    width: u32,
+    height: u32,
+    tile_width: u32,
+    tile_height: u32,
+    tilesets: Vec<TilesetData>,
+    layers: Vec<MapLayer>,
+    properties: HashMap<String, String>,
+}
+
+/// A tileset as described in a map or tileset file.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct TilesetData {
+    first_gid: u32,
+    /// The file the rest of the tileset is in, if it isn't in the map.
+    source: Option<String>,
+    name: String,
+    tile_width: u32,
+    tile_height: u32,
+    margin: u32,
+    spacing: u32,
+    columns: Option<u32>,
+    tile_count: Option<u32>,
+    image: Option<String>,
+}
+
+impl TilesetData {
+    /// Fills in anything the file left out from the image's size.
+    fn into_tileset(self, image: Image) -> GameResult<Tileset> {
+        if self.tile_width == 0 || self.tile_height == 0 {
+            return Err(tiled_error(format!("Tileset {:?} has no tile size", self.name)));
+        }
+        let (margin, spacing) = (self.margin, self.spacing);
+        let fit = |image_len: u32, tile_len: u32| {
+            let lengths = (
+                image_len.checked_add(spacing),
+                margin.checked_mul(2),
+                tile_len.checked_add(spacing),
+            );
+            match lengths {
+                (Some(room), Some(margins), Some(step)) => Some(room.saturating_sub(margins) / step),
+                _ => None,
+            }
+        };
+        // The sizes come straight from the file, so they may
+        // be big enough to overflow.
+        let (columns, tile_count) = {
+            let too_big = || tiled_error(format!("Tileset {:?} is too big", self.name));
+            let columns = match self.columns {
+                Some(columns) => columns,
+                None => fit(image.width(), self.tile_width).ok_or_else(&too_big)?,
+            };
+            if columns == 0 {
+                return Err(tiled_error(format!("Tileset {:?} has no tiles", self.name)));
+            }
+            let tile_count = match self.tile_count {
+                Some(count) => count,
+                None => {
+                    let rows = fit(image.height(), self.tile_height).ok_or_else(&too_big)?;
+                    columns.checked_mul(rows).ok_or_else(&too_big)?
+                }
+            };
+            let last = tile_count.saturating_sub(1);
+            if tile_origin(last, columns, self.tile_width, self.tile_height, margin, spacing).is_none() {
+                return Err(too_big());
+            }
+            (columns, tile_count)
+        };
+        Ok(Tileset {
+            tile_count: tile_count,
+            name: self.name,
+            first_gid: self.first_gid,
+            tile_width: self.tile_width,
+            tile_height: self.tile_height,
+            margin: self.margin,
+            spacing: self.spacing,
+            columns: columns,
+            image: image,
+        })
+    }
+}
+
+/// The offset, visibility and opacity a group passes on to its layers.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Inherited {
+    offset: Point,
+    visible: bool,
+    opacity: f32,
+}
+
+impl Inherited {
+    fn top() -> Self {
+        Inherited {
+            offset: Point::zero(),
+            visible: true,
+            opacity: 1.0,
+        }
+    }
+
+    fn apply(&self, offset: Point, visible: bool, opacity: f32) -> Self {
+        Inherited {
+            offset: Point::new(self.offset.x + offset.x, self.offset.y + offset.y),
+            visible: self.visible && visible,
+            opacity: self.opacity * opacity,
+        }
+    }
+}
+
+fn parse_orientation(orientation: &str) -> GameResult<MapOrientation> {
+    match orientation {
+        "orthogonal" => Ok(MapOrientation::Orthogonal),
+        "isometric" => Ok(MapOrientation::Isometric),
+        other => Err(tiled_error(format!("{} maps aren't supported", other))),
+    }
+}
+
+fn new_tile_layer(
+    name: String,
+    width: u32,
+    height: u32,
+    gids: Vec<u32>,
+    inherited: Inherited,
+    properties: HashMap<String, String>,
+) -> GameResult<TileLayer> {
+    let count = match width.checked_mul(height) {
+        Some(count) => count,
+        None => {
+            return Err(tiled_error(format!(
+                "Layer {:?} is too big at {}x{} tiles",
+                name,
+                width,
+                height
+            )))
+        }
+    };
+    if gids.len() != count as usize {
+        return Err(tiled_error(format!(
+            "Layer {:?} has {} tiles instead of {}x{}",
+            name,
+            gids.len(),
+            width,
+            height
+        )));
+    }
+    Ok(TileLayer {
+        name: name,
+        width: width,
+        height: height,
+        tiles: gids.into_iter().map(Tile::from_gid).collect(),
+        visible: inherited.visible,
+        opacity: inherited.opacity,
+        offset: inherited.offset,
+        properties: properties,
+    })
+}
+
+/// Decodes a layer's tiles from Tiled's CSV or base64 formats,
+/// the latter optionally compressed.
+fn decode_tile_data(encoding: &str, compression: Option<&str>, data: &str) -> GameResult<Vec<u32>> {
+    match encoding {
+        "csv" => data.split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse()
+                    .map_err(|_| tiled_error(format!("Invalid tile {:?}", s)))
+            })
+            .collect(),
+        "base64" => {
+            let bytes = base64::decode(data.trim())
+                .map_err(|e| tiled_error(format!("Invalid base64 tile data: {}", e)))?;
+            let bytes = match compression {
+                None | Some("") => bytes,
+                Some("zlib") => {
+                    let mut decoded = Vec::new();
+                    ZlibDecoder::new(&bytes[..]).read_to_end(&mut decoded)?;
+                    decoded
+                }
+                Some("gzip") => {
+                    let mut decoded = Vec::new();
+                    GzDecoder::new(&bytes[..])?.read_to_end(&mut decoded)?;
+                    decoded
+                }
+                Some(other) => {
+                    return Err(tiled_error(format!("{} compression isn't supported", other)))
+                }
+            };
+            if bytes.len() % 4 != 0 {
+                return Err(tiled_error("Tile data isn't a whole number of tiles"));
+            }
+            Ok(bytes
+                .chunks(4)
+                .map(|b| {
+                    u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16 |
+                        u32::from(b[3]) << 24
+                })
+                .collect())
+        }
+        other => Err(tiled_error(format!("Unknown tile encoding {:?}", other))),
+    }
+}
+
+/// Parses the `x,y x,y ...` points of a TMX polygon or polyline.
+fn parse_points(points: &str) -> GameResult<Vec<Point>> {
+    points
+        .split_whitespace()
+        .map(|pair| {
+            let mut coords = pair.split(',').map(|c| c.parse::<f32>());
+            match (coords.next(), coords.next(), coords.next()) {
+                (Some(Ok(x)), Some(Ok(y)), None) => Ok(Point::new(x, y)),
+                _ => Err(tiled_error(format!("Invalid point {:?}", pair))),
+            }
+        })
+        .collect()
+}
+
+/// A bare-bones XML element tree, which is all
+/// we need to pick a TMX file apart.
+#[derive(Debug, Clone, Default)]
+struct XmlElement {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<XmlElement>,
+    text: String,
+}
+
+impl XmlElement {
+    fn parse(source: &str) -> GameResult<XmlElement> {
+        let mut stack: Vec<XmlElement> = Vec::new();
+        for event in EventReader::from_str(source) {
+            match event? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => stack.push(XmlElement {
+                    name: name.local_name,
+                    attributes: attributes
+                        .into_iter()
+                        .map(|a| (a.name.local_name, a.value))
+                        .collect(),
+                    children: Vec::new(),
+                    text: String::new(),
+                }),
+                XmlEvent::EndElement { .. } => {
+                    let element = match stack.pop() {
+                        Some(element) => element,
+                        None => return Err(tiled_error("End of an XML element that never started")),
+                    };
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(element),
+                        None => return Ok(element),
+                    }
+                }
+                XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                    if let Some(element) = stack.last_mut() {
+                        element.text.push_str(&text);
+                    }
+                }
+                _ => (),
+            }
+        }
+        Err(tiled_error("No root XML element"))
+    }
+
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(|s| &s[..])
+    }
+
+    fn parse_attr_opt<T: str::FromStr>(&self, name: &str) -> GameResult<Option<T>> {
+        match self.attr(name) {
+            None => Ok(None),
+            Some(value) => value.trim().parse().map(Some).map_err(|_| {
+                tiled_error(format!(
+                    "<{}> has an invalid {} of {:?}",
+                    self.name,
+                    name,
+                    value
+                ))
+            }),
+        }
+    }
+
+    fn parse_attr<T: str::FromStr>(&self, name: &str, default: T) -> GameResult<T> {
+        Ok(self.parse_attr_opt(name)?.unwrap_or(default))
+    }
+
+    fn required_attr<T: str::FromStr>(&self, name: &str) -> GameResult<T> {
+        self.parse_attr_opt(name)?
+            .ok_or_else(|| tiled_error(format!("<{}> has no {}", self.name, name)))
+    }
+
+    fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// The `visible`, `opacity` and offset attributes of a layer.
+    fn layer_attrs(&self, inherited: Inherited) -> GameResult<Inherited> {
+        let offset = Point::new(self.parse_attr("offsetx", 0.0)?, self.parse_attr("offsety", 0.0)?);
+        let visible = self.parse_attr("visible", 1u32)? != 0;
+        Ok(inherited.apply(offset, visible, self.parse_attr("opacity", 1.0)?))
+    }
+
+    fn properties(&self) -> HashMap<String, String> {
+        let mut properties = HashMap::new();
+        if let Some(list) = self.child("properties") {
+            for property in list.children.iter().filter(|p| p.name == "property") {
+                if let Some(name) = property.attr("name") {
+                    // Multi-line strings are stored as text instead.
+                    let value = property.attr("value").unwrap_or(&property.text);
+                    properties.insert(name.to_owned(), value.to_owned());
+                }
+            }
+        }
+        properties
+    }
+}
+
+fn parse_tmx(source: &str) -> GameResult<MapData> {
+    let map = XmlElement::parse(source)?;
+    if map.name != "map" {
+        return Err(tiled_error("The root element isn't <map>"));
+    }
+    if map.parse_attr("infinite", 0u32)? != 0 {
+        return Err(tiled_error("Infinite maps aren't supported"));
+    }
+    let mut tilesets = Vec::new();
+    for tileset in map.children.iter().filter(|c| c.name == "tileset") {
+        tilesets.push(tmx_tileset(tileset)?);
+    }
+    let mut layers = Vec::new();
+    tmx_layers(&map, Inherited::top(), &mut layers)?;
+    Ok(MapData {
+        orientation: parse_orientation(map.attr("orientation").unwrap_or("orthogonal"))?,
+        width: map.required_attr("width")?,
+        height: map.required_attr("height")?,
+        tile_width: map.required_attr("tilewidth")?,
+        tile_height: map.required_attr("tileheight")?,
+        tilesets: tilesets,
+        layers: layers,
+        properties: map.properties(),
+    })
+}
+
+/// Parses a `<tileset>` from a map, or the root of a `.tsx` file.
+fn tmx_tileset(tileset: &XmlElement) -> GameResult<TilesetData> {
+    let first_gid = tileset.parse_attr("firstgid", 1)?;
+    if let Some(source) = tileset.attr("source") {
+        return Ok(TilesetData {
+            first_gid: first_gid,
+            source: Some(source.to_owned()),
+            ..Default::default()
+        });
+    }
+    Ok(TilesetData {
+        first_gid: first_gid,
+        source: None,
+        name: tileset.attr("name").unwrap_or("").to_owned(),
+        tile_width: tileset.required_attr("tilewidth")?,
+        tile_height: tileset.required_attr("tileheight")?,
+        margin: tileset.parse_attr("margin", 0)?,
+        spacing: tileset.parse_attr("spacing", 0)?,
+        columns: tileset.parse_attr_opt("columns")?,
+        tile_count: tileset.parse_attr_opt("tilecount")?,
+        image: tileset
+            .child("image")
+            .and_then(|image| image.attr("source"))
+            .map(|s| s.to_owned()),
+    })
+}
+
+fn parse_tsx(source: &str) -> GameResult<TilesetData> {
+    let tileset = XmlElement::parse(source)?;
+    if tileset.name != "tileset" {
+        return Err(tiled_error("The root element of a tileset file isn't <tileset>"));
+    }
+    tmx_tileset(&tileset)
+}
+
+fn tmx_layers(parent: &XmlElement, inherited: Inherited, layers: &mut Vec<MapLayer>) -> GameResult<()> {
+    for child in &parent.children {
+        match &child.name[..] {
+            "layer" => layers.push(MapLayer::Tiles(tmx_tile_layer(child, inherited)?)),
+            "objectgroup" => {
+                let attrs = child.layer_attrs(inherited)?;
+                let mut objects = Vec::new();
+                for object in child.children.iter().filter(|c| c.name == "object") {
+                    objects.push(tmx_object(object)?);
+                }
+                layers.push(MapLayer::Objects(ObjectLayer {
+                    name: child.attr("name").unwrap_or("").to_owned(),
+                    objects: objects,
+                    visible: attrs.visible,
+                    opacity: attrs.opacity,
+                    offset: attrs.offset,
+                    properties: child.properties(),
+                }));
+            }
+            "group" => tmx_layers(child, child.layer_attrs(inherited)?, layers)?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn tmx_tile_layer(layer: &XmlElement, inherited: Inherited) -> GameResult<TileLayer> {
+    let data = layer
+        .child("data")
+        .ok_or_else(|| tiled_error("A tile layer has no <data>"))?;
+    let gids = match data.attr("encoding") {
+        Some(encoding) => decode_tile_data(encoding, data.attr("compression"), &data.text)?,
+        None => {
+            let mut gids = Vec::new();
+            for tile in data.children.iter().filter(|c| c.name == "tile") {
+                gids.push(tile.parse_attr("gid", 0)?);
+            }
+            gids
+        }
+    };
+    new_tile_layer(
+        layer.attr("name").unwrap_or("").to_owned(),
+        layer.required_attr("width")?,
+        layer.required_attr("height")?,
+        gids,
+        layer.layer_attrs(inherited)?,
+        layer.properties(),
+    )
+}
+
+fn tmx_object(object: &XmlElement) -> GameResult<MapObject> {
+    let shape = if let Some(tile) = object.parse_attr_opt("gid")?.and_then(Tile::from_gid) {
+        ObjectShape::Tile(tile)
+    } else if object.child("ellipse").is_some() {
+        ObjectShape::Ellipse
+    } else if object.child("point").is_some() {
+        ObjectShape::Point
+    } else if let Some(polygon) = object.child("polygon") {
+        ObjectShape::Polygon(parse_points(polygon.attr("points").unwrap_or(""))?)
+    } else if let Some(polyline) = object.child("polyline") {
+        ObjectShape::Polyline(parse_points(polyline.attr("points").unwrap_or(""))?)
+    } else {
+        ObjectShape::Rectangle
+    };
+    Ok(MapObject {
+        id: object.parse_attr("id", 0)?,
+        name: object.attr("name").unwrap_or("").to_owned(),
+        kind: object.attr("type").unwrap_or("").to_owned(),
+        x: object.parse_attr("x", 0.0)?,
+        y: object.parse_attr("y", 0.0)?,
+        width: object.parse_attr("width", 0.0)?,
+        height: object.parse_attr("height", 0.0)?,
+        rotation: object.parse_attr("rotation", 0.0f32)?.to_radians(),
+        visible: object.parse_attr("visible", 1u32)? != 0,
+        shape: shape,
+        properties: object.properties(),
+    })
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonMap {
+    #[serde(default = "default_orientation")]
+    orientation: String,
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    layers: Vec<JsonLayer>,
+    #[serde(default)]
+    tilesets: Vec<JsonTileset>,
+    #[serde(default)]
+    properties: Option<serde_json::Value>,
+}
+
+fn default_orientation() -> String {
+    "orthogonal".to_owned()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum JsonTileData {
+    Tiles(Vec<u32>),
+    Encoded(String),
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    data: Option<JsonTileData>,
+    encoding: Option<String>,
+    compression: Option<String>,
+    #[serde(default = "default_true")]
+    visible: bool,
+    #[serde(default = "default_opacity")]
+    opacity: f32,
+    #[serde(default)]
+    offsetx: f32,
+    #[serde(default)]
+    offsety: f32,
+    #[serde(default)]
+    objects: Vec<JsonObject>,
+    #[serde(default)]
+    layers: Vec<JsonLayer>,
+    #[serde(default)]
+    properties: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonPoint {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonObject {
+    #[serde(default)]
+    id: u32,
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    x: f32,
+    #[serde(default)]
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    rotation: f32,
+    gid: Option<u32>,
+    #[serde(default = "default_true")]
+    visible: bool,
+    #[serde(default)]
+    ellipse: bool,
+    #[serde(default)]
+    point: bool,
+    polygon: Option<Vec<JsonPoint>>,
+    polyline: Option<Vec<JsonPoint>>,
+    #[serde(default)]
+    properties: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonTileset {
+    #[serde(default = "default_first_gid")]
+    firstgid: u32,
+    source: Option<String>,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    tilewidth: u32,
+    #[serde(default)]
+    tileheight: u32,
+    #[serde(default)]
+    margin: u32,
+    #[serde(default)]
+    spacing: u32,
+    columns: Option<u32>,
+    tilecount: Option<u32>,
+    image: Option<String>,
+}
+
+fn default_first_gid() -> u32 {
+    1
+}
+
+impl From<JsonTileset> for TilesetData {
+    fn from(t: JsonTileset) -> Self {
+        TilesetData {
+            first_gid: t.firstgid,
+            source: t.source,
+            name: t.name,
+            tile_width: t.tilewidth,
+            tile_height: t.tileheight,
+            margin: t.margin,
+            spacing: t.spacing,
+            columns: t.columns,
+            tile_count: t.tilecount,
+            image: t.image,
+        }
+    }
+}
+
+/// Reads custom properties, which newer versions of Tiled save as a
+/// list of `{name, type, value}` and older ones as a plain object.
+fn json_properties(properties: Option<serde_json::Value>) -> HashMap<String, String> {
+    fn to_string(value: &serde_json::Value) -> String {
+        match *value {
+            serde_json::Value::String(ref s) => s.clone(),
+            ref other => other.to_string(),
+        }
+    }
+    match properties {
+        Some(serde_json::Value::Array(list)) => list.iter()
+            .filter_map(|p| match (p.get("name").and_then(|n| n.as_str()), p.get("value")) {
+                (Some(name), Some(value)) => Some((name.to_owned(), to_string(value))),
+                _ => None,
+            })
+            .collect(),
+        Some(serde_json::Value::Object(map)) => map.iter()
+            .map(|(name, value)| (name.clone(), to_string(value)))
+            .collect(),
+        _ => HashMap::new(),
+    }
+}
+
+fn parse_json_map(source: &str) -> GameResult<MapData> {
+    let map: JsonMap = serde_json::from_str(source)?;
+    if map.infinite {
+        return Err(tiled_error("Infinite maps aren't supported"));
+    }
+    let mut layers = Vec::new();
+    json_layers(map.layers, Inherited::top(), &mut layers)?;
+    Ok(MapData {
+        orientation: parse_orientation(&map.orientation)?,
+        width: map.width,
+        height: map.height,
+        tile_width: map.tilewidth,
+        tile_height: map.tileheight,
+        tilesets: map.tilesets.into_iter().map(TilesetData::from).collect(),
+        layers: layers,
+        properties: json_properties(map.properties),
+    })
+}
+
+fn parse_json_tileset(source: &str) -> GameResult<TilesetData> {
+    let tileset: JsonTileset = serde_json::from_str(source)?;
+    Ok(tileset.into())
+}
+
+fn json_layers(
+    json: Vec<JsonLayer>,
+    inherited: Inherited,
+    layers: &mut Vec<MapLayer>,
+) -> GameResult<()> {
+    for layer in json {
+        let attrs = inherited.apply(
+            Point::new(layer.offsetx, layer.offsety),
+            layer.visible,
+            layer.opacity,
+        );
+        match &layer.kind[..] {
+            "tilelayer" => {
+                let gids = match layer.data {
+                    Some(JsonTileData::Tiles(gids)) => gids,
+                    Some(JsonTileData::Encoded(ref data)) => decode_tile_data(
+                        layer.encoding.as_ref().map_or("base64", |e| &e[..]),
+                        layer.compression.as_ref().map(|c| &c[..]),
+                        data,
+                    )?,
+                    None => return Err(tiled_error(format!("Layer {:?} has no data", layer.name))),
+                };
+                layers.push(MapLayer::Tiles(new_tile_layer(
+                    layer.name,
+                    layer.width,
+                    layer.height,
+                    gids,
+                    attrs,
+                    json_properties(layer.properties),
+                )?));
+            }
+            "objectgroup" => {
+                let objects = layer.objects.into_iter().map(json_object).collect();
+                layers.push(MapLayer::Objects(ObjectLayer {
+                    name: layer.name,
+                    objects: objects,
+                    visible: attrs.visible,
+                    opacity: attrs.opacity,
+                    offset: attrs.offset,
+                    properties: json_properties(layer.properties),
+                }));
+            }
+            "group" => json_layers(layer.layers, attrs, layers)?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn json_object(object: JsonObject) -> MapObject {
+    let points = |list: Vec<JsonPoint>| list.into_iter().map(|p| Point::new(p.x, p.y)).collect();
+    let shape = if let Some(tile) = object.gid.and_then(Tile::from_gid) {
+        ObjectShape::Tile(tile)
+    } else if object.ellipse {
+        ObjectShape::Ellipse
+    } else if object.point {
+        ObjectShape::Point
+    } else if let Some(polygon) = object.polygon {
+        ObjectShape::Polygon(points(polygon))
+    } else if let Some(polyline) = object.polyline {
+        ObjectShape::Polyline(points(polyline))
+    } else {
+        ObjectShape::Rectangle
+    };
+    MapObject {
+        id: object.id,
+        name: object.name,
+        kind: object.kind,
+        x: object.x,
+        y: object.y,
+        width: object.width,
+        height: object.height,
+        rotation: object.rotation.to_radians(),
+        visible: object.visible,
+        shape: shape,
+        properties: json_properties(object.properties),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tile_data() {
+        let csv = decode_tile_data("csv", None, "\n1,2,\n2147483651\n").unwrap();
+        let base64 = decode_tile_data("base64", None, "\n   AQAAAAIAAAADAACA\n").unwrap();
+        assert_eq!(csv, vec![1, 2, 0x8000_0003]);
+        assert_eq!(base64, csv);
+
+        let tile = Tile::from_gid(0x8000_0003).unwrap();
+        assert_eq!(tile.gid, 3);
+        assert!(tile.flip_horizontal && !tile.flip_vertical && !tile.flip_diagonal);
+        assert_eq!(Tile::from_gid(0), None);
+        assert_eq!(Tile::from_gid(FLIPPED_DIAGONALLY), None);
+
+        let layer = new_tile_layer("a".into(), 2, 1, csv[..2].to_vec(), Inherited::top(), HashMap::new());
+        assert_eq!(layer.unwrap().get_tile(1, 0).map(|t| t.gid), Some(2));
+        assert!(new_tile_layer("b".into(), 3, 1, csv.clone(), Inherited::top(), HashMap::new()).is_ok());
+        assert!(new_tile_layer("c".into(), 2, 2, csv.clone(), Inherited::top(), HashMap::new()).is_err());
+        // 65536 * 65536 wraps around to 0 in a u32.
+        assert!(new_tile_layer("d".into(), 65536, 65536, vec![], Inherited::top(), HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_grid() {
+        // Tile 5 of a 3-column tileset is in the middle of the second row.
+        assert_eq!(tile_origin(5, 3, 16, 8, 2, 1), Some((2 + 2 * 17, 2 + 9)));
+        assert_eq!(tile_origin(5, 0, 16, 8, 2, 1), None);
+        assert_eq!(tile_origin(5, 3, 16, ::std::u32::MAX, 2, 1), None);
+        assert_eq!(tile_origin(0x8000_0000, 1, 1, 1, 0, 1), None);
+        assert_eq!(chunk_count(CHUNK_SIZE * 2), 2);
+        assert_eq!(chunk_count(CHUNK_SIZE * 2 + 1), 3);
+        assert_eq!(chunk_count(::std::u32::MAX), ::std::u32::MAX / CHUNK_SIZE + 1);
+
+        let mut grid = Grid {
+            orientation: MapOrientation::Orthogonal,
+            width: 10,
+            height: 4,
+            tile_width: 32.0,
+            tile_height: 16.0,
+        };
+        assert_eq!(grid.tile_to_world(2.0, 3.0), Point::new(64.0, 48.0));
+        assert_eq!(grid.world_to_tile(Point::new(64.0, 48.0)), (2.0, 3.0));
+
+        grid.orientation = MapOrientation::Isometric;
+        // The top corner of the first tile is in the middle of the
+        // left edge of a 4 tile high map, along the top.
+        assert_eq!(grid.tile_to_world(0.0, 0.0), Point::new(64.0, 0.0));
+        assert_eq!(grid.tile_to_world(1.0, 0.0), Point::new(80.0, 8.0));
+        assert_eq!(grid.tile_to_world(0.0, 1.0), Point::new(48.0, 8.0));
+        assert_eq!(grid.world_to_tile(Point::new(80.0, 16.0)), (1.5, 0.5));
+
+        let a = Bounds {
+            left: 0.0,
+            top: 0.0,
+            right: 10.0,
+            bottom: 10.0,
+        };
+        let b = Bounds {
+            left: 10.0,
+            top: 5.0,
+            right: 20.0,
+            bottom: 15.0,
+        };
+        assert!(!a.overlaps(&b));
+        assert!(a.union(&b).overlaps(&b));
+    }
+
+    #[test]
+    fn test_parse_tmx() {
+        let tmx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="isometric" width="2" height="2" tilewidth="32" tileheight="16">
+ <properties>
+  <property name="music" value="town.ogg"/>
+ </properties>
+ <tileset firstgid="1" name="ground" tilewidth="32" tileheight="32" spacing="1" margin="2" tilecount="4" columns="2">
+  <image source="../images/ground.png" width="69" height="69"/>
+ </tileset>
+ <tileset firstgid="5" source="trees.tsx"/>
+ <layer name="floor" width="2" height="2">
+  <data encoding="csv">1,2,0,3</data>
+ </layer>
+ <group name="things" offsetx="4" opacity="0.5">
+  <layer name="decor" width="2" height="2" visible="0">
+   <data><tile gid="5"/><tile/><tile/><tile gid="1073741830"/></data>
+  </layer>
+  <objectgroup name="spawns">
+   <object id="3" name="player" type="spawn" x="10" y="20" rotation="90">
+    <point/>
+   </object>
+   <object id="4" x="0" y="0">
+    <polygon points="0,0 16,0 16,8"/>
+   </object>
+  </objectgroup>
+ </group>
+</map>"#;
+        let map = parse_tmx(tmx).unwrap();
+        assert_eq!(map.orientation, MapOrientation::Isometric);
+        assert_eq!((map.width, map.height, map.tile_width, map.tile_height), (2, 2, 32, 16));
+        assert_eq!(map.properties["music"], "town.ogg");
+
+        assert_eq!(map.tilesets.len(), 2);
+        let ground = &map.tilesets[0];
+        assert_eq!((ground.margin, ground.spacing, ground.columns), (2, 1, Some(2)));
+        assert_eq!(ground.image, Some("../images/ground.png".to_owned()));
+        assert_eq!(map.tilesets[1].first_gid, 5);
+        assert_eq!(map.tilesets[1].source, Some("trees.tsx".to_owned()));
+
+        assert_eq!(map.layers.len(), 3);
+        match map.layers[0] {
+            MapLayer::Tiles(ref floor) => {
+                assert_eq!(floor.get_tile(1, 0).unwrap().gid, 2);
+                assert_eq!(floor.get_tile(0, 1), None);
+                assert_eq!(floor.get_tile(2, 0), None);
+            }
+            _ => panic!("Expected a tile layer"),
+        }
+        match map.layers[1] {
+            MapLayer::Tiles(ref decor) => {
+                assert!(!decor.visible);
+                assert_eq!(decor.opacity, 0.5);
+                assert_eq!(decor.offset, Point::new(4.0, 0.0));
+                let tile = decor.get_tile(1, 1).unwrap();
+                assert_eq!(tile.gid, 6);
+                assert!(tile.flip_vertical);
+            }
+            _ => panic!("Expected a tile layer"),
+        }
+        match map.layers[2] {
+            MapLayer::Objects(ref spawns) => {
+                assert!(spawns.visible);
+                let player = &spawns.objects[0];
+                assert_eq!((&player.name[..], &player.kind[..]), ("player", "spawn"));
+                assert_eq!((player.x, player.y), (10.0, 20.0));
+                assert!((player.rotation - PI / 2.0).abs() < 0.0001);
+                assert_eq!(player.shape, ObjectShape::Point);
+                let points = vec![Point::new(0.0, 0.0), Point::new(16.0, 0.0), Point::new(16.0, 8.0)];
+                assert_eq!(spawns.objects[1].shape, ObjectShape::Polygon(points));
+            }
+            _ => panic!("Expected an object layer"),
+        }
+
+        let tsx = r#"<tileset name="trees" tilewidth="16" tileheight="48"><image source="trees.png"/></tileset>"#;
+        let trees = parse_tsx(tsx).unwrap();
+        assert_eq!((trees.tile_width, trees.tile_height), (16, 48));
+        assert_eq!(trees.image, Some("trees.png".to_owned()));
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let json = r#"{
+            "orientation": "orthogonal", "width": 2, "height": 1,
+            "tilewidth": 16, "tileheight": 16, "infinite": false,
+            "properties": [{"name": "gravity", "type": "float", "value": 9.5}],
+            "tilesets": [{"firstgid": 1, "name": "tiles", "tilewidth": 16, "tileheight": 16,
+                          "image": "tiles.png", "imagewidth": 64, "imageheight": 64}],
+            "layers": [
+                {"type": "tilelayer", "name": "ground", "width": 2, "height": 1,
+                 "data": [1, 536870914], "visible": true, "opacity": 1},
+                {"type": "tilelayer", "name": "packed", "width": 3, "height": 1,
+                 "encoding": "base64", "data": "AQAAAAIAAAADAACA"},
+                {"type": "objectgroup", "name": "walls", "objects": [
+                    {"id": 1, "x": 0, "y": 0, "width": 16, "height": 8, "ellipse": true,
+                     "properties": {"solid": true}}
+                ]}
+            ]
+        }"#;
+        let map = parse_json_map(json).unwrap();
+        assert_eq!(map.orientation, MapOrientation::Orthogonal);
+        assert_eq!(map.properties["gravity"], "9.5");
+        assert_eq!(map.tilesets[0].image, Some("tiles.png".to_owned()));
+        assert_eq!(map.tilesets[0].columns, None);
+        match map.layers[0] {
+            MapLayer::Tiles(ref ground) => {
+                let tile = ground.get_tile(1, 0).unwrap();
+                assert_eq!(tile.gid, 2);
+                assert!(tile.flip_diagonal);
+            }
+            _ => panic!("Expected a tile layer"),
+        }
+        match map.layers[1] {
+            MapLayer::Tiles(ref packed) => assert_eq!(packed.get_tile(2, 0).unwrap().gid, 3),
+            _ => panic!("Expected a tile layer"),
+        }
+        match map.layers[2] {
+            MapLayer::Objects(ref walls) => {
+                assert_eq!(walls.objects[0].shape, ObjectShape::Ellipse);
+                assert_eq!(walls.objects[0].properties["solid"], "true");
+            }
+            _ => panic!("Expected an object layer"),
+        }
+
+        assert!(parse_json_map(r#"{"width": 1, "height": 1, "tilewidth": 8,
+            "tileheight": 8, "infinite": true}"#).is_err());
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let map = path::Path::new("/maps/level1.tmx");
+        assert_eq!(resolve_path(map, "tiles.png"), path::Path::new("/maps/tiles.png"));
+        assert_eq!(
+            resolve_path(map, "../images/./tiles.png"),
+            path::Path::new("/images/tiles.png")
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use sdl2;
 
+use lyon::tessellation as t;
+
 /// A simple 2D point.
 #[derive(Copy, Clone, PartialEq, Debug, Default)]
 pub struct Point {
@@ -146,6 +148,132 @@ impl From<Rect> for [f32; 4] {
 }
 
 
+/// A 4x4 transformation matrix, stored row-major, used for
+/// the global transform of the graphics context.
+///
+/// We only really care about 2D affine transforms, but we keep
+/// it 4x4 so it can be sent straight to the shader.  Rather than
+/// create a dependency on cgmath or nalgebra for this, we just
+/// define the few operations we need ourselves.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Matrix {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Matrix {
+    /// Creates a new identity matrix.
+    pub fn identity() -> Self {
+        Matrix {
+            m: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Creates a 2D affine matrix out of its six meaningful components,
+    /// such that a point is transformed to `(a*x + b*y + tx, c*x + d*y + ty)`.
+    pub fn affine(a: f32, b: f32, c: f32, d: f32, tx: f32, ty: f32) -> Self {
+        Matrix {
+            m: [
+                [a, b, 0.0, tx],
+                [c, d, 0.0, ty],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Creates a matrix translating by the given amounts.
+    pub fn translation(dx: f32, dy: f32) -> Self {
+        Matrix::affine(1.0, 0.0, 0.0, 1.0, dx, dy)
+    }
+
+    /// Creates a matrix rotating by the given angle, in radians.
+    pub fn rotation(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Matrix::affine(cos, -sin, sin, cos, 0.0, 0.0)
+    }
+
+    /// Creates a matrix scaling by the given factors.
+    pub fn scale(sx: f32, sy: f32) -> Self {
+        Matrix::affine(sx, 0.0, 0.0, sy, 0.0, 0.0)
+    }
+
+    /// Creates a matrix shearing by the given factors.
+    pub fn shear(kx: f32, ky: f32) -> Self {
+        Matrix::affine(1.0, kx, ky, 1.0, 0.0, 0.0)
+    }
+
+    /// Transforms the given point by this matrix.
+    pub fn transform_point(&self, point: Point) -> Point {
+        let m = &self.m;
+        Point::new(
+            m[0][0] * point.x + m[0][1] * point.y + m[0][3],
+            m[1][0] * point.x + m[1][1] * point.y + m[1][3],
+        )
+    }
+
+    /// Returns the inverse of the matrix's 2D affine part, or `None`
+    /// if it is not invertible.  The z and w components are ignored.
+    pub fn inverse(&self) -> Option<Matrix> {
+        let m = &self.m;
+        let (a, b, tx) = (m[0][0], m[0][1], m[0][3]);
+        let (c, d, ty) = (m[1][0], m[1][1], m[1][3]);
+        let det = a * d - b * c;
+        if det == 0.0 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let ia = d * inv_det;
+        let ib = -b * inv_det;
+        let ic = -c * inv_det;
+        let id = a * inv_det;
+        Some(Matrix::affine(
+            ia,
+            ib,
+            ic,
+            id,
+            -(ia * tx + ib * ty),
+            -(ic * tx + id * ty),
+        ))
+    }
+}
+
+impl Default for Matrix {
+    fn default() -> Self {
+        Matrix::identity()
+    }
+}
+
+impl ::std::ops::Mul for Matrix {
+    type Output = Matrix;
+    fn mul(self, rhs: Matrix) -> Matrix {
+        let mut result = [[0.0; 4]; 4];
+        for (r, row) in result.iter_mut().enumerate() {
+            for (c, cell) in row.iter_mut().enumerate() {
+                *cell = (0..4).map(|k| self.m[r][k] * rhs.m[k][c]).sum();
+            }
+        }
+        Matrix { m: result }
+    }
+}
+
+impl From<[[f32; 4]; 4]> for Matrix {
+    fn from(m: [[f32; 4]; 4]) -> Self {
+        Matrix { m: m }
+    }
+}
+
+impl From<Matrix> for [[f32; 4]; 4] {
+    fn from(m: Matrix) -> Self {
+        m.m
+    }
+}
+
+
 /// A RGBA color.
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Color {
@@ -255,6 +383,117 @@ pub enum DrawMode {
     Fill,
 }
 
+/// Specifies how the ends of an arc are joined up.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ArcType {
+    /// The ends are connected to the center, like a slice of pie.
+    Pie,
+    /// The ends are connected to each other.  When filled, this
+    /// is the same as `Closed`.
+    Open,
+    /// The ends are connected to each other with a straight line.
+    Closed,
+}
+
+/// Specifies how the ends of lines are drawn.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineCap {
+    /// The line stops square at its end.
+    Butt,
+    /// The line ends in a half circle.
+    Round,
+    /// The line goes on half its width past its end, and stops square.
+    Square,
+}
+
+/// Specifies how the corners of lines are drawn.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineJoin {
+    /// The outer edges are extended to a point, unless that would
+    /// go past the miter limit, in which case the corner is beveled.
+    Miter,
+    /// The corner is rounded off.
+    Round,
+    /// The corner is cut off straight.
+    Bevel,
+}
+
+/// How lines and outlines, drawn with `DrawMode::Line`, look.
+///
+/// ```rust,ignore
+/// let dotted = LineStyle {
+///     width: 4.0,
+///     cap: LineCap::Round,
+///     dashes: vec![0.0, 10.0],
+///     ..Default::default()
+/// };
+/// graphics::line_ex(ctx, &points, &dotted)?;
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineStyle {
+    /// How wide lines are.
+    pub width: f32,
+    /// How the ends of lines are drawn.  Closed outlines
+    /// have no ends, unless they are dashed.
+    pub cap: LineCap,
+    /// How the corners of lines are drawn.
+    pub join: LineJoin,
+    /// How far, in multiples of half the line width, a mitered
+    /// corner can stick out before it is beveled instead.
+    /// Can't be less than 1.
+    pub miter_limit: f32,
+    /// The lengths of dashes and the gaps between them, in turn,
+    /// repeated along the line.  A list of odd length is repeated
+    /// twice over.  If empty, lines are solid.  Dashes of zero length
+    /// are drawn as just their caps, so they make dots with round or
+    /// square caps and are left out with butt caps.
+    pub dashes: Vec<f32>,
+    /// How far into the dash pattern lines start.
+    pub dash_offset: f32,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        LineStyle {
+            width: 1.0,
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+            miter_limit: 4.0,
+            dashes: Vec::new(),
+            dash_offset: 0.0,
+        }
+    }
+}
+
+impl<'a> From<&'a LineStyle> for t::StrokeOptions {
+    fn from(style: &'a LineStyle) -> Self {
+        let cap = match style.cap {
+            LineCap::Butt => t::LineCap::Butt,
+            LineCap::Round => t::LineCap::Round,
+            LineCap::Square => t::LineCap::Square,
+        };
+        let join = match style.join {
+            LineJoin::Miter => t::LineJoin::Miter,
+            LineJoin::Round => t::LineJoin::Round,
+            LineJoin::Bevel => t::LineJoin::Bevel,
+        };
+        t::StrokeOptions::default()
+            .with_line_width(style.width)
+            .with_line_cap(cap)
+            .with_line_join(join)
+            .with_miter_limit(style.miter_limit.max(1.0))
+    }
+}
+
+/// Specifies how points are drawn by `graphics::points()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PointStyle {
+    /// Points are squares, `point_size` on a side.
+    Square,
+    /// Points are circles, `point_size` across.
+    Round,
+}
+
 /// Specifies what blending method to use when scaling up/down images.
 #[derive(Debug, Copy, Clone)]
 pub enum FilterMode {
@@ -262,6 +501,8 @@ pub enum FilterMode {
     Nearest,
 }
 
+use gfx::preset::blend;
+use gfx::state::{Blend, BlendChannel, BlendValue, Equation, Factor};
 use gfx::texture;
 use gfx::texture::FilterMethod;
 
@@ -284,9 +525,129 @@ impl From<FilterMode> for FilterMethod {
     }
 }
 
+/// Specifies how to blend what is being drawn with what is
+/// already on the render target.
+///
+/// These are the same as Love2D's blend modes, more or less.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// When combining two fragments, add their values together, saturating at 1.0
+    Add,
+    /// When combining two fragments, subtract the source value from the destination value
+    Subtract,
+    /// When combining two fragments, add the value of the source times its
+    /// alpha channel with the value of the destination multiplied by the
+    /// inverse of the source alpha channel.  Has the usual transparency
+    /// effect: mixes the two colors using a fraction of each one specified
+    /// by the alpha of the source.  This is the default.
+    Alpha,
+    /// When combining two fragments, multiply their values together.
+    Multiply,
+    /// When combining two fragments, choose the source value
+    Replace,
+    /// When combining two fragments, add the source value to the destination
+    /// value multiplied by the inverse of the source alpha.  Correct for images
+    /// whose color channels are already multiplied by their alpha.
+    Premultiplied,
+    /// When combining two fragments, choose the lighter value
+    Lighten,
+    /// When combining two fragments, choose the darker value
+    Darken,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::Alpha
+    }
+}
+
+impl From<BlendMode> for Blend {
+    fn from(bm: BlendMode) -> Self {
+        match bm {
+            BlendMode::Add => Blend {
+                color: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::ZeroPlus(BlendValue::SourceAlpha),
+                    destination: Factor::One,
+                },
+                alpha: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::ZeroPlus(BlendValue::SourceAlpha),
+                    destination: Factor::One,
+                },
+            },
+            BlendMode::Subtract => Blend {
+                color: BlendChannel {
+                    equation: Equation::RevSub,
+                    source: Factor::ZeroPlus(BlendValue::SourceAlpha),
+                    destination: Factor::One,
+                },
+                alpha: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::Zero,
+                    destination: Factor::One,
+                },
+            },
+            BlendMode::Alpha => blend::ALPHA,
+            BlendMode::Multiply => Blend {
+                color: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::ZeroPlus(BlendValue::DestColor),
+                    destination: Factor::Zero,
+                },
+                alpha: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::ZeroPlus(BlendValue::DestAlpha),
+                    destination: Factor::Zero,
+                },
+            },
+            BlendMode::Replace => blend::REPLACE,
+            BlendMode::Premultiplied => Blend {
+                color: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::One,
+                    destination: Factor::OneMinus(BlendValue::SourceAlpha),
+                },
+                alpha: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::One,
+                    destination: Factor::OneMinus(BlendValue::SourceAlpha),
+                },
+            },
+            BlendMode::Lighten => Blend {
+                color: BlendChannel {
+                    equation: Equation::Max,
+                    source: Factor::One,
+                    destination: Factor::One,
+                },
+                alpha: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::ZeroPlus(BlendValue::SourceAlpha),
+                    destination: Factor::OneMinus(BlendValue::SourceAlpha),
+                },
+            },
+            BlendMode::Darken => Blend {
+                color: BlendChannel {
+                    equation: Equation::Min,
+                    source: Factor::One,
+                    destination: Factor::One,
+                },
+                alpha: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::ZeroPlus(BlendValue::SourceAlpha),
+                    destination: Factor::OneMinus(BlendValue::SourceAlpha),
+                },
+            },
+        }
+    }
+}
+
 /// Specifies how to wrap textures.
 pub type WrapMode = texture::WrapMode;
 
+/// Specifies how the stencil test compares values.
+pub type StencilCompare = ::gfx::state::Comparison;
+
 
 pub type FullscreenType = sdl2::video::FullscreenType;
 
@@ -366,6 +727,24 @@ mod tests {
         assert_eq!(b2, 0x000000FF);
     }
 
+    #[test]
+    fn test_matrix_transforms() {
+        let p = Point::new(3.0, 4.0);
+        let t = Matrix::translation(10.0, 20.0) * Matrix::scale(2.0, 2.0);
+        assert_eq!(t.transform_point(p), Point::new(16.0, 28.0));
+
+        let back = t.inverse().unwrap().transform_point(Point::new(16.0, 28.0));
+        assert_eq!(back, p);
+
+        let r = Matrix::rotation(::std::f32::consts::PI / 2.0);
+        let rotated = r.transform_point(Point::new(1.0, 0.0));
+        assert!(rotated.x.abs() < 0.0001);
+        assert!((rotated.y - 1.0).abs() < 0.0001);
+
+        assert_eq!(Matrix::scale(0.0, 1.0).inverse(), None);
+        assert_eq!(Matrix::identity() * t, t);
+    }
+
     #[test]
     fn test_rect_scaling() {
         let r1 = Rect::new(0.0, 0.0, 128.0, 128.0);
@@ -0,0 +1,799 @@
+//! Vector paths built out of lines and curves, like those of SVG or
+//! an HTML canvas.
+//!
+//! A `Path` can have any number of subpaths, each started with
+//! `move_to()`.  When it is filled, the fill rule decides which parts
+//! are inside it, so subpaths inside each other can make holes.
+//! Curves are flattened into straight lines when the path is turned
+//! into a `Mesh`, close enough together to be within the given
+//! tolerance of the real curve.
+
+use std::f32::consts::PI;
+
+use lyon::{FlattenedEvent, PathEvent};
+use lyon::tessellation as t;
+
+use super::*;
+
+/// Decides which parts of a filled `Path` are inside it, by how
+/// many times its subpaths wind around each point.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FillRule {
+    /// Points that subpaths wind around a non-zero number of times,
+    /// counting clockwise and counter-clockwise turns against each
+    /// other, are inside.  A subpath inside another one going the
+    /// other way makes a hole; going the same way, it doesn't.
+    NonZero,
+    /// Points that subpaths wind around an odd number of times are
+    /// inside, so every subpath inside another one makes a hole.
+    EvenOdd,
+}
+
+impl Default for FillRule {
+    fn default() -> Self {
+        FillRule::NonZero
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Segment {
+    MoveTo(Point),
+    LineTo(Point),
+    QuadTo(Point, Point),
+    CubicTo(Point, Point, Point),
+    Close,
+}
+
+impl Segment {
+    fn map_points<F: Fn(Point) -> Point>(self, f: F) -> Segment {
+        match self {
+            Segment::MoveTo(to) => Segment::MoveTo(f(to)),
+            Segment::LineTo(to) => Segment::LineTo(f(to)),
+            Segment::QuadTo(control, to) => Segment::QuadTo(f(control), f(to)),
+            Segment::CubicTo(control1, control2, to) => {
+                Segment::CubicTo(f(control1), f(control2), f(to))
+            }
+            Segment::Close => Segment::Close,
+        }
+    }
+}
+
+/// A vector path made of lines and bezier curves, which can be
+/// filled or stroked with `Mesh::new_path()`, `MeshBuilder::path()`
+/// or `graphics::path()`.
+///
+/// ```rust,ignore
+/// // A square with a square hole in it.
+/// let mut path = Path::new();
+/// path.move_to(Point::new(0.0, 0.0))
+///     .line_to(Point::new(100.0, 0.0))
+///     .line_to(Point::new(100.0, 100.0))
+///     .line_to(Point::new(0.0, 100.0))
+///     .close()
+///     .move_to(Point::new(25.0, 25.0))
+///     .line_to(Point::new(25.0, 75.0))
+///     .line_to(Point::new(75.0, 75.0))
+///     .line_to(Point::new(75.0, 25.0))
+///     .close();
+/// graphics::path(ctx, DrawMode::Fill, &path, 0.1)?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Path {
+    segments: Vec<Segment>,
+    start: Point,
+    current: Point,
+    fill_rule: FillRule,
+}
+
+impl Path {
+    /// Creates a new, empty path.
+    pub fn new() -> Path {
+        Path::default()
+    }
+
+    /// Starts a new subpath at the given point.
+    pub fn move_to(&mut self, to: Point) -> &mut Self {
+        self.segments.push(Segment::MoveTo(to));
+        self.start = to;
+        self.current = to;
+        self
+    }
+
+    /// Adds a straight line to the given point.
+    pub fn line_to(&mut self, to: Point) -> &mut Self {
+        self.segments.push(Segment::LineTo(to));
+        self.current = to;
+        self
+    }
+
+    /// Adds a quadratic bezier curve to the given point.
+    pub fn quad_to(&mut self, control: Point, to: Point) -> &mut Self {
+        self.segments.push(Segment::QuadTo(control, to));
+        self.current = to;
+        self
+    }
+
+    /// Adds a cubic bezier curve to the given point.
+    pub fn cubic_to(&mut self, control1: Point, control2: Point, to: Point) -> &mut Self {
+        self.segments.push(Segment::CubicTo(control1, control2, to));
+        self.current = to;
+        self
+    }
+
+    /// Adds an elliptical arc to the given point, the same way as
+    /// SVG's `A` path command.
+    ///
+    /// Of the ellipses with the given radii, rotated by `x_rotation`
+    /// radians, that pass through both points, there are two arcs
+    /// each on two ellipses; `large_arc` picks the longer of them and
+    /// `sweep` the one going clockwise, with the Y axis pointing
+    /// down.  The radii are scaled up if they are too small to reach.
+    pub fn arc_to(
+        &mut self,
+        radii: Point,
+        x_rotation: f32,
+        large_arc: bool,
+        sweep: bool,
+        to: Point,
+    ) -> &mut Self {
+        let from = self.current;
+        if from == to {
+            return self;
+        }
+        let (mut rx, mut ry) = (radii.x.abs(), radii.y.abs());
+        if rx == 0.0 || ry == 0.0 {
+            return self.line_to(to);
+        }
+
+        // Find the center, as in the SVG specification's
+        // "conversion from endpoint to center parameterization".
+        // lyon's `SvgArc` does this too, but doesn't scale up
+        // radii that are too small.
+        let (sin, cos) = x_rotation.sin_cos();
+        let (dx, dy) = ((from.x - to.x) / 2.0, (from.y - to.y) / 2.0);
+        let x1 = cos * dx + sin * dy;
+        let y1 = -sin * dx + cos * dy;
+        let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+        if lambda > 1.0 {
+            rx *= lambda.sqrt();
+            ry *= lambda.sqrt();
+        }
+        let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+        let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+        let mut coefficient = (numerator.max(0.0) / denominator).sqrt();
+        if large_arc == sweep {
+            coefficient = -coefficient;
+        }
+        let cx1 = coefficient * rx * y1 / ry;
+        let cy1 = -coefficient * ry * x1 / rx;
+        let center = Point::new(
+            cos * cx1 - sin * cy1 + (from.x + to.x) / 2.0,
+            sin * cx1 + cos * cy1 + (from.y + to.y) / 2.0,
+        );
+
+        let angle =
+            |ux: f32, uy: f32, vx: f32, vy: f32| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+        let (ux, uy) = ((x1 - cx1) / rx, (y1 - cy1) / ry);
+        let (vx, vy) = ((-x1 - cx1) / rx, (-y1 - cy1) / ry);
+        let start_angle = angle(1.0, 0.0, ux, uy);
+        let mut sweep_angle = angle(ux, uy, vx, vy);
+        if sweep && sweep_angle < 0.0 {
+            sweep_angle += 2.0 * PI;
+        } else if !sweep && sweep_angle > 0.0 {
+            sweep_angle -= 2.0 * PI;
+        }
+
+        let arc = bezier::Arc {
+            center: to_lyon(center),
+            radii: t::math::vec2(rx, ry),
+            start_angle: euclid::Radians::new(start_angle),
+            sweep_angle: euclid::Radians::new(sweep_angle),
+            x_rotation: euclid::Radians::new(x_rotation),
+        };
+        let mut curves = Vec::new();
+        arc.to_quadratic_beziers(&mut |control, end| curves.push((control, end)));
+        if curves.is_empty() {
+            return self.line_to(to);
+        }
+        let last = curves.len() - 1;
+        for (i, (control, end)) in curves.into_iter().enumerate() {
+            // Land exactly on `to`, rather than wherever
+            // rounding errors put the end of the last curve.
+            let end = if i == last { to } else { Point::new(end.x, end.y) };
+            self.quad_to(Point::new(control.x, control.y), end);
+        }
+        self
+    }
+
+    /// Closes the current subpath with a straight line back to where
+    /// it started.  Anything added after this, without a `move_to()`
+    /// first, starts a new subpath there.
+    pub fn close(&mut self) -> &mut Self {
+        self.segments.push(Segment::Close);
+        self.current = self.start;
+        self
+    }
+
+    /// Transforms every point of the path by the given matrix.
+    pub fn transform(&mut self, matrix: &Matrix) -> &mut Self {
+        for segment in &mut self.segments {
+            *segment = segment.map_points(|p| matrix.transform_point(p));
+        }
+        self.start = matrix.transform_point(self.start);
+        self.current = matrix.transform_point(self.current);
+        self
+    }
+
+    /// Sets how the path decides what is inside it when filled.
+    /// Default: `FillRule::NonZero`.
+    pub fn set_fill_rule(&mut self, fill_rule: FillRule) -> &mut Self {
+        self.fill_rule = fill_rule;
+        self
+    }
+
+    /// Returns how the path decides what is inside it when filled.
+    pub fn get_fill_rule(&self) -> FillRule {
+        self.fill_rule
+    }
+
+    /// Returns the point the next segment will start from.
+    pub fn get_current_point(&self) -> Point {
+        self.current
+    }
+
+    /// Returns true if nothing has been added to the path.
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Turns the path into lists of points, one per subpath,
+    /// with curves flattened to within `tolerance` by lyon.
+    fn flatten(&self, tolerance: f32) -> Vec<Contour> {
+        let mut builder = flattening_builder(tolerance);
+        for segment in &self.segments {
+            match *segment {
+                Segment::MoveTo(to) => builder.move_to(to_lyon(to)),
+                Segment::LineTo(to) => builder.line_to(to_lyon(to)),
+                Segment::QuadTo(control, to) => {
+                    builder.quadratic_bezier_to(to_lyon(control), to_lyon(to))
+                }
+                Segment::CubicTo(control1, control2, to) => {
+                    builder.cubic_bezier_to(to_lyon(control1), to_lyon(control2), to_lyon(to))
+                }
+                Segment::Close => builder.close(),
+            }
+        }
+
+        let mut contours = Vec::new();
+        let mut contour: Option<Contour> = None;
+        let mut current = Point::default();
+        for event in builder.build().iter() {
+            match event {
+                PathEvent::MoveTo(to) => {
+                    contours.extend(contour.take());
+                    current = Point::new(to.x, to.y);
+                }
+                PathEvent::LineTo(to) => {
+                    let to = Point::new(to.x, to.y);
+                    let points = &mut contour.get_or_insert_with(|| Contour::new(current)).points;
+                    points.push(to);
+                    current = to;
+                }
+                PathEvent::Close => {
+                    if let Some(mut finished) = contour.take() {
+                        finished.closed = true;
+                        current = finished.points[0];
+                        contours.push(finished);
+                    }
+                }
+                // The builder has flattened these into lines already.
+                PathEvent::QuadraticTo(..) | PathEvent::CubicTo(..) => {}
+            }
+        }
+        contours.extend(contour);
+        contours.retain(|c| c.points.len() > 1);
+        contours
+    }
+}
+
+/// A flattened subpath.
+#[derive(Debug, Clone, PartialEq)]
+struct Contour {
+    points: Vec<Point>,
+    closed: bool,
+}
+
+impl Contour {
+    fn new(start: Point) -> Contour {
+        Contour {
+            points: vec![start],
+            closed: false,
+        }
+    }
+}
+
+/// A key to tell points apart by, since `f32`s can't be hashed.
+fn point_key(p: Point) -> (u32, u32) {
+    // Adding zero turns -0.0 into 0.0, so they're the same point.
+    ((p.x + 0.0).to_bits(), (p.y + 0.0).to_bits())
+}
+
+/// Whether `a` comes before `b` going up the Y axis, or along the
+/// X axis where they're level.
+fn comes_before(a: Point, b: Point) -> bool {
+    a.y < b.y || (a.y == b.y && a.x < b.x)
+}
+
+/// Which side of the line from `p` to `q` `r` is on: positive to
+/// the left with the Y axis pointing up, negative to the right, and
+/// zero on the line.
+fn side(p: Point, q: Point, r: Point) -> f32 {
+    (q.x - p.x) * (r.y - p.y) - (q.y - p.y) * (r.x - p.x)
+}
+
+/// Whether `r`, on the line through `p` and `q`, is strictly between them.
+fn inside_segment(p: Point, q: Point, r: Point) -> bool {
+    r != p && r != q && r.x >= p.x.min(q.x) && r.x <= p.x.max(q.x) &&
+        r.y >= p.y.min(q.y) && r.y <= p.y.max(q.y)
+}
+
+/// Cuts the edges of the closed polygons into pieces that only
+/// meet at their ends, wherever they cross or touch each other.
+/// The pieces keep the direction of the edge they're from.
+fn split_edges(polygons: &[Vec<Point>]) -> Vec<(Point, Point)> {
+    let edges: Vec<(Point, Point)> = polygons
+        .iter()
+        .flat_map(|points| {
+            (0..points.len()).map(move |i| (points[i], points[(i + 1) % points.len()]))
+        })
+        .collect();
+    let mut cuts: Vec<Vec<Point>> = edges.iter().map(|&(a, b)| vec![a, b]).collect();
+
+    // Sweep across the edges from left to right, only comparing
+    // those whose spans along the X axis overlap.
+    let mut order: Vec<usize> = (0..edges.len()).collect();
+    order.sort_by(|&i, &j| {
+        let (a, b) = (edges[i], edges[j]);
+        a.0.x.min(a.1.x).partial_cmp(&b.0.x.min(b.1.x)).unwrap_or(cmp::Ordering::Equal)
+    });
+    for (n, &i) in order.iter().enumerate() {
+        let (p1, p2) = edges[i];
+        let right = p1.x.max(p2.x);
+        for &j in &order[n + 1..] {
+            let (q1, q2) = edges[j];
+            if q1.x.min(q2.x) > right {
+                break;
+            }
+            if p1.y.max(p2.y) < q1.y.min(q2.y) || q1.y.max(q2.y) < p1.y.min(p2.y) {
+                continue;
+            }
+            let (d1, d2) = (side(q1, q2, p1), side(q1, q2, p2));
+            let (d3, d4) = (side(p1, p2, q1), side(p1, p2, q2));
+            if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+                // They cross; both are cut at the very same point.
+                let t = d1 / (d1 - d2);
+                let cross = Point::new(p1.x + (p2.x - p1.x) * t, p1.y + (p2.y - p1.y) * t);
+                cuts[i].push(cross);
+                cuts[j].push(cross);
+                continue;
+            }
+            // Ends lying on the other edge, which includes edges
+            // that lie along each other.
+            for &(d, end, (a, b), cut) in &[
+                (d1, p1, (q1, q2), j),
+                (d2, p2, (q1, q2), j),
+                (d3, q1, (p1, p2), i),
+                (d4, q2, (p1, p2), i),
+            ] {
+                if d == 0.0 && inside_segment(a, b, end) {
+                    cuts[cut].push(end);
+                }
+            }
+        }
+    }
+
+    let mut pieces = Vec::new();
+    for (&(a, b), mut points) in edges.iter().zip(cuts) {
+        let along = |p: &Point| (p.x - a.x) * (b.x - a.x) + (p.y - a.y) * (b.y - a.y);
+        points.sort_by(|p, q| along(p).partial_cmp(&along(q)).unwrap_or(cmp::Ordering::Equal));
+        points.dedup();
+        pieces.extend(points.windows(2).map(|pair| (pair[0], pair[1])));
+    }
+    pieces
+}
+
+/// A piece of the edges of a filled path, with pieces lying on top
+/// of each other merged into one.  `from` comes before `to`, and
+/// `count` is how many more of the pieces go from `from` to `to`
+/// than the other way.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Edge {
+    from: Point,
+    to: Point,
+    count: i32,
+}
+
+/// Merges pieces of edges that lie on top of each other.
+fn merge_edges(pieces: &[(Point, Point)]) -> Vec<Edge> {
+    let mut edges: Vec<Edge> = Vec::new();
+    let mut index = HashMap::new();
+    for &(a, b) in pieces {
+        let (from, to, count) = if comes_before(a, b) {
+            (a, b, 1)
+        } else {
+            (b, a, -1)
+        };
+        let key = (point_key(from), point_key(to));
+        let i = *index.entry(key).or_insert_with(|| {
+            edges.push(Edge {
+                from: from,
+                to: to,
+                count: 0,
+            });
+            edges.len() - 1
+        });
+        edges[i].count += count;
+    }
+    edges.retain(|e| e.count != 0);
+    edges
+}
+
+/// Finds the outlines of the parts of the closed polygons that they
+/// wind around a non-zero number of times, which give the same shape
+/// filled by the even-odd rule.
+///
+/// The edges are cut up wherever they cross, so every piece has one
+/// winding number on each side, and the pieces with zero on just one
+/// side are kept.  Finding the winding number next to a piece only
+/// looks at the edges in the same horizontal band as it.
+fn nonzero_outlines(polygons: &[Vec<Point>]) -> Vec<Vec<Point>> {
+    let edges = merge_edges(&split_edges(polygons));
+    if edges.is_empty() {
+        return Vec::new();
+    }
+
+    let bottom = edges.iter().fold(edges[0].from.y, |y, e| y.min(e.from.y));
+    let top = edges.iter().fold(edges[0].to.y, |y, e| y.max(e.to.y));
+    let band_count = ((edges.len() as f32).sqrt().ceil() as usize).max(1);
+    let band_height = (top - bottom) / band_count as f32;
+    let band_of = |y: f32| {
+        if band_height > 0.0 {
+            cmp::min(((y - bottom) / band_height) as usize, band_count - 1)
+        } else {
+            0
+        }
+    };
+    let mut bands = vec![Vec::new(); band_count];
+    for (i, e) in edges.iter().enumerate() {
+        // Level edges never cross the rays the winding numbers
+        // are counted along.
+        if e.from.y < e.to.y {
+            for band in &mut bands[band_of(e.from.y)..band_of(e.to.y) + 1] {
+                band.push(i);
+            }
+        }
+    }
+
+    let mut outline = Vec::new();
+    for (i, e) in edges.iter().enumerate() {
+        let middle = Point::new((e.from.x + e.to.x) / 2.0, (e.from.y + e.to.y) / 2.0);
+        // The winding number just above and to the right of the
+        // middle, counted along a ray going right from it.  Ends
+        // count as just above themselves, so rays that go through
+        // them cross the edges meeting there the right number of times.
+        let winding: i32 = bands[band_of(middle.y)]
+            .iter()
+            .filter(|&&j| j != i)
+            .map(|&j| &edges[j])
+            .filter(|other| {
+                other.from.y <= middle.y && middle.y < other.to.y &&
+                    side(other.from, other.to, middle) > 0.0
+            })
+            .map(|other| other.count)
+            .sum();
+        // The winding number goes up by `count` crossing the edge
+        // from its right to its left.
+        let (left, right) = if e.from.y < e.to.y {
+            (winding + e.count, winding)
+        } else {
+            (winding, winding - e.count)
+        };
+        // Pointing them with the filled side on the left makes
+        // them join up into loops.
+        match (left != 0, right != 0) {
+            (true, false) => outline.push((e.from, e.to)),
+            (false, true) => outline.push((e.to, e.from)),
+            _ => (),
+        }
+    }
+
+    let mut starting_at: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    for (i, &(from, _)) in outline.iter().enumerate() {
+        starting_at.entry(point_key(from)).or_insert_with(Vec::new).push(i);
+    }
+    let mut used = vec![false; outline.len()];
+    let mut loops = Vec::new();
+    for first in 0..outline.len() {
+        let mut points = Vec::new();
+        let mut next = Some(first);
+        while let Some(i) = next {
+            if used[i] {
+                break;
+            }
+            used[i] = true;
+            points.push(outline[i].0);
+            next = starting_at
+                .get(&point_key(outline[i].1))
+                .and_then(|starts| starts.iter().cloned().find(|&j| !used[j]));
+        }
+        if points.len() > 2 {
+            loops.push(points);
+        }
+    }
+    loops
+}
+
+/// Turns the contours into closed polygons for lyon to fill by the
+/// even-odd rule, which is all lyon can do, so that it fills the
+/// parts that are inside them under the fill rule.
+fn fill_contours(contours: &[Contour], fill_rule: FillRule) -> Vec<Vec<Point>> {
+    let polygons: Vec<Vec<Point>> = contours
+        .iter()
+        .map(|c| {
+            let mut points = c.points.clone();
+            points.dedup();
+            if points.len() > 1 && points[0] == points[points.len() - 1] {
+                points.pop();
+            }
+            points
+        })
+        // Lines have no inside.
+        .filter(|points| points.len() > 2)
+        .collect();
+    match fill_rule {
+        FillRule::EvenOdd => polygons,
+        FillRule::NonZero => nonzero_outlines(&polygons),
+    }
+}
+
+impl Mesh {
+    /// Create a new mesh for a path, with curves flattened to within
+    /// `tolerance`.  Outlines are as wide as the current line width.
+    pub fn new_path(
+        ctx: &mut Context,
+        mode: DrawMode,
+        path: &Path,
+        tolerance: f32,
+    ) -> GameResult<Mesh> {
+        MeshBuilder::new()
+            .set_line_style(&ctx.gfx_context.line_style)
+            .path(mode, path, tolerance, WHITE)
+            .build(ctx)
+    }
+}
+
+impl MeshBuilder {
+    /// Adds a path, with curves flattened to within `tolerance`.
+    /// Filling it fills every subpath, closed or not, with holes
+    /// decided by its fill rule; outlining it outlines each subpath.
+    pub fn path(
+        &mut self,
+        mode: DrawMode,
+        path: &Path,
+        tolerance: f32,
+        color: Color,
+    ) -> &mut Self {
+        let contours = path.flatten(tolerance);
+        match mode {
+            DrawMode::Fill => {
+                let contours = fill_contours(&contours, path.fill_rule);
+                if contours.is_empty() {
+                    return self;
+                }
+                self.tessellate(color, |buffers, vertex_builder| {
+                    let mut events = Vec::new();
+                    for points in contours {
+                        let mut points = points.iter().map(|p| t::math::point(p.x, p.y));
+                        events.extend(points.next().map(FlattenedEvent::MoveTo));
+                        events.extend(points.map(FlattenedEvent::LineTo));
+                        events.push(FlattenedEvent::Close);
+                    }
+                    let builder = &mut t::BuffersBuilder::new(buffers, vertex_builder);
+                    let options = t::FillOptions::default();
+                    t::FillTessellator::new()
+                        .tessellate_flattened_path(events.into_iter(), &options, builder)
+                        .map(|_| ())
+                        .map_err(|_| "Could not tessellate path".to_owned())
+                })
+            }
+            DrawMode::Line => {
+                // Each subpath starts the dash pattern over.
+                let style = self.line_style.clone();
+                for contour in &contours {
+                    self.stroke_polyline(&contour.points, contour.closed, &style, color);
+                }
+                self
+            }
+        }
+    }
+}
+
+/// Draws a path, with curves flattened to within `tolerance`.
+pub fn path(ctx: &mut Context, mode: DrawMode, path: &Path, tolerance: f32) -> GameResult<()> {
+    let m = Mesh::new_path(ctx, mode, path, tolerance)?;
+    m.draw(ctx, Point::default(), 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(path: &mut Path, x: f32, y: f32, size: f32, clockwise: bool) {
+        let mut corners = vec![(size, 0.0), (size, size), (0.0, size)];
+        if !clockwise {
+            corners.reverse();
+        }
+        path.move_to(Point::new(x, y));
+        for (dx, dy) in corners {
+            path.line_to(Point::new(x + dx, y + dy));
+        }
+        path.close();
+    }
+
+    #[test]
+    fn test_flatten_subpaths() {
+        let mut path = Path::new();
+        path.move_to(Point::new(0.0, 0.0))
+            .line_to(Point::new(10.0, 0.0))
+            .close()
+            .line_to(Point::new(0.0, 10.0))
+            .move_to(Point::new(5.0, 5.0))
+            .quad_to(Point::new(10.0, 10.0), Point::new(15.0, 5.0))
+            .move_to(Point::new(20.0, 20.0));
+        let contours = path.flatten(0.1);
+        assert_eq!(contours.len(), 3);
+        assert!(contours[0].closed);
+        // Closing goes back to the start, so the next line starts there.
+        assert_eq!(contours[1].points, vec![Point::new(0.0, 0.0), Point::new(0.0, 10.0)]);
+        assert!(!contours[1].closed);
+        assert!(contours[2].points.len() > 2);
+        assert_eq!(contours[2].points[contours[2].points.len() - 1], Point::new(15.0, 5.0));
+    }
+
+    #[test]
+    fn test_arc_to() {
+        // Half a circle of radius 10 around (10, 0).
+        let mut path = Path::new();
+        path.move_to(Point::new(0.0, 0.0))
+            .arc_to(Point::new(10.0, 10.0), 0.0, false, true, Point::new(20.0, 0.0));
+        assert_eq!(path.get_current_point(), Point::new(20.0, 0.0));
+        let points = &path.flatten(0.01)[0].points;
+        for p in points {
+            let d = ((p.x - 10.0).powi(2) + p.y.powi(2)).sqrt();
+            assert!((d - 10.0).abs() < 0.05);
+            // Clockwise with Y down goes through negative Y.
+            assert!(p.y <= 0.001);
+        }
+
+        // Radii too small to reach are scaled up.
+        let mut path = Path::new();
+        path.move_to(Point::new(0.0, 0.0))
+            .arc_to(Point::new(1.0, 1.0), 0.0, false, false, Point::new(20.0, 0.0));
+        let points = &path.flatten(0.01)[0].points;
+        assert!(points.iter().any(|p| (p.y - 10.0).abs() < 0.05));
+    }
+
+    /// Whether `p` is inside the outlines, filled by the even-odd
+    /// rule the way lyon fills them.
+    fn filled(outlines: &[Vec<Point>], p: Point) -> bool {
+        let mut inside = false;
+        for points in outlines {
+            for (i, &a) in points.iter().enumerate() {
+                let b = points[(i + 1) % points.len()];
+                if (a.y > p.y) != (b.y > p.y) &&
+                    p.x < a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x)
+                {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+
+    fn check(path: &Path, fill_rule: FillRule, inside: &[(f32, f32)], outside: &[(f32, f32)]) {
+        let outlines = fill_contours(&path.flatten(0.1), fill_rule);
+        for &(x, y) in inside {
+            assert!(filled(&outlines, Point::new(x, y)), "({}, {}) is empty", x, y);
+        }
+        for &(x, y) in outside {
+            assert!(!filled(&outlines, Point::new(x, y)), "({}, {}) is filled", x, y);
+        }
+    }
+
+    #[test]
+    fn test_fill_rules() {
+        let mut same_way = Path::new();
+        square(&mut same_way, 0.0, 0.0, 100.0, true);
+        square(&mut same_way, 25.0, 25.0, 50.0, true);
+        check(&same_way, FillRule::NonZero, &[(10.0, 10.0), (50.0, 50.0)], &[(150.0, 50.0)]);
+        check(&same_way, FillRule::EvenOdd, &[(10.0, 10.0)], &[(50.0, 50.0)]);
+
+        let mut opposite = Path::new();
+        square(&mut opposite, 0.0, 0.0, 100.0, true);
+        square(&mut opposite, 25.0, 25.0, 50.0, false);
+        // An island inside the hole, going the first way again.
+        square(&mut opposite, 40.0, 40.0, 20.0, true);
+        check(&opposite, FillRule::NonZero, &[(10.0, 10.0), (50.0, 50.0)], &[(30.0, 30.0)]);
+
+        // Where squares going the same way overlap is filled once.
+        let mut overlapping = Path::new();
+        square(&mut overlapping, 0.0, 0.0, 100.0, true);
+        square(&mut overlapping, 50.0, 50.0, 100.0, true);
+        check(
+            &overlapping,
+            FillRule::NonZero,
+            &[(25.0, 25.0), (75.0, 75.0), (125.0, 125.0)],
+            &[(125.0, 25.0), (25.0, 125.0)],
+        );
+        check(&overlapping, FillRule::EvenOdd, &[(25.0, 25.0)], &[(75.0, 75.0)]);
+        let outlines = fill_contours(&overlapping.flatten(0.1), FillRule::NonZero);
+        assert_eq!(outlines.len(), 1);
+        assert_eq!(outlines[0].len(), 8);
+        // Nesting inside one of them still works.
+        square(&mut overlapping, 10.0, 10.0, 20.0, true);
+        check(&overlapping, FillRule::NonZero, &[(20.0, 20.0), (75.0, 75.0)], &[]);
+
+        // Going opposite ways, they cancel out where they overlap.
+        let mut cancelling = Path::new();
+        square(&mut cancelling, 0.0, 0.0, 100.0, true);
+        square(&mut cancelling, 50.0, 50.0, 100.0, false);
+        check(
+            &cancelling,
+            FillRule::NonZero,
+            &[(25.0, 25.0), (125.0, 125.0)],
+            &[(75.0, 75.0), (125.0, 25.0)],
+        );
+
+        // Squares sharing an edge make one rectangle.
+        let mut side_by_side = Path::new();
+        square(&mut side_by_side, 0.0, 0.0, 50.0, true);
+        square(&mut side_by_side, 50.0, 0.0, 50.0, true);
+        check(&side_by_side, FillRule::NonZero, &[(25.0, 25.0), (75.0, 25.0)], &[(125.0, 25.0)]);
+        let outlines = fill_contours(&side_by_side.flatten(0.1), FillRule::NonZero);
+        assert_eq!(outlines.len(), 1);
+
+        // A subpath that ends where it started.
+        let mut closed = Path::new();
+        square(&mut closed, 0.0, 0.0, 100.0, true);
+        closed
+            .move_to(Point::new(25.0, 25.0))
+            .line_to(Point::new(75.0, 25.0))
+            .line_to(Point::new(75.0, 75.0))
+            .line_to(Point::new(25.0, 75.0))
+            .line_to(Point::new(25.0, 25.0))
+            .close();
+        check(&closed, FillRule::NonZero, &[(50.0, 50.0)], &[]);
+
+        // A star winds twice around its middle.
+        let mut star = Path::new();
+        star.move_to(Point::new(0.0, -100.0));
+        for i in 1..5 {
+            let angle = (i * 2) as f32 * PI * 2.0 / 5.0;
+            star.line_to(Point::new(100.0 * angle.sin(), -100.0 * angle.cos()));
+        }
+        star.close();
+        check(&star, FillRule::NonZero, &[(0.0, 0.0), (0.0, -70.0)], &[(0.0, 90.0)]);
+        check(&star, FillRule::EvenOdd, &[(0.0, -70.0)], &[(0.0, 0.0)]);
+
+        // Both halves of a figure of eight wind around once,
+        // going opposite ways.
+        let mut eight = Path::new();
+        eight
+            .move_to(Point::new(0.0, 0.0))
+            .line_to(Point::new(10.0, 10.0))
+            .line_to(Point::new(10.0, 0.0))
+            .line_to(Point::new(0.0, 10.0))
+            .close();
+        check(&eight, FillRule::NonZero, &[(2.0, 5.0), (8.0, 5.0)], &[(5.0, 2.0), (5.0, 8.0)]);
+    }
+}
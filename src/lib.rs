@@ -96,6 +96,8 @@
 
 extern crate sdl2;
 extern crate app_dirs;
+extern crate base64;
+extern crate flate2;
 #[macro_use]
 extern crate gfx;
 extern crate gfx_device_gl;
@@ -105,8 +107,10 @@ extern crate rand;
 extern crate rodio;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 extern crate rusttype;
 extern crate toml;
+extern crate xml;
 extern crate zip;
 extern crate lyon;
 extern crate euclid;